@@ -1,30 +1,219 @@
 use dotenv::dotenv;
 use serde::Deserialize;
+use std::net::IpAddr;
+use std::str::FromStr;
 use std::sync::OnceLock;
 use std::{env, sync::Arc};
 
+// Allowed requests per window, written as "<limit>/<window seconds>"
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct RateLimitQuota {
+    pub limit: u32,
+    pub window: u64,
+}
+
+impl FromStr for RateLimitQuota {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (limit, window) = s
+            .split_once('/')
+            .ok_or_else(|| format!("Invalid rate limit quota: {s}"))?;
+
+        let quota = RateLimitQuota {
+            limit: limit.trim().parse().map_err(|e| format!("{s}: {e}"))?,
+            window: window.trim().parse().map_err(|e| format!("{s}: {e}"))?,
+        };
+        if quota.window == 0 {
+            return Err(format!("Rate limit window must be positive: {s}"));
+        }
+        Ok(quota)
+    }
+}
+
+// Proxy whose forwarding headers are believed, an address or a CIDR range
+// such as "10.0.0.0/8"
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct TrustedProxy {
+    pub network: IpAddr,
+    pub prefix: u8,
+}
+
+impl TrustedProxy {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for TrustedProxy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+
+        let network = address
+            .trim()
+            .parse::<IpAddr>()
+            .map_err(|e| format!("Invalid trusted proxy {s}: {e}"))?
+            .to_canonical();
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("Invalid trusted proxy prefix: {s}"))?,
+            None => max_prefix,
+        };
+        Ok(TrustedProxy { network, prefix })
+    }
+}
+
+// Who may start a registration. Invitations work under every policy.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationPolicy {
+    Open,
+    InviteOnly,
+    // Only emails of REGISTRATION_ALLOWED_DOMAINS
+    AllowedDomains,
+}
+
+impl FromStr for RegistrationPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "open" => Ok(RegistrationPolicy::Open),
+            "invite_only" => Ok(RegistrationPolicy::InviteOnly),
+            "allowed_domains" => Ok(RegistrationPolicy::AllowedDomains),
+            _ => Err(format!(
+                "Invalid registration policy: {s}, expected open, \
+                 invite_only or allowed_domains"
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub database_url: String,
     pub server_host: String,
     pub server_port: u16,
-    pub jwt_access_secret: String,
+    pub jwt_access_secret: Option<String>,
+    pub jwt_keys_dir: Option<String>,
+    pub jwt_signing_kid: Option<String>,
     pub jwt_access_expires: i64,
     pub jwt_refresh_expires: i64,
+    pub token_denylist_sync_interval: u64,
 
+    pub email_transport: String,
+    pub email_file_dir: String,
     pub email_host: String,
     pub email_user: String,
     pub email_password: String,
     pub email_port: u16,
     pub email_from: String,
+    pub dkim_selector: Option<String>,
+    pub dkim_domain: Option<String>,
+    pub dkim_private_key_path: Option<String>,
+    pub email_daily_quota: i64,
+    pub email_attachments_max_size: usize,
+    pub email_outbox_poll_interval: u64,
+    pub email_outbox_max_attempts: i32,
+    pub email_outbox_retry_base: i64,
+    pub email_templates_dir: String,
+    pub email_default_locale: String,
+    pub email_campaign_batch_size: i64,
+    pub email_campaign_batch_interval: u64,
+    pub email_unsubscribe_url: String,
+    pub email_recipient_allow: Vec<String>,
+    pub email_recipient_deny: Vec<String>,
+
+    pub argon2_memory_cost: u32,
+    pub argon2_time_cost: u32,
+    pub argon2_parallelism: u32,
+
+    pub login_free_attempts: i32,
+    pub login_delay_max: i64,
+    pub login_lockout_threshold: i32,
+    pub login_ip_lockout_threshold: i32,
+    pub login_lockout_duration: i64,
+
+    pub rate_limit_backend: String,
+    pub rate_limit_api: RateLimitQuota,
+    pub rate_limit_login: RateLimitQuota,
+    pub rate_limit_register: RateLimitQuota,
+    pub rate_limit_email: RateLimitQuota,
+    pub trusted_proxies: Vec<TrustedProxy>,
+
+    pub mfa_issuer: String,
+    pub mfa_challenge_expires: i64,
+
+    pub password_reset_url: String,
+    pub password_reset_expires: i64,
+
+    pub registration_code_max_attempts: i32,
+    pub registration_resend_cooldown: i64,
+    pub registration_policy: RegistrationPolicy,
+    pub registration_allowed_domains: Vec<String>,
+
+    pub invitation_url: String,
+    pub invitation_expires: i64,
+
+    pub username_min_length: usize,
+    pub username_max_length: usize,
+    pub username_allowed_symbols: String,
+    pub username_reserved: Vec<String>,
+    pub username_blocklist_path: Option<String>,
+
+    pub job_scheduler_interval: u64,
+    pub job_purge_registrations_schedule: String,
+    pub job_purge_refresh_tokens_schedule: String,
 }
 
 static CONFIG: OnceLock<Arc<Config>> = OnceLock::new();
 
+// Comma separated list, empty when the variable is not set
+fn list_var(name: &str) -> Vec<String> {
+    list_var_or(name, "")
+}
+
+fn list_var_or(name: &str, default: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_else(|_| default.to_string())
+        .split(',')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
 impl Config {
     pub fn init() -> Result<(), Box<dyn std::error::Error>> {
         dotenv().expect("Failed to load .env file");
+        Self::init_from_env()
+    }
 
+    /// Like `init`, but reads the process environment only, `.env` is left
+    /// alone.
+    pub fn init_from_env() -> Result<(), Box<dyn std::error::Error>> {
         let config = Config {
             database_url: env::var("DATABASE_URL")?,
             server_port: env::var("SERVER_PORT")
@@ -32,22 +221,180 @@ impl Config {
                 .parse()?,
             server_host: env::var("SERVER_HOST")
                 .unwrap_or_else(|_| "127.0.0.1".to_string()),
-            jwt_access_secret: env::var("JWT_ACCESS_SECRET")?,
+            jwt_access_secret: env::var("JWT_ACCESS_SECRET").ok(),
+            jwt_keys_dir: env::var("JWT_KEYS_DIR").ok(),
+            jwt_signing_kid: env::var("JWT_SIGNING_KID").ok(),
             jwt_access_expires: env::var("JWT_ACCESS_EXPIRES")
                 .unwrap_or("60".to_string())
                 .parse()?,
             jwt_refresh_expires: env::var("JWT_REFRESH_EXPIRES")
                 .unwrap_or("3600".to_string())
                 .parse()?,
-            email_host: env::var("EMAIL_HOST")?,
-            email_user: env::var("EMAIL_USER")?,
-            email_password: env::var("EMAIL_PASSWORD")?,
+            token_denylist_sync_interval: env::var(
+                "TOKEN_DENYLIST_SYNC_INTERVAL",
+            )
+            .unwrap_or("10".to_string())
+            .parse()?,
+            email_transport: env::var("EMAIL_TRANSPORT")
+                .unwrap_or("smtp".to_string()),
+            email_file_dir: env::var("EMAIL_FILE_DIR")
+                .unwrap_or("emails".to_string()),
+            // Only needed by the smtp transport
+            email_host: env::var("EMAIL_HOST").unwrap_or_default(),
+            email_user: env::var("EMAIL_USER").unwrap_or_default(),
+            email_password: env::var("EMAIL_PASSWORD").unwrap_or_default(),
             email_port: env::var("EMAIL_PORT")
                 .unwrap_or("465".to_string())
                 .parse()?,
             email_from: env::var("EMAIL_FROM")?,
+            dkim_selector: env::var("DKIM_SELECTOR").ok(),
+            dkim_domain: env::var("DKIM_DOMAIN").ok(),
+            dkim_private_key_path: env::var("DKIM_PRIVATE_KEY_PATH").ok(),
+            email_daily_quota: env::var("EMAIL_DAILY_QUOTA")
+                .unwrap_or("100".to_string())
+                .parse()?,
+            email_attachments_max_size: env::var("EMAIL_ATTACHMENTS_MAX_SIZE")
+                .unwrap_or("10485760".to_string())
+                .parse()?,
+            email_outbox_poll_interval: env::var("EMAIL_OUTBOX_POLL_INTERVAL")
+                .unwrap_or("5".to_string())
+                .parse()?,
+            email_outbox_max_attempts: env::var("EMAIL_OUTBOX_MAX_ATTEMPTS")
+                .unwrap_or("8".to_string())
+                .parse()?,
+            email_outbox_retry_base: env::var("EMAIL_OUTBOX_RETRY_BASE")
+                .unwrap_or("30".to_string())
+                .parse()?,
+            email_templates_dir: env::var("EMAIL_TEMPLATES_DIR")
+                .unwrap_or("templates/email".to_string()),
+            email_default_locale: env::var("EMAIL_DEFAULT_LOCALE")
+                .unwrap_or("en".to_string())
+                .to_lowercase(),
+            email_campaign_batch_size: env::var("EMAIL_CAMPAIGN_BATCH_SIZE")
+                .unwrap_or("50".to_string())
+                .parse()?,
+            email_campaign_batch_interval: env::var(
+                "EMAIL_CAMPAIGN_BATCH_INTERVAL",
+            )
+            .unwrap_or("60".to_string())
+            .parse()?,
+            email_unsubscribe_url: env::var("EMAIL_UNSUBSCRIBE_URL")
+                .unwrap_or_else(|_| {
+                    "http://localhost:8080/api/unsubscribe".to_string()
+                }),
+            email_recipient_allow: list_var("EMAIL_RECIPIENT_ALLOW"),
+            email_recipient_deny: list_var("EMAIL_RECIPIENT_DENY"),
+            argon2_memory_cost: env::var("ARGON2_MEMORY_COST")
+                .unwrap_or("19456".to_string())
+                .parse()?,
+            argon2_time_cost: env::var("ARGON2_TIME_COST")
+                .unwrap_or("2".to_string())
+                .parse()?,
+            argon2_parallelism: env::var("ARGON2_PARALLELISM")
+                .unwrap_or("1".to_string())
+                .parse()?,
+            login_free_attempts: env::var("LOGIN_FREE_ATTEMPTS")
+                .unwrap_or("3".to_string())
+                .parse()?,
+            login_delay_max: env::var("LOGIN_DELAY_MAX")
+                .unwrap_or("60".to_string())
+                .parse()?,
+            login_lockout_threshold: env::var("LOGIN_LOCKOUT_THRESHOLD")
+                .unwrap_or("10".to_string())
+                .parse()?,
+            login_ip_lockout_threshold: env::var("LOGIN_IP_LOCKOUT_THRESHOLD")
+                .unwrap_or("50".to_string())
+                .parse()?,
+            login_lockout_duration: env::var("LOGIN_LOCKOUT_DURATION")
+                .unwrap_or("900".to_string())
+                .parse()?,
+            rate_limit_backend: env::var("RATE_LIMIT_BACKEND")
+                .unwrap_or_else(|_| "memory".to_string()),
+            rate_limit_api: env::var("RATE_LIMIT_API")
+                .unwrap_or("300/60".to_string())
+                .parse()?,
+            rate_limit_login: env::var("RATE_LIMIT_LOGIN")
+                .unwrap_or("10/60".to_string())
+                .parse()?,
+            rate_limit_register: env::var("RATE_LIMIT_REGISTER")
+                .unwrap_or("5/300".to_string())
+                .parse()?,
+            rate_limit_email: env::var("RATE_LIMIT_EMAIL")
+                .unwrap_or("20/3600".to_string())
+                .parse()?,
+            trusted_proxies: list_var("TRUSTED_PROXIES")
+                .iter()
+                .map(|proxy| proxy.parse())
+                .collect::<Result<_, _>>()?,
+            mfa_issuer: env::var("MFA_ISSUER")
+                .unwrap_or_else(|_| "Admin Panel".to_string()),
+            mfa_challenge_expires: env::var("MFA_CHALLENGE_EXPIRES")
+                .unwrap_or("300".to_string())
+                .parse()?,
+            password_reset_url: env::var("PASSWORD_RESET_URL").unwrap_or_else(
+                |_| "http://localhost:5173/password/reset".to_string(),
+            ),
+            password_reset_expires: env::var("PASSWORD_RESET_EXPIRES")
+                .unwrap_or("3600".to_string())
+                .parse()?,
+            registration_code_max_attempts: env::var(
+                "REGISTRATION_CODE_MAX_ATTEMPTS",
+            )
+            .unwrap_or("5".to_string())
+            .parse()?,
+            registration_resend_cooldown: env::var(
+                "REGISTRATION_RESEND_COOLDOWN",
+            )
+            .unwrap_or("60".to_string())
+            .parse()?,
+            registration_policy: env::var("REGISTRATION_POLICY")
+                .unwrap_or("open".to_string())
+                .parse()?,
+            registration_allowed_domains: list_var(
+                "REGISTRATION_ALLOWED_DOMAINS",
+            ),
+            invitation_url: env::var("INVITATION_URL").unwrap_or_else(|_| {
+                "http://localhost:5173/register/invitation".to_string()
+            }),
+            invitation_expires: env::var("INVITATION_EXPIRES")
+                .unwrap_or("604800".to_string())
+                .parse()?,
+            username_min_length: env::var("USERNAME_MIN_LENGTH")
+                .unwrap_or("3".to_string())
+                .parse()?,
+            username_max_length: env::var("USERNAME_MAX_LENGTH")
+                .unwrap_or("25".to_string())
+                .parse()?,
+            username_allowed_symbols: env::var("USERNAME_ALLOWED_SYMBOLS")
+                .unwrap_or("_.-".to_string()),
+            username_reserved: list_var_or(
+                "USERNAME_RESERVED",
+                "admin,administrator,root,system,support,help,security,\
+                 moderator,staff,api,www,mail,postmaster,abuse,noreply,\
+                 no-reply,null,undefined,me",
+            ),
+            username_blocklist_path: env::var("USERNAME_BLOCKLIST_PATH").ok(),
+            job_scheduler_interval: env::var("JOB_SCHEDULER_INTERVAL")
+                .unwrap_or("30".to_string())
+                .parse()?,
+            job_purge_registrations_schedule: env::var(
+                "JOB_PURGE_REGISTRATIONS_SCHEDULE",
+            )
+            .unwrap_or("*/15 * * * *".to_string()),
+            job_purge_refresh_tokens_schedule: env::var(
+                "JOB_PURGE_REFRESH_TOKENS_SCHEDULE",
+            )
+            .unwrap_or("0 * * * *".to_string()),
         };
 
+        if config.registration_policy == RegistrationPolicy::AllowedDomains
+            && config.registration_allowed_domains.is_empty()
+        {
+            return Err("REGISTRATION_ALLOWED_DOMAINS must be set for the \
+                        allowed_domains registration policy"
+                .into());
+        }
+
         CONFIG
             .set(Arc::new(config))
             .map_err(|_| "Config already initialized".into())
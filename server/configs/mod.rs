@@ -1,2 +1,2 @@
 pub mod config;
-pub use config::Config;
+pub use config::{Config, RateLimitQuota, RegistrationPolicy, TrustedProxy};
@@ -0,0 +1,17 @@
+CREATE TABLE roles (id SERIAL PRIMARY KEY, name VARCHAR(64) NOT NULL UNIQUE, description TEXT NOT NULL DEFAULT '', created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW());
+
+CREATE TABLE permissions (id SERIAL PRIMARY KEY, name VARCHAR(128) NOT NULL UNIQUE, description TEXT NOT NULL DEFAULT '');
+
+CREATE TABLE role_permissions (role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE, permission_id INTEGER NOT NULL REFERENCES permissions(id) ON DELETE CASCADE, PRIMARY KEY (role_id, permission_id));
+
+CREATE TABLE user_roles (user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE, role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE, created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), PRIMARY KEY (user_id, role_id));
+
+CREATE INDEX idx_user_roles_role_id ON user_roles(role_id);
+
+INSERT INTO roles (name, description) VALUES ('admin', 'Full access to the admin panel'), ('viewer', 'Read-only access to the admin panel');
+
+INSERT INTO permissions (name, description) VALUES ('users.read', 'View users'), ('users.create', 'Create users'), ('users.update', 'Update users'), ('users.delete', 'Delete users'), ('roles.read', 'View roles and role assignments'), ('roles.assign', 'Assign and revoke user roles');
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin';
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'viewer' AND p.name IN ('users.read', 'roles.read');
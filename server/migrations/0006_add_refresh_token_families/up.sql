@@ -0,0 +1,13 @@
+ALTER TABLE refresh_tokens ADD COLUMN family_id VARCHAR(255);
+
+-- A fresh id, the token itself would be shown as the session id
+UPDATE refresh_tokens SET family_id = gen_random_uuid()::text;
+
+ALTER TABLE refresh_tokens ALTER COLUMN family_id SET NOT NULL;
+ALTER TABLE refresh_tokens ADD COLUMN rotated_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_refresh_tokens_family_id ON refresh_tokens(family_id);
+
+CREATE TABLE security_events (id SERIAL PRIMARY KEY, user_id INTEGER REFERENCES users(id) ON DELETE SET NULL, event_type VARCHAR(64) NOT NULL, details TEXT NOT NULL DEFAULT '', created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW());
+
+CREATE INDEX idx_security_events_user_id ON security_events(user_id);
@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS mfa_challenges;
+
+DROP INDEX IF EXISTS idx_mfa_recovery_codes_user_id;
+DROP TABLE IF EXISTS mfa_recovery_codes;
+
+DROP TABLE IF EXISTS user_mfa;
+
+DELETE FROM schema_migrations WHERE version = 8;
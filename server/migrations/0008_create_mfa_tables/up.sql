@@ -0,0 +1,7 @@
+CREATE TABLE user_mfa (user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE, secret VARCHAR(64) NOT NULL, enabled BOOLEAN NOT NULL DEFAULT FALSE, last_used_step BIGINT, created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), enabled_at TIMESTAMP WITH TIME ZONE);
+
+CREATE TABLE mfa_recovery_codes (id SERIAL PRIMARY KEY, user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE, code_hash VARCHAR(64) NOT NULL, used_at TIMESTAMP WITH TIME ZONE, created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW());
+
+CREATE INDEX idx_mfa_recovery_codes_user_id ON mfa_recovery_codes(user_id);
+
+CREATE TABLE mfa_challenges (token VARCHAR(255) PRIMARY KEY, user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE, attempts INTEGER NOT NULL DEFAULT 0, expires_at TIMESTAMP WITH TIME ZONE NOT NULL, created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW());
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_password_reset_tokens_user_id;
+DROP TABLE IF EXISTS password_reset_tokens;
+
+DELETE FROM schema_migrations WHERE version = 9;
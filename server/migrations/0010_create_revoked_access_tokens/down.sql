@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS revoked_access_tokens;
+
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS access_expires_at;
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS access_jti;
+
+DELETE FROM schema_migrations WHERE version = 10;
@@ -0,0 +1,6 @@
+ALTER TABLE refresh_tokens ADD COLUMN access_jti VARCHAR(64);
+ALTER TABLE refresh_tokens ADD COLUMN access_expires_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE revoked_access_tokens (jti VARCHAR(64) PRIMARY KEY, user_id INTEGER NOT NULL, expires_at TIMESTAMP WITH TIME ZONE NOT NULL, revoked_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW());
+
+CREATE INDEX idx_revoked_access_tokens_revoked_at ON revoked_access_tokens(revoked_at);
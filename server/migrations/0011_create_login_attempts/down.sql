@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS login_attempts;
+
+DELETE FROM schema_migrations WHERE version = 11;
@@ -0,0 +1 @@
+CREATE TABLE login_attempts (scope VARCHAR(16) NOT NULL, key VARCHAR(255) NOT NULL, failed_attempts INTEGER NOT NULL DEFAULT 0, last_failed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), locked_until TIMESTAMP WITH TIME ZONE, PRIMARY KEY (scope, key));
@@ -0,0 +1,3 @@
+CREATE TABLE rate_limit_counters (key VARCHAR(255) NOT NULL, window_start BIGINT NOT NULL, hits BIGINT NOT NULL DEFAULT 0, expires_at TIMESTAMP WITH TIME ZONE NOT NULL, PRIMARY KEY (key, window_start));
+
+CREATE INDEX idx_rate_limit_counters_expires_at ON rate_limit_counters(expires_at);
@@ -0,0 +1,5 @@
+DELETE FROM permissions WHERE name IN ('email.send', 'email.log.read');
+
+DROP TABLE IF EXISTS email_log;
+
+DELETE FROM schema_migrations WHERE version = 13;
@@ -0,0 +1,7 @@
+CREATE TABLE email_log (id SERIAL PRIMARY KEY, sender_id INTEGER, recipient VARCHAR(255) NOT NULL, subject TEXT NOT NULL, status VARCHAR(16) NOT NULL, error TEXT, created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW());
+
+CREATE INDEX idx_email_log_sender_created_at ON email_log(sender_id, created_at);
+
+INSERT INTO permissions (name, description) VALUES ('email.send', 'Send emails through the relay'), ('email.log.read', 'View the email log');
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin' AND p.name IN ('email.send', 'email.log.read');
@@ -0,0 +1,5 @@
+DELETE FROM permissions WHERE name = 'email.outbox.manage';
+
+DROP TABLE IF EXISTS email_outbox;
+
+DELETE FROM schema_migrations WHERE version = 14;
@@ -0,0 +1,9 @@
+CREATE TABLE email_outbox (id SERIAL PRIMARY KEY, recipient VARCHAR(255) NOT NULL, subject TEXT NOT NULL, text_body TEXT NOT NULL, html_body TEXT, status VARCHAR(16) NOT NULL DEFAULT 'pending', attempts INTEGER NOT NULL DEFAULT 0, last_error TEXT, next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), sent_at TIMESTAMP WITH TIME ZONE);
+
+CREATE INDEX idx_email_outbox_due ON email_outbox(next_attempt_at) WHERE status = 'pending';
+
+CREATE INDEX idx_email_outbox_status ON email_outbox(status, created_at);
+
+INSERT INTO permissions (name, description) VALUES ('email.outbox.manage', 'View and requeue queued emails');
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin' AND p.name = 'email.outbox.manage';
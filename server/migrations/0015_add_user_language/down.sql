@@ -0,0 +1,7 @@
+DELETE FROM permissions WHERE name = 'email.template.preview';
+
+ALTER TABLE temp_registrations DROP COLUMN IF EXISTS language;
+
+ALTER TABLE users DROP COLUMN IF EXISTS language;
+
+DELETE FROM schema_migrations WHERE version = 15;
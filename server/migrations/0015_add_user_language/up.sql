@@ -0,0 +1,7 @@
+ALTER TABLE users ADD COLUMN language VARCHAR(16);
+
+ALTER TABLE temp_registrations ADD COLUMN language VARCHAR(16);
+
+INSERT INTO permissions (name, description) VALUES ('email.template.preview', 'Render email templates with sample data');
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin' AND p.name = 'email.template.preview';
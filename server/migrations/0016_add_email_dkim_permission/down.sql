@@ -0,0 +1,3 @@
+DELETE FROM permissions WHERE name = 'email.dkim.read';
+
+DELETE FROM schema_migrations WHERE version = 16;
@@ -0,0 +1,3 @@
+INSERT INTO permissions (name, description) VALUES ('email.dkim.read', 'View the DKIM DNS record to publish');
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin' AND p.name = 'email.dkim.read';
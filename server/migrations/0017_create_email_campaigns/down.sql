@@ -0,0 +1,9 @@
+DELETE FROM permissions WHERE name = 'email.campaigns.manage';
+
+DROP TABLE IF EXISTS email_campaign_recipients;
+
+DROP TABLE IF EXISTS email_campaigns;
+
+ALTER TABLE users DROP COLUMN IF EXISTS email_opt_out;
+
+DELETE FROM schema_migrations WHERE version = 17;
@@ -0,0 +1,11 @@
+ALTER TABLE users ADD COLUMN email_opt_out BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE email_campaigns (id SERIAL PRIMARY KEY, name VARCHAR(255) NOT NULL, subject TEXT NOT NULL, text_body TEXT NOT NULL, html_body TEXT, segment_registered_within_days INTEGER, segment_role VARCHAR(64), segment_language VARCHAR(16), status VARCHAR(16) NOT NULL DEFAULT 'draft', created_by INTEGER REFERENCES users(id) ON DELETE SET NULL, created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), started_at TIMESTAMP WITH TIME ZONE, finished_at TIMESTAMP WITH TIME ZONE);
+
+CREATE TABLE email_campaign_recipients (campaign_id INTEGER NOT NULL REFERENCES email_campaigns(id) ON DELETE CASCADE, user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE, email VARCHAR(255) NOT NULL, status VARCHAR(16) NOT NULL DEFAULT 'pending', error TEXT, unsubscribe_token_hash VARCHAR(64) UNIQUE, claimed_until TIMESTAMP WITH TIME ZONE, sent_at TIMESTAMP WITH TIME ZONE, PRIMARY KEY (campaign_id, user_id));
+
+CREATE INDEX idx_email_campaign_recipients_pending ON email_campaign_recipients(campaign_id) WHERE status = 'pending';
+
+INSERT INTO permissions (name, description) VALUES ('email.campaigns.manage', 'Create, preview and send email campaigns');
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin' AND p.name = 'email.campaigns.manage';
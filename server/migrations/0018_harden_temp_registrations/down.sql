@@ -0,0 +1,14 @@
+ALTER TABLE temp_registrations DROP COLUMN IF EXISTS code_sent_at;
+
+ALTER TABLE temp_registrations DROP COLUMN IF EXISTS attempts;
+
+-- Codes are stored hashed from here on and cannot be turned back into the
+-- plain codes the old schema expects, so every pending registration is
+-- dropped. Users who were mid-registration have to start again.
+DELETE FROM temp_registrations;
+
+ALTER TABLE temp_registrations ALTER COLUMN secret_key_hash TYPE VARCHAR(64);
+
+ALTER TABLE temp_registrations RENAME COLUMN secret_key_hash TO secret_key;
+
+DELETE FROM schema_migrations WHERE version = 18;
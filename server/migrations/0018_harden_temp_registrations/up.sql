@@ -0,0 +1,9 @@
+ALTER TABLE temp_registrations RENAME COLUMN secret_key TO secret_key_hash;
+
+ALTER TABLE temp_registrations ALTER COLUMN secret_key_hash TYPE VARCHAR(255);
+
+ALTER TABLE temp_registrations ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE temp_registrations ADD COLUMN code_sent_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+UPDATE temp_registrations SET code_sent_at = created_at;
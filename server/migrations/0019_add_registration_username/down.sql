@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_users_username_lower;
+
+ALTER TABLE temp_registrations DROP COLUMN IF EXISTS username;
+
+DELETE FROM schema_migrations WHERE version = 19;
@@ -0,0 +1,3 @@
+ALTER TABLE temp_registrations ADD COLUMN username VARCHAR(255);
+
+CREATE UNIQUE INDEX idx_users_username_lower ON users(LOWER(username));
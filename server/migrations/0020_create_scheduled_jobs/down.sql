@@ -0,0 +1,5 @@
+DELETE FROM permissions WHERE name = 'jobs.manage';
+
+DROP TABLE IF EXISTS scheduled_jobs;
+
+DELETE FROM schema_migrations WHERE version = 20;
@@ -0,0 +1,5 @@
+CREATE TABLE scheduled_jobs (name VARCHAR(64) PRIMARY KEY, schedule VARCHAR(255) NOT NULL, next_run_at TIMESTAMP WITH TIME ZONE NOT NULL, last_trigger VARCHAR(16), last_status VARCHAR(16), last_error TEXT, last_affected BIGINT, last_started_at TIMESTAMP WITH TIME ZONE, last_finished_at TIMESTAMP WITH TIME ZONE);
+
+INSERT INTO permissions (name, description) VALUES ('jobs.manage', 'View and trigger background jobs');
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin' AND p.name = 'jobs.manage';
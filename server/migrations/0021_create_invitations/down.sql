@@ -0,0 +1,5 @@
+DELETE FROM permissions WHERE name = 'invitations.manage';
+
+DROP TABLE IF EXISTS invitations;
+
+DELETE FROM schema_migrations WHERE version = 21;
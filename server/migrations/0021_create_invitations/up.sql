@@ -0,0 +1,7 @@
+CREATE TABLE invitations (id SERIAL PRIMARY KEY, email VARCHAR(255) NOT NULL, token_hash VARCHAR(64) NOT NULL UNIQUE, role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE, language VARCHAR(16), invited_by INTEGER REFERENCES users(id) ON DELETE SET NULL, created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), expires_at TIMESTAMP WITH TIME ZONE NOT NULL, accepted_at TIMESTAMP WITH TIME ZONE, accepted_by INTEGER REFERENCES users(id) ON DELETE SET NULL, revoked_at TIMESTAMP WITH TIME ZONE);
+
+CREATE INDEX idx_invitations_email ON invitations(LOWER(email));
+
+INSERT INTO permissions (name, description) VALUES ('invitations.manage', 'Invite users and revoke invitations');
+
+INSERT INTO role_permissions (role_id, permission_id) SELECT r.id, p.id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin' AND p.name = 'invitations.manage';
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_users_created_at;
+
+DELETE FROM schema_migrations WHERE version = 22;
@@ -0,0 +1,3 @@
+-- The old ids were refresh tokens, they are not brought back
+
+DELETE FROM schema_migrations WHERE version = 23;
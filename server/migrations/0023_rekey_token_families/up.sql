@@ -0,0 +1,3 @@
+-- 0006 used to start the families of existing tokens with the token itself,
+-- which the sessions listing shows as the session id
+UPDATE refresh_tokens r SET family_id = f.new_id FROM (SELECT family_id, gen_random_uuid()::text AS new_id FROM refresh_tokens WHERE family_id IN (SELECT token FROM refresh_tokens) GROUP BY family_id) f WHERE r.family_id = f.family_id;
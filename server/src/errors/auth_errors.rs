@@ -1,4 +1,4 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{HttpResponse, ResponseError, http::header::RETRY_AFTER};
 use jsonwebtoken::errors::Error as JwtError;
 use serde_json::json;
 use sqlx::Error as SqlxError;
@@ -6,6 +6,8 @@ use thiserror::Error;
 use time::error::ComponentRange;
 use validator::ValidationErrors;
 
+use crate::errors::{password_errors::PasswordError, roles_errors::RoleError};
+
 #[derive(Debug, Error)]
 pub enum AuthError {
     #[error("Invalid token: {0}")]
@@ -20,9 +22,45 @@ pub enum AuthError {
     #[error("Token expired")]
     TokenExpired,
 
+    #[error("Token revoked")]
+    TokenRevoked,
+
+    #[error("Too many login attempts, retry in {0} seconds")]
+    TooManyAttempts(i64),
+
+    #[error("Account locked for {0} seconds")]
+    AccountLocked(i64),
+
     #[error("Refresh token not found")]
     RefreshTokenNotFound,
 
+    #[error("Refresh token reused")]
+    RefreshTokenReused,
+
+    #[error("Session not found")]
+    SessionNotFound,
+
+    #[error("Invalid or expired password reset token")]
+    InvalidResetToken,
+
+    #[error("Invalid MFA code")]
+    InvalidMfaCode,
+
+    #[error("MFA challenge not found")]
+    MfaChallengeNotFound,
+
+    #[error("MFA is already enabled")]
+    MfaAlreadyEnabled,
+
+    #[error("MFA is not enrolled")]
+    MfaNotEnrolled,
+
+    #[error("MFA error: {0}")]
+    Mfa(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     // #[error("Unauthorized: {0}")]
     // Unauthorized(String),
 
@@ -33,9 +71,16 @@ pub enum AuthError {
 
     #[error("Database error: {0}")]
     Database(#[from] SqlxError),
+
+    #[error("Password error: {0}")]
+    Password(#[from] PasswordError),
+
+    #[error("Role error: {0}")]
+    Role(#[from] RoleError),
 }
 
 impl ResponseError for AuthError {
+    #[allow(clippy::too_many_lines)]
     fn error_response(&self) -> HttpResponse {
         match self {
             AuthError::Validation(errors) => {
@@ -84,6 +129,30 @@ impl ResponseError for AuthError {
                 }))
             }
 
+            AuthError::TokenRevoked => {
+                log::warn!("Revoked token used");
+                HttpResponse::Unauthorized().json(json!({
+                    "error": "token_revoked",
+                    "message": "Token has been revoked"
+                }))
+            }
+
+            AuthError::TooManyAttempts(retry_after) => {
+                HttpResponse::TooManyRequests()
+                    .insert_header((RETRY_AFTER, retry_after.to_string()))
+                    .json(json!({
+                        "error": "too_many_attempts",
+                        "message": "Too many failed login attempts, try again later"
+                    }))
+            }
+
+            AuthError::AccountLocked(retry_after) => HttpResponse::Locked()
+                .insert_header((RETRY_AFTER, retry_after.to_string()))
+                .json(json!({
+                    "error": "account_locked",
+                    "message": "Account is temporarily locked after too many failed login attempts"
+                })),
+
             AuthError::InvalidTime(e) => {
                 log::error!("Invalid timestamp: {}", e);
                 HttpResponse::InternalServerError().json(json!({
@@ -100,6 +169,73 @@ impl ResponseError for AuthError {
                 }))
             }
 
+            AuthError::RefreshTokenReused => {
+                log::warn!("Refresh token reused");
+                HttpResponse::Unauthorized().json(json!({
+                    "error": "refresh_token_reused",
+                    "message": "Refresh token was already used, session revoked"
+                }))
+            }
+
+            AuthError::SessionNotFound => {
+                HttpResponse::NotFound().json(json!({
+                    "error": "session_not_found",
+                    "message": "Session not found"
+                }))
+            }
+
+            AuthError::InvalidResetToken => {
+                log::warn!("Invalid password reset token");
+                HttpResponse::BadRequest().json(json!({
+                    "error": "invalid_reset_token",
+                    "message": "Password reset link is invalid or has expired"
+                }))
+            }
+
+            AuthError::InvalidMfaCode => {
+                log::warn!("Invalid MFA code");
+                HttpResponse::Unauthorized().json(json!({
+                    "error": "invalid_mfa_code",
+                    "message": "Invalid authentication code"
+                }))
+            }
+
+            AuthError::MfaChallengeNotFound => HttpResponse::Unauthorized()
+                .json(json!({
+                    "error": "mfa_challenge_not_found",
+                    "message": "MFA token not found or already expired"
+                })),
+
+            AuthError::MfaAlreadyEnabled => {
+                HttpResponse::Conflict().json(json!({
+                    "error": "mfa_already_enabled",
+                    "message": "Two-factor authentication is already enabled"
+                }))
+            }
+
+            AuthError::MfaNotEnrolled => {
+                HttpResponse::BadRequest().json(json!({
+                    "error": "mfa_not_enrolled",
+                    "message": "Two-factor authentication is not set up"
+                }))
+            }
+
+            AuthError::Mfa(message) => {
+                log::error!("MFA error: {message}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "mfa_error",
+                    "message": "Two-factor authentication failed"
+                }))
+            }
+
+            AuthError::Forbidden(message) => {
+                log::warn!("Forbidden: {message}");
+                HttpResponse::Forbidden().json(json!({
+                    "error": "forbidden",
+                    "message": message
+                }))
+            }
+
             // AuthError::Unauthorized(message) => {
             //     log::warn!("Unauthorized: {}", message);
             //     HttpResponse::Unauthorized().json(json!({
@@ -122,6 +258,16 @@ impl ResponseError for AuthError {
                     "message": "Database operation failed"
                 }))
             }
+
+            AuthError::Password(e) => {
+                log::error!("Password error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "password_error",
+                    "message": "Password processing failed"
+                }))
+            }
+
+            AuthError::Role(e) => e.error_response(),
         }
     }
 }
@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DkimError {
+    #[error("Failed to read DKIM key file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid DKIM private key: {0}")]
+    Key(String),
+
+    #[error("DKIM configuration error: {0}")]
+    Config(String),
+}
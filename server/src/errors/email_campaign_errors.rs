@@ -0,0 +1,102 @@
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+use sqlx::Error as SqlxError;
+use thiserror::Error;
+use validator::ValidationErrors;
+
+use crate::errors::{
+    auth_errors::AuthError, email_template_errors::EmailTemplateError,
+};
+
+#[derive(Debug, Error)]
+pub enum CampaignError {
+    #[error("Validation error: {0}")]
+    Validation(#[from] ValidationErrors),
+
+    #[error("Database error: {0}")]
+    Database(#[from] SqlxError),
+
+    #[error("Campaign #{0} not found")]
+    NotFound(i32),
+
+    #[error("Invalid campaign template: {0}")]
+    InvalidTemplate(String),
+
+    #[error("Campaign is {0}")]
+    InvalidState(String),
+
+    #[error("Invalid or unknown unsubscribe token")]
+    InvalidUnsubscribeToken,
+
+    #[error("Auth error: {0}")]
+    Auth(#[from] AuthError),
+
+    #[error("Template error: {0}")]
+    Template(#[from] EmailTemplateError),
+}
+
+impl ResponseError for CampaignError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            CampaignError::Validation(errors) => {
+                let details: Vec<String> = errors
+                    .field_errors()
+                    .iter()
+                    .flat_map(|(field, errors)| {
+                        errors.iter().map(move |e| {
+                            log::error!("Validation error, campaign: {e}");
+                            format!(
+                                "{}: {}",
+                                field,
+                                e.message.as_deref().unwrap_or("invalid")
+                            )
+                        })
+                    })
+                    .collect();
+                HttpResponse::BadRequest().json(json!({
+                    "error": "validation_failed",
+                    "message": "Validation failed",
+                    "details": details
+                }))
+            }
+
+            CampaignError::Database(e) => {
+                log::error!("Database error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "database_error",
+                    "message": "Database operation failed"
+                }))
+            }
+
+            CampaignError::NotFound(id) => {
+                HttpResponse::NotFound().json(json!({
+                    "error": "campaign_not_found",
+                    "message": format!("Campaign #{id} not found")
+                }))
+            }
+
+            CampaignError::InvalidTemplate(message) => {
+                HttpResponse::BadRequest().json(json!({
+                    "error": "invalid_template",
+                    "message": message
+                }))
+            }
+
+            CampaignError::InvalidState(status) => HttpResponse::Conflict()
+                .json(json!({
+                    "error": "invalid_campaign_state",
+                    "message": format!("Campaign is {status}")
+                })),
+
+            CampaignError::InvalidUnsubscribeToken => HttpResponse::NotFound()
+                .json(json!({
+                    "error": "invalid_token",
+                    "message": "Invalid or unknown unsubscribe token"
+                })),
+
+            CampaignError::Auth(e) => e.error_response(),
+
+            CampaignError::Template(e) => e.error_response(),
+        }
+    }
+}
@@ -4,9 +4,14 @@ use lettre::{
     transport::smtp::Error as SmtpError,
 };
 use serde_json::json;
+use sqlx::Error as SqlxError;
 use thiserror::Error;
 use validator::ValidationErrors;
 
+use crate::errors::{
+    auth_errors::AuthError, email_template_errors::EmailTemplateError,
+};
+
 #[derive(Debug, Error)]
 pub enum EmailError {
     #[error("Failed to parse email address: {0}")]
@@ -33,14 +38,39 @@ pub enum EmailError {
     #[error("Email subject cannot be empty")]
     EmptySubject,
 
+    #[error("Invalid email header: {0}")]
+    InvalidHeader(String),
+
+    #[error("Invalid attachment: {0}")]
+    InvalidAttachment(String),
+
+    #[error("Attachments exceed the limit of {0} bytes")]
+    AttachmentsTooLarge(usize),
+
     #[error("Failed to send email: {0}")]
     SendFailed(String),
 
     #[error("SMTP server unavailable: {0}")]
     ServiceUnavailable(String),
+
+    #[error("Recipient not allowed: {0}")]
+    RecipientNotAllowed(String),
+
+    #[error("Daily email quota of {0} exceeded")]
+    QuotaExceeded(i64),
+
+    #[error("Database error: {0}")]
+    Database(#[from] SqlxError),
+
+    #[error("Auth error: {0}")]
+    Auth(#[from] AuthError),
+
+    #[error("Template error: {0}")]
+    Template(#[from] EmailTemplateError),
 }
 
 impl ResponseError for EmailError {
+    #[allow(clippy::too_many_lines)]
     fn error_response(&self) -> HttpResponse {
         match self {
             EmailError::Validation(errors) => {
@@ -92,6 +122,32 @@ impl ResponseError for EmailError {
                 }))
             }
 
+            EmailError::InvalidHeader(name) => {
+                log::warn!("Invalid email header: {name}");
+                HttpResponse::BadRequest().json(json!({
+                    "error": "invalid_email_header",
+                    "message": format!("Header '{name}' cannot be set")
+                }))
+            }
+
+            EmailError::InvalidAttachment(message) => {
+                log::warn!("Invalid attachment: {message}");
+                HttpResponse::BadRequest().json(json!({
+                    "error": "invalid_attachment",
+                    "message": message
+                }))
+            }
+
+            EmailError::AttachmentsTooLarge(limit) => {
+                log::warn!("Attachments exceed the limit of {limit} bytes");
+                HttpResponse::PayloadTooLarge().json(json!({
+                    "error": "attachments_too_large",
+                    "message": format!(
+                        "Attachments cannot exceed {limit} bytes in total"
+                    )
+                }))
+            }
+
             EmailError::EmptySubject => {
                 log::warn!("Email subject cannot be empty");
                 HttpResponse::BadRequest().json(json!({
@@ -132,6 +188,34 @@ impl ResponseError for EmailError {
                 }))
             }
 
+            EmailError::RecipientNotAllowed(recipient) => {
+                log::warn!("Email to {recipient} rejected by recipient rules");
+                HttpResponse::Forbidden().json(json!({
+                    "error": "recipient_not_allowed",
+                    "message": "Sending to this recipient is not allowed"
+                }))
+            }
+
+            EmailError::QuotaExceeded(quota) => {
+                log::warn!("Daily email quota of {quota} exceeded");
+                HttpResponse::TooManyRequests().json(json!({
+                    "error": "email_quota_exceeded",
+                    "message": format!("Daily quota of {quota} emails exceeded")
+                }))
+            }
+
+            EmailError::Database(e) => {
+                log::error!("Database error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "database_error",
+                    "message": "Database operation failed"
+                }))
+            }
+
+            EmailError::Auth(e) => e.error_response(),
+
+            EmailError::Template(e) => e.error_response(),
+
             EmailError::SendFailed(message) => {
                 log::error!("Email sending failed: {}", message);
                 HttpResponse::InternalServerError().json(json!({
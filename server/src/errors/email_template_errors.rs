@@ -0,0 +1,54 @@
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EmailTemplateError {
+    #[error("Email template not found: {0}")]
+    NotFound(String),
+
+    #[error("Failed to render email template: {0}")]
+    Render(#[from] minijinja::Error),
+
+    #[error("Failed to read sample data: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid sample data: {0}")]
+    SampleData(#[from] serde_json::Error),
+
+    #[error("Email template configuration error: {0}")]
+    Config(String),
+}
+
+impl ResponseError for EmailTemplateError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            EmailTemplateError::NotFound(name) => {
+                log::warn!("Email template not found: {name}");
+                HttpResponse::NotFound().json(json!({
+                    "error": "template_not_found",
+                    "message": format!("Email template '{name}' not found")
+                }))
+            }
+
+            // Only admins see these, the details help fixing the template
+            EmailTemplateError::Render(e) => {
+                log::error!("Failed to render email template: {e:#}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "template_render_failed",
+                    "message": e.to_string()
+                }))
+            }
+
+            EmailTemplateError::Io(_)
+            | EmailTemplateError::SampleData(_)
+            | EmailTemplateError::Config(_) => {
+                log::error!("{self}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "template_error",
+                    "message": self.to_string()
+                }))
+            }
+        }
+    }
+}
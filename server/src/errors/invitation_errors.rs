@@ -0,0 +1,110 @@
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+use sqlx::Error as SqlxError;
+use thiserror::Error;
+use validator::ValidationErrors;
+
+use crate::errors::{
+    auth_errors::AuthError, email_template_errors::EmailTemplateError,
+};
+
+#[derive(Debug, Error)]
+pub enum InvitationError {
+    #[error("Validation error: {0}")]
+    Validation(#[from] ValidationErrors),
+
+    #[error("Database error: {0}")]
+    Database(#[from] SqlxError),
+
+    #[error("Invitation #{0} not found")]
+    NotFound(i32),
+
+    #[error("Role '{0}' not found")]
+    RoleNotFound(String),
+
+    #[error("Email is already taken")]
+    EmailAlreadyTaken,
+
+    #[error("Invitation is {0}")]
+    InvalidState(String),
+
+    #[error("Internal server error")]
+    Internal,
+
+    #[error("Auth error: {0}")]
+    Auth(#[from] AuthError),
+
+    #[error("Template error: {0}")]
+    Template(#[from] EmailTemplateError),
+}
+
+impl ResponseError for InvitationError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            InvitationError::Validation(errors) => {
+                let details: Vec<String> = errors
+                    .field_errors()
+                    .iter()
+                    .flat_map(|(field, errors)| {
+                        errors.iter().map(move |e| {
+                            log::error!("Validation error, invitation: {e}");
+                            format!(
+                                "{}: {}",
+                                field,
+                                e.message.as_deref().unwrap_or("invalid")
+                            )
+                        })
+                    })
+                    .collect();
+                HttpResponse::BadRequest().json(json!({
+                    "error": "validation_failed",
+                    "message": "Validation failed",
+                    "details": details
+                }))
+            }
+
+            InvitationError::Database(e) => {
+                log::error!("Database error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "database_error",
+                    "message": "Database operation failed"
+                }))
+            }
+
+            InvitationError::NotFound(id) => {
+                HttpResponse::NotFound().json(json!({
+                    "error": "invitation_not_found",
+                    "message": format!("Invitation #{id} not found")
+                }))
+            }
+
+            InvitationError::RoleNotFound(role) => HttpResponse::BadRequest()
+                .json(json!({
+                    "error": "role_not_found",
+                    "message": format!("Role '{role}' not found")
+                })),
+
+            InvitationError::EmailAlreadyTaken => HttpResponse::Conflict()
+                .json(json!({
+                    "error": "email_already_taken",
+                    "message": "A user with this email already exists"
+                })),
+
+            InvitationError::InvalidState(status) => HttpResponse::Conflict()
+                .json(json!({
+                    "error": "invalid_invitation_state",
+                    "message": format!("Invitation is {status}")
+                })),
+
+            InvitationError::Internal => HttpResponse::InternalServerError()
+                .json(json!({
+                    "error": "internal_error",
+                    "message": "Internal server error"
+                })),
+
+            InvitationError::Auth(e) => e.error_response(),
+
+            InvitationError::Template(e) => e.error_response(),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+use sqlx::Error as SqlxError;
+use thiserror::Error;
+use validator::ValidationErrors;
+
+use crate::errors::{
+    auth_errors::AuthError, temp_registration_errors::TempRegistrationError,
+};
+
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("Validation error: {0}")]
+    Validation(#[from] ValidationErrors),
+
+    #[error("Database error: {0}")]
+    Database(#[from] SqlxError),
+
+    #[error("Job {0} not found")]
+    NotFound(String),
+
+    #[error("Job {0} is already running")]
+    AlreadyRunning(String),
+
+    #[error("Job scheduler configuration error: {0}")]
+    Config(String),
+
+    #[error("Auth error: {0}")]
+    Auth(#[from] AuthError),
+
+    #[error("Temporary registration error: {0}")]
+    TempRegistration(#[from] TempRegistrationError),
+}
+
+impl ResponseError for JobError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            JobError::Validation(e) => {
+                log::error!("Validation error, job: {e}");
+                HttpResponse::BadRequest().json(json!({
+                    "error": "validation_failed",
+                    "message": e.to_string()
+                }))
+            }
+
+            JobError::Database(e) => {
+                log::error!("Database error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "database_error",
+                    "message": "Database operation failed"
+                }))
+            }
+
+            JobError::NotFound(name) => HttpResponse::NotFound().json(json!({
+                "error": "job_not_found",
+                "message": format!("Job {name} not found")
+            })),
+
+            JobError::AlreadyRunning(name) => {
+                HttpResponse::Conflict().json(json!({
+                    "error": "job_running",
+                    "message": format!("Job {name} is already running")
+                }))
+            }
+
+            JobError::Config(message) => {
+                log::error!("Job scheduler configuration error: {message}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "internal_error",
+                    "message": "Job scheduler is misconfigured"
+                }))
+            }
+
+            JobError::Auth(e) => e.error_response(),
+
+            JobError::TempRegistration(e) => e.error_response(),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use jsonwebtoken::errors::Error as JwtError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JwtKeyError {
+    #[error("Failed to read key file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid PEM file: {0}")]
+    Pem(#[from] pem::PemError),
+
+    #[error("Invalid RSA public key: {0}")]
+    Rsa(#[from] rsa::pkcs8::spki::Error),
+
+    #[error("Invalid JWT key: {0}")]
+    Jwt(#[from] JwtError),
+
+    #[error("JWT key configuration error: {0}")]
+    Config(String),
+}
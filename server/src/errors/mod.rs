@@ -1,6 +1,15 @@
 pub mod auth_errors;
 pub mod cookies_errors;
+pub mod dkim_errors;
+pub mod email_campaign_errors;
 pub mod email_errors;
+pub mod email_template_errors;
+pub mod invitation_errors;
+pub mod job_errors;
+pub mod jwt_key_errors;
+pub mod password_errors;
 pub mod posts_errors;
+pub mod rate_limit_errors;
+pub mod roles_errors;
 pub mod temp_registration_errors;
 pub mod users_errors;
@@ -0,0 +1,12 @@
+use argon2::password_hash::Error as HashError;
+use thiserror::Error;
+use tokio::task::JoinError;
+
+#[derive(Debug, Error)]
+pub enum PasswordError {
+    #[error("Password hashing failed: {0}")]
+    Hash(#[from] HashError),
+
+    #[error("Password hashing task failed: {0}")]
+    Task(#[from] JoinError),
+}
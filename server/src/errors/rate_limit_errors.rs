@@ -0,0 +1,42 @@
+use actix_web::{HttpResponse, ResponseError, http::header::RETRY_AFTER};
+use serde_json::json;
+use sqlx::Error as SqlxError;
+use thiserror::Error;
+
+use crate::models::rate_limit_models::RateLimitStatus;
+
+#[derive(Debug, Error)]
+pub enum RateLimitError {
+    #[error("Rate limit exceeded, retry in {} seconds", .0.reset)]
+    Exceeded(RateLimitStatus),
+
+    #[error("Database error: {0}")]
+    Database(#[from] SqlxError),
+}
+
+impl ResponseError for RateLimitError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            RateLimitError::Exceeded(status) => {
+                let mut response = HttpResponse::TooManyRequests();
+                for header in status.headers() {
+                    response.insert_header(header);
+                }
+                response
+                    .insert_header((RETRY_AFTER, status.reset.to_string()))
+                    .json(json!({
+                        "error": "rate_limited",
+                        "message": "Too many requests, try again later"
+                    }))
+            }
+
+            RateLimitError::Database(e) => {
+                log::error!("Database error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "database_error",
+                    "message": "Database operation failed"
+                }))
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+use sqlx::Error as SqlxError;
+use thiserror::Error;
+use validator::ValidationErrors;
+
+#[derive(Debug, Error)]
+pub enum RoleError {
+    #[error("Validation error: {0}")]
+    Validation(#[from] ValidationErrors),
+
+    #[error("Database error: {0}")]
+    Database(#[from] SqlxError),
+
+    #[error("Role not found: {0}")]
+    NotFound(String),
+
+    #[error("User not found")]
+    UserNotFound,
+}
+
+impl ResponseError for RoleError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            RoleError::Validation(errors) => {
+                let details: Vec<String> = errors
+                    .field_errors()
+                    .iter()
+                    .flat_map(|(field, errors)| {
+                        errors.iter().map(move |e| {
+                            log::error!("Validation error, role: {e}");
+                            format!(
+                                "{}: {}",
+                                field,
+                                e.message.as_deref().unwrap_or("invalid")
+                            )
+                        })
+                    })
+                    .collect();
+                HttpResponse::BadRequest().json(json!({
+                    "error": "validation_failed",
+                    "message": "Validation failed",
+                    "details": details
+                }))
+            }
+
+            RoleError::Database(e) => {
+                log::error!("Database error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "database_error",
+                    "message": "Database operation failed"
+                }))
+            }
+
+            RoleError::NotFound(name) => HttpResponse::NotFound().json(json!({
+                "error": "role_not_found",
+                "message": format!("Role '{name}' not found")
+            })),
+
+            RoleError::UserNotFound => HttpResponse::NotFound().json(json!({
+                "error": "not_found",
+                "message": "User not found"
+            })),
+        }
+    }
+}
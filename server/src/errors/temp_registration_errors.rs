@@ -3,6 +3,10 @@ use serde_json::json;
 use sqlx::Error as SqlxError;
 use thiserror::Error;
 
+use crate::errors::{
+    invitation_errors::InvitationError, password_errors::PasswordError,
+};
+
 #[derive(Debug, Error)]
 pub enum TempRegistrationError {
     #[error("Validation error: {0}")]
@@ -17,16 +21,41 @@ pub enum TempRegistrationError {
     #[error("Email is already taken")]
     EmailAlreadyTaken,
 
+    #[error("Username is already taken")]
+    UsernameTaken,
+
     #[error("Registration already in progress")]
     AlreadyInProgress,
 
-    // #[error("Invalid secret key")]
-    // InvalidSecretKey,
+    #[error("Invalid secret key")]
+    InvalidSecretKey,
+
+    #[error("Too many wrong confirmation codes")]
+    TooManyAttempts,
+
+    #[error("Confirmation code was sent recently")]
+    ResendTooSoon,
+
     #[error("Registration expired")]
     Expired,
 
+    #[error("Registration is by invitation only")]
+    RegistrationClosed,
+
+    #[error("Email domain is not allowed to register")]
+    EmailDomainNotAllowed,
+
+    #[error("Invalid or expired invitation")]
+    InvalidInvitation,
+
     #[error("Internal server error")]
     Internal,
+
+    #[error("Password error: {0}")]
+    Password(#[from] PasswordError),
+
+    #[error("Invitation error: {0}")]
+    Invitation(#[from] InvitationError),
 }
 
 impl ResponseError for TempRegistrationError {
@@ -55,6 +84,13 @@ impl ResponseError for TempRegistrationError {
                 }))
             }
 
+            TempRegistrationError::UsernameTaken => {
+                HttpResponse::Conflict().json(json!({
+                    "error": "username_taken",
+                    "message": "Username is already taken"
+                }))
+            }
+
             TempRegistrationError::NotFound => {
                 HttpResponse::NotFound().json(json!({
                     "error": "not_found",
@@ -69,12 +105,28 @@ impl ResponseError for TempRegistrationError {
                 "message": "Registration for this email is already in progress"
             })),
 
-            // TempRegistrationError::InvalidSecretKey => {
-            //     HttpResponse::BadRequest().json(json!({
-            //         "error": "invalid_secret_key",
-            //         "message": "Invalid confirmation key"
-            //     }))
-            // }
+            TempRegistrationError::InvalidSecretKey => {
+                HttpResponse::BadRequest().json(json!({
+                    "error": "invalid_secret_key",
+                    "message": "Invalid confirmation key"
+                }))
+            }
+
+            // The registration is gone, the user has to start over
+            TempRegistrationError::TooManyAttempts => {
+                HttpResponse::BadRequest().json(json!({
+                    "error": "too_many_attempts",
+                    "message": "Too many wrong confirmation codes, start the registration again"
+                }))
+            }
+
+            TempRegistrationError::ResendTooSoon => {
+                HttpResponse::TooManyRequests().json(json!({
+                    "error": "resend_too_soon",
+                    "message": "A confirmation code was sent recently, try again later"
+                }))
+            }
+
             TempRegistrationError::Expired => {
                 HttpResponse::BadRequest().json(json!({
                     "error": "expired",
@@ -82,12 +134,44 @@ impl ResponseError for TempRegistrationError {
                 }))
             }
 
+            TempRegistrationError::RegistrationClosed => {
+                HttpResponse::Forbidden().json(json!({
+                    "error": "registration_closed",
+                    "message": "Registration is by invitation only"
+                }))
+            }
+
+            TempRegistrationError::EmailDomainNotAllowed => {
+                HttpResponse::Forbidden().json(json!({
+                    "error": "email_domain_not_allowed",
+                    "message": "Registration is not open to this email domain"
+                }))
+            }
+
+            // Unknown, used, revoked and expired look the same
+            TempRegistrationError::InvalidInvitation => {
+                HttpResponse::NotFound().json(json!({
+                    "error": "invalid_invitation",
+                    "message": "Invalid or expired invitation"
+                }))
+            }
+
             TempRegistrationError::Internal => {
                 HttpResponse::InternalServerError().json(json!({
                     "error": "internal_error",
                     "message": "Internal server error"
                 }))
             }
+
+            TempRegistrationError::Password(e) => {
+                log::error!("Password error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "password_error",
+                    "message": "Password processing failed"
+                }))
+            }
+
+            TempRegistrationError::Invitation(e) => e.error_response(),
         }
     }
 }
@@ -4,6 +4,8 @@ use sqlx::Error as SqlxError;
 use thiserror::Error;
 use validator::ValidationErrors;
 
+use crate::errors::{auth_errors::AuthError, password_errors::PasswordError};
+
 #[derive(Debug, Error)]
 pub enum UserError {
     #[error("Validation error: {0}")]
@@ -14,6 +16,21 @@ pub enum UserError {
 
     #[error("User not found")]
     NotFound,
+
+    #[error("Email is already taken")]
+    AlreadyExists,
+
+    #[error("Username is already taken")]
+    UsernameTaken,
+
+    #[error("Current password is incorrect")]
+    WrongPassword,
+
+    #[error("Password error: {0}")]
+    Password(#[from] PasswordError),
+
+    #[error("Auth error: {0}")]
+    Auth(#[from] AuthError),
 }
 
 impl ResponseError for UserError {
@@ -53,6 +70,31 @@ impl ResponseError for UserError {
                 "error": "not_found",
                 "message": "User not found"
             })),
+
+            UserError::AlreadyExists => HttpResponse::Conflict().json(json!({
+                "error": "already_exists",
+                "message": "Email is already taken"
+            })),
+
+            UserError::UsernameTaken => HttpResponse::Conflict().json(json!({
+                "error": "username_taken",
+                "message": "Username is already taken"
+            })),
+
+            UserError::WrongPassword => HttpResponse::Forbidden().json(json!({
+                "error": "wrong_password",
+                "message": "Current password is incorrect"
+            })),
+
+            UserError::Password(e) => {
+                log::error!("Password error: {e}");
+                HttpResponse::InternalServerError().json(json!({
+                    "error": "password_error",
+                    "message": "Password processing failed"
+                }))
+            }
+
+            UserError::Auth(e) => e.error_response(),
         }
     }
 }
@@ -5,33 +5,48 @@ use actix_web::{
 use sqlx::PgPool;
 use validator::Validate;
 
+use configs::Config;
+
 use crate::{
     errors::auth_errors::AuthError,
-    models::auth_models::{LoginRequest, RefreshRequest},
-    services::auth_services::AuthService,
+    middlewares::rate_limit_middleware::RateLimit,
+    models::{
+        auth_models::{LoginRequest, RefreshRequest},
+        password_reset_models::{ForgotPasswordRequest, ResetPasswordRequest},
+        sessions_models::ClientInfo,
+    },
+    services::{
+        auth_services::AuthService,
+        password_reset_service::PasswordResetService,
+    },
 };
 
-#[post("/login")]
+#[post(
+    "/login",
+    wrap = "RateLimit::per_ip(\"login\", Config::global().rate_limit_login)"
+)]
 pub async fn login(
     credentials: Json<LoginRequest>,
+    client: ClientInfo,
     pool: Data<PgPool>,
 ) -> Result<HttpResponse, AuthError> {
     credentials.validate().map_err(AuthError::Validation)?;
 
     let token_pair =
-        AuthService::login(&pool, credentials.into_inner()).await?;
+        AuthService::login(&pool, credentials.into_inner(), client).await?;
     Ok(HttpResponse::Ok().json(token_pair))
 }
 
 #[post("/refresh")]
 pub async fn refresh(
     token_data: Json<RefreshRequest>,
+    client: ClientInfo,
     pool: Data<PgPool>,
 ) -> Result<HttpResponse, AuthError> {
     token_data.validate().map_err(AuthError::Validation)?;
 
     let token_pair =
-        AuthService::refresh(&pool, token_data.into_inner()).await?;
+        AuthService::refresh(&pool, token_data.into_inner(), client).await?;
     Ok(HttpResponse::Ok().json(token_pair))
 }
 
@@ -46,6 +61,39 @@ pub async fn logout(
     Ok(HttpResponse::Ok().json("Logged out successfully"))
 }
 
+#[post("/auth/password/forgot")]
+pub async fn forgot_password(
+    request: Json<ForgotPasswordRequest>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    request.validate().map_err(AuthError::Validation)?;
+
+    // Same answer and timing whether or not the email is registered
+    let email = request.into_inner().email;
+    actix_web::rt::spawn(async move {
+        PasswordResetService::request_reset(&pool, &email).await;
+    });
+
+    Ok(HttpResponse::Ok().json(
+        "If the email is registered, a password reset link has been sent",
+    ))
+}
+
+#[post("/auth/password/reset")]
+pub async fn reset_password(
+    request: Json<ResetPasswordRequest>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    request.validate().map_err(AuthError::Validation)?;
+
+    PasswordResetService::reset_password(&pool, request.into_inner()).await?;
+    Ok(HttpResponse::Ok().json("Password has been reset successfully"))
+}
+
 pub fn auth_routes(cfg: &mut ServiceConfig) {
-    cfg.service(login).service(refresh).service(logout);
+    cfg.service(login)
+        .service(refresh)
+        .service(logout)
+        .service(forgot_password)
+        .service(reset_password);
 }
@@ -1,36 +1,70 @@
 use actix_web::{
-    HttpResponse,
-    web::{self, Data, Json},
+    HttpMessage, HttpRequest, HttpResponse,
+    web::{self, Data, Json, Path, Query},
 };
+use actix_web_httpauth::middleware::HttpAuthentication;
+use sqlx::PgPool;
 use validator::Validate;
 
 use crate::{
-    errors::email_errors::EmailError,
-    models::email_models::{SendEmailRequest, SendEmailResponse},
-    services::email_services::EmailService,
+    errors::{
+        auth_errors::AuthError, email_campaign_errors::CampaignError,
+        email_errors::EmailError,
+    },
+    middlewares::{
+        auth_middleware::auth_middleware_validator,
+        permission_middleware::RequirePermission,
+        rate_limit_middleware::RateLimit,
+    },
+    models::{
+        auth_models::Claims,
+        email_campaign_models::{
+            CampaignPath, CampaignRecipientsQuery, CreateCampaignRequest,
+            UnsubscribeQuery,
+        },
+        email_models::{EmailLogQuery, SendEmailRequest, SendEmailResponse},
+        email_outbox_models::{OutboxPath, OutboxQuery},
+        email_template_models::{TemplatePath, TemplatePreviewQuery},
+    },
+    repositories::{
+        email_campaign_repository::EmailCampaignRepository,
+        email_log_repository::EmailLogRepository,
+        email_outbox_repository::EmailOutboxRepository,
+    },
+    services::{
+        email_campaign_service::EmailCampaignService,
+        email_relay_service::EmailRelayService, email_services::EmailService,
+    },
+    utils::{dkim::DkimSigner, email_templates::EmailTemplates},
 };
 
+/// Extracts user ID from the request's JWT.
+fn extract_user_id(req: &HttpRequest) -> Result<i32, AuthError> {
+    req.extensions()
+        .get::<Claims>()
+        .map(|claims| claims.sub)
+        .ok_or(AuthError::Authentication("Missing access token".to_string()))
+}
+
 pub async fn send_email(
+    req: HttpRequest,
+    pool: Data<PgPool>,
     email_service: Data<dyn EmailService>,
     request: Json<SendEmailRequest>,
 ) -> Result<HttpResponse, EmailError> {
+    let sender_id = extract_user_id(&req)?;
     request.validate().map_err(EmailError::Validation)?;
 
-    log::info!("Attempting to send email to: {}", request.to);
-
-    let result = if request.is_html.unwrap_or(false) {
-        email_service
-            .send_html_email(&request.to, &request.subject, &request.body)
-            .await
-    } else {
-        email_service
-            .send_text_email(&request.to, &request.subject, &request.body)
-            .await
-    };
-
-    match result {
-        Ok(_) => {
-            log::info!("Email successfully sent to {}", request.to);
+    log::info!(
+        "User {sender_id} attempting to send email to: {}",
+        request.to.join(", ")
+    );
+
+    match EmailRelayService::send(&pool, &**email_service, sender_id, &request)
+        .await
+    {
+        Ok(()) => {
+            log::info!("Email successfully sent to {}", request.to.join(", "));
             Ok(HttpResponse::Ok().json(SendEmailResponse {
                 success: true,
                 message: "Email sent successfully".to_string(),
@@ -43,8 +77,283 @@ pub async fn send_email(
     }
 }
 
+pub async fn get_email_log(
+    query: Query<EmailLogQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, EmailError> {
+    query.validate().map_err(EmailError::Validation)?;
+
+    let entries = EmailLogRepository::find(&pool, &query).await?;
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+pub async fn get_outbox(
+    query: Query<OutboxQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, EmailError> {
+    query.validate().map_err(EmailError::Validation)?;
+
+    let entries = EmailOutboxRepository::find(&pool, &query).await?;
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+pub async fn requeue_outbox_email(
+    path: Path<OutboxPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, EmailError> {
+    path.validate().map_err(EmailError::Validation)?;
+
+    let requeued = EmailOutboxRepository::requeue(&pool, path.email_id).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "requeued": requeued })))
+}
+
+pub async fn preview_template(
+    path: Path<TemplatePath>,
+    query: Query<TemplatePreviewQuery>,
+) -> Result<HttpResponse, EmailError> {
+    path.validate().map_err(EmailError::Validation)?;
+    query.validate().map_err(EmailError::Validation)?;
+
+    let rendered = EmailTemplates::global()
+        .preview(&path.name, query.language.as_deref())?;
+    Ok(HttpResponse::Ok().json(rendered))
+}
+
+// The TXT record to publish for the configured DKIM key
+pub async fn get_dkim_record() -> HttpResponse {
+    match DkimSigner::global() {
+        Some(signer) => HttpResponse::Ok().json(serde_json::json!({
+            "enabled": true,
+            "record": signer.dns_record()
+        })),
+        None => {
+            HttpResponse::Ok().json(serde_json::json!({ "enabled": false }))
+        }
+    }
+}
+
+pub async fn create_campaign(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+    request: Json<CreateCampaignRequest>,
+) -> Result<HttpResponse, CampaignError> {
+    let created_by = extract_user_id(&req)?;
+    request.validate().map_err(CampaignError::Validation)?;
+
+    let campaign =
+        EmailCampaignService::create(&pool, created_by, &request).await?;
+    Ok(HttpResponse::Created().json(campaign))
+}
+
+pub async fn get_campaigns(
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, CampaignError> {
+    let campaigns = EmailCampaignRepository::find_all(&pool).await?;
+    Ok(HttpResponse::Ok().json(campaigns))
+}
+
+pub async fn get_campaign(
+    path: Path<CampaignPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, CampaignError> {
+    path.validate().map_err(CampaignError::Validation)?;
+
+    let campaign =
+        EmailCampaignRepository::find_by_id(&pool, path.campaign_id).await?;
+    Ok(HttpResponse::Ok().json(campaign))
+}
+
+pub async fn preview_campaign(
+    path: Path<CampaignPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, CampaignError> {
+    path.validate().map_err(CampaignError::Validation)?;
+
+    let preview =
+        EmailCampaignService::preview(&pool, path.campaign_id).await?;
+    Ok(HttpResponse::Ok().json(preview))
+}
+
+pub async fn send_campaign(
+    path: Path<CampaignPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, CampaignError> {
+    path.validate().map_err(CampaignError::Validation)?;
+
+    let recipients =
+        EmailCampaignService::start(&pool, path.campaign_id).await?;
+    Ok(HttpResponse::Accepted()
+        .json(serde_json::json!({ "recipients": recipients })))
+}
+
+pub async fn cancel_campaign(
+    path: Path<CampaignPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, CampaignError> {
+    path.validate().map_err(CampaignError::Validation)?;
+
+    EmailCampaignService::cancel(&pool, path.campaign_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_campaign_recipients(
+    path: Path<CampaignPath>,
+    query: Query<CampaignRecipientsQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, CampaignError> {
+    path.validate().map_err(CampaignError::Validation)?;
+    query.validate().map_err(CampaignError::Validation)?;
+
+    // 404 rather than an empty list for unknown campaigns
+    EmailCampaignRepository::find_by_id(&pool, path.campaign_id).await?;
+    let recipients = EmailCampaignRepository::find_recipients(
+        &pool,
+        path.campaign_id,
+        &query,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(recipients))
+}
+
+// Posts back to the same URL, token included, so the page never has to
+// echo it
+const UNSUBSCRIBE_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Unsubscribe</title></head>
+<body>
+<form method="post">
+<p>Stop receiving campaign emails?</p>
+<button type="submit">Unsubscribe</button>
+</form>
+</body>
+</html>
+"#;
+
+// Target of the link in campaign emails. Link scanners and prefetchers
+// follow GETs, so this only asks for confirmation.
+pub async fn unsubscribe_page(
+    query: Query<UnsubscribeQuery>,
+) -> Result<HttpResponse, CampaignError> {
+    query.validate().map_err(CampaignError::Validation)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(UNSUBSCRIBE_PAGE))
+}
+
+// Confirmation from the page above and one-click unsubscribe POSTs from
+// mail clients (RFC 8058), the token is all the authentication there is
+pub async fn unsubscribe(
+    query: Query<UnsubscribeQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, CampaignError> {
+    query.validate().map_err(CampaignError::Validation)?;
+
+    EmailCampaignService::unsubscribe(&pool, &query.token).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "unsubscribed": true })))
+}
+
+pub fn unsubscribe_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/unsubscribe")
+            .route(web::get().to(unsubscribe_page))
+            .route(web::post().to(unsubscribe)),
+    );
+}
+
 pub fn email_routes(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(auth_middleware_validator);
+
+    // Room for base64 encoded attachments, a third larger than decoded
+    let json_limit = configs::Config::global().email_attachments_max_size / 3
+        * 4
+        + 1024 * 1024;
+
     cfg.service(
-        web::scope("/email").route("/send", web::post().to(send_email)),
+        web::scope("/email")
+            .wrap(auth)
+            .app_data(web::JsonConfig::default().limit(json_limit))
+            .route(
+                "/send",
+                web::post()
+                    .to(send_email)
+                    .wrap(RateLimit::per_user(
+                        "email",
+                        configs::Config::global().rate_limit_email,
+                    ))
+                    .wrap(RequirePermission("email.send")),
+            )
+            .route(
+                "/log",
+                web::get()
+                    .to(get_email_log)
+                    .wrap(RequirePermission("email.log.read")),
+            )
+            .route(
+                "/outbox",
+                web::get()
+                    .to(get_outbox)
+                    .wrap(RequirePermission("email.outbox.manage")),
+            )
+            .route(
+                "/outbox/{email_id}/requeue",
+                web::post()
+                    .to(requeue_outbox_email)
+                    .wrap(RequirePermission("email.outbox.manage")),
+            )
+            .route(
+                "/templates/{name}/preview",
+                web::get()
+                    .to(preview_template)
+                    .wrap(RequirePermission("email.template.preview")),
+            )
+            .route(
+                "/campaigns",
+                web::get()
+                    .to(get_campaigns)
+                    .wrap(RequirePermission("email.campaigns.manage")),
+            )
+            .route(
+                "/campaigns",
+                web::post()
+                    .to(create_campaign)
+                    .wrap(RequirePermission("email.campaigns.manage")),
+            )
+            .route(
+                "/campaigns/{campaign_id}",
+                web::get()
+                    .to(get_campaign)
+                    .wrap(RequirePermission("email.campaigns.manage")),
+            )
+            .route(
+                "/campaigns/{campaign_id}/preview",
+                web::get()
+                    .to(preview_campaign)
+                    .wrap(RequirePermission("email.campaigns.manage")),
+            )
+            .route(
+                "/campaigns/{campaign_id}/send",
+                web::post()
+                    .to(send_campaign)
+                    .wrap(RequirePermission("email.campaigns.manage")),
+            )
+            .route(
+                "/campaigns/{campaign_id}/cancel",
+                web::post()
+                    .to(cancel_campaign)
+                    .wrap(RequirePermission("email.campaigns.manage")),
+            )
+            .route(
+                "/campaigns/{campaign_id}/recipients",
+                web::get()
+                    .to(get_campaign_recipients)
+                    .wrap(RequirePermission("email.campaigns.manage")),
+            )
+            .route(
+                "/dkim",
+                web::get()
+                    .to(get_dkim_record)
+                    .wrap(RequirePermission("email.dkim.read")),
+            ),
     );
 }
@@ -0,0 +1,163 @@
+use actix_web::{
+    HttpMessage, HttpRequest, HttpResponse, delete, get, post,
+    web::{Data, Json, Path, Query, ServiceConfig, scope},
+};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::{
+    errors::{auth_errors::AuthError, invitation_errors::InvitationError},
+    middlewares::{
+        auth_middleware::auth_middleware_validator,
+        permission_middleware::RequirePermission,
+    },
+    models::{
+        auth_models::Claims,
+        invitation_models::{
+            CreateInvitationRequest, InvitationPath, InvitationsQuery,
+        },
+    },
+    repositories::invitation_repository::InvitationRepository,
+    services::invitation_service::InvitationService,
+};
+
+/// Extracts user ID from the request's JWT.
+fn extract_user_id(req: &HttpRequest) -> Result<i32, AuthError> {
+    req.extensions()
+        .get::<Claims>()
+        .map(|claims| claims.sub)
+        .ok_or(AuthError::Authentication("Missing access token".to_string()))
+}
+
+#[get("", wrap = "RequirePermission(\"invitations.manage\")")]
+pub async fn list_invitations(
+    query: Query<InvitationsQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, InvitationError> {
+    query.validate()?;
+
+    let invitations = InvitationRepository::find_all(&pool, &query).await?;
+    Ok(HttpResponse::Ok().json(invitations))
+}
+
+// The role is granted without anyone assigning it later, so inviting takes
+// the permission to assign roles as well
+#[post(
+    "",
+    wrap = "RequirePermission(\"invitations.manage\")",
+    wrap = "RequirePermission(\"roles.assign\")"
+)]
+pub async fn create_invitation(
+    req: HttpRequest,
+    request: Json<CreateInvitationRequest>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, InvitationError> {
+    let invited_by = extract_user_id(&req)?;
+    request.validate()?;
+
+    let invitation =
+        InvitationService::create(&pool, invited_by, &request).await?;
+    Ok(HttpResponse::Created().json(invitation))
+}
+
+#[get("/{invitation_id}", wrap = "RequirePermission(\"invitations.manage\")")]
+pub async fn get_invitation(
+    path: Path<InvitationPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, InvitationError> {
+    path.validate()?;
+
+    let invitation =
+        InvitationRepository::find_by_id(pool.get_ref(), path.invitation_id)
+            .await?;
+    Ok(HttpResponse::Ok().json(invitation))
+}
+
+#[delete(
+    "/{invitation_id}",
+    wrap = "RequirePermission(\"invitations.manage\")"
+)]
+pub async fn revoke_invitation(
+    path: Path<InvitationPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, InvitationError> {
+    path.validate()?;
+
+    InvitationService::revoke(&pool, path.invitation_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn invitations_routes(cfg: &mut ServiceConfig) {
+    let auth = HttpAuthentication::bearer(auth_middleware_validator);
+
+    cfg.service(
+        scope("/invitations")
+            .wrap(auth)
+            .service(list_invitations)
+            .service(create_invitation)
+            .service(get_invitation)
+            .service(revoke_invitation),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        dev::Service,
+        http::StatusCode,
+        test::{self, TestRequest},
+    };
+
+    use super::*;
+
+    // Stands in for the auth middleware, the request is refused or fails
+    // validation before the pool is used
+    async fn create_as(permissions: &[&str]) -> StatusCode {
+        let permissions: Vec<String> =
+            permissions.iter().map(ToString::to_string).collect();
+        let pool = PgPool::connect_lazy("postgres://localhost/unused")
+            .expect("lazy pool");
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(Claims {
+                        sub: 1,
+                        exp: 0,
+                        iat: 0,
+                        jti: String::new(),
+                        sid: String::new(),
+                        permissions: permissions.clone(),
+                    });
+                    srv.call(req)
+                })
+                .service(scope("/invitations").service(create_invitation)),
+        )
+        .await;
+
+        let request = TestRequest::post()
+            .uri("/invitations")
+            .set_json(serde_json::json!({ "email": "", "role": "admin" }))
+            .to_request();
+        match test::try_call_service(&app, request).await {
+            Ok(response) => response.status(),
+            Err(e) => e.error_response().status(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn inviting_requires_roles_assign() {
+        assert_eq!(
+            create_as(&["invitations.manage"]).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(create_as(&["roles.assign"]).await, StatusCode::FORBIDDEN);
+        // Past both checks, the empty email is rejected
+        assert_eq!(
+            create_as(&["invitations.manage", "roles.assign"]).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
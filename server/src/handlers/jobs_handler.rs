@@ -0,0 +1,60 @@
+use actix_web::{
+    HttpResponse, get, post,
+    web::{Data, Path, ServiceConfig, scope},
+};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::{
+    errors::job_errors::JobError,
+    middlewares::{
+        auth_middleware::auth_middleware_validator,
+        permission_middleware::RequirePermission,
+    },
+    models::job_models::JobPath,
+    repositories::job_repository::JobRepository,
+    services::job_scheduler_service::JobScheduler,
+};
+
+#[get("", wrap = "RequirePermission(\"jobs.manage\")")]
+pub async fn list_jobs(pool: Data<PgPool>) -> Result<HttpResponse, JobError> {
+    let jobs = JobRepository::find_all(&pool).await?;
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+#[get("/{name}", wrap = "RequirePermission(\"jobs.manage\")")]
+pub async fn get_job(
+    path: Path<JobPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, JobError> {
+    path.validate()?;
+
+    let job = JobRepository::find_by_name(&pool, &path.name).await?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+// Runs the job right away and reports how it went, the schedule is left as
+// it is
+#[post("/{name}/run", wrap = "RequirePermission(\"jobs.manage\")")]
+pub async fn run_job(
+    path: Path<JobPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, JobError> {
+    path.validate()?;
+
+    let run = JobScheduler::trigger(&pool, &path.name).await?;
+    Ok(HttpResponse::Ok().json(run))
+}
+
+pub fn jobs_routes(cfg: &mut ServiceConfig) {
+    let auth = HttpAuthentication::bearer(auth_middleware_validator);
+
+    cfg.service(
+        scope("/jobs")
+            .wrap(auth)
+            .service(list_jobs)
+            .service(get_job)
+            .service(run_job),
+    );
+}
@@ -0,0 +1,21 @@
+use actix_web::{
+    HttpResponse, get,
+    http::header::{CacheControl, CacheDirective},
+    web::ServiceConfig,
+};
+
+use crate::utils::jwt_keys::JwtKeyStore;
+
+#[get("/.well-known/jwks.json")]
+pub async fn get_jwks() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(300),
+        ]))
+        .json(JwtKeyStore::global().jwks())
+}
+
+pub fn jwks_routes(cfg: &mut ServiceConfig) {
+    cfg.service(get_jwks);
+}
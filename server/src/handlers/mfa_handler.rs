@@ -0,0 +1,114 @@
+use crate::{
+    errors::auth_errors::AuthError,
+    middlewares::{
+        auth_middleware::auth_middleware_validator,
+        rate_limit_middleware::RateLimit,
+    },
+    models::{
+        auth_models::Claims,
+        mfa_models::{MfaCodeRequest, MfaVerifyRequest},
+        sessions_models::ClientInfo,
+    },
+    services::mfa_services::MfaService,
+};
+use actix_web::{
+    HttpMessage, HttpRequest, HttpResponse, Result, post,
+    web::{Data, Json, ServiceConfig, scope},
+};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use configs::Config;
+use sqlx::PgPool;
+use validator::Validate;
+
+/// Extracts user ID from the request's JWT.
+fn extract_user_id(req: &HttpRequest) -> Result<i32, AuthError> {
+    req.extensions()
+        .get::<Claims>()
+        .map(|claims| claims.sub)
+        .ok_or(AuthError::Authentication("Missing access token".to_string()))
+}
+
+// No access token yet, limited like the password step of the login
+#[post(
+    "/verify",
+    wrap = "RateLimit::per_ip(\"mfa_verify\", Config::global().rate_limit_login)"
+)]
+pub async fn verify_mfa(
+    verify_data: Json<MfaVerifyRequest>,
+    client: ClientInfo,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    verify_data.validate().map_err(AuthError::Validation)?;
+
+    let token_pair =
+        MfaService::verify(&pool, verify_data.into_inner(), client).await?;
+    Ok(HttpResponse::Ok().json(token_pair))
+}
+
+#[post("/enroll")]
+pub async fn enroll_mfa(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    let user_id = extract_user_id(&req)?;
+
+    let enrollment = MfaService::enroll(&pool, user_id).await?;
+    Ok(HttpResponse::Ok().json(enrollment))
+}
+
+#[post("/enable")]
+pub async fn enable_mfa(
+    req: HttpRequest,
+    code_data: Json<MfaCodeRequest>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    let user_id = extract_user_id(&req)?;
+    code_data.validate().map_err(AuthError::Validation)?;
+
+    let recovery_codes =
+        MfaService::enable(&pool, user_id, &code_data.code).await?;
+    Ok(HttpResponse::Ok().json(recovery_codes))
+}
+
+#[post("/disable")]
+pub async fn disable_mfa(
+    req: HttpRequest,
+    code_data: Json<MfaCodeRequest>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    let user_id = extract_user_id(&req)?;
+    code_data.validate().map_err(AuthError::Validation)?;
+
+    MfaService::disable(&pool, user_id, &code_data.code).await?;
+    Ok(HttpResponse::Ok().json("Two-factor authentication disabled"))
+}
+
+#[post("/recovery-codes")]
+pub async fn regenerate_recovery_codes(
+    req: HttpRequest,
+    code_data: Json<MfaCodeRequest>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    let user_id = extract_user_id(&req)?;
+    code_data.validate().map_err(AuthError::Validation)?;
+
+    let recovery_codes =
+        MfaService::regenerate_recovery_codes(&pool, user_id, &code_data.code)
+            .await?;
+    Ok(HttpResponse::Ok().json(recovery_codes))
+}
+
+pub fn mfa_routes(cfg: &mut ServiceConfig) {
+    let auth = HttpAuthentication::bearer(auth_middleware_validator);
+
+    cfg.service(
+        scope("/auth/mfa").service(verify_mfa).service(
+            scope("")
+                .wrap(auth)
+                .service(enroll_mfa)
+                .service(enable_mfa)
+                .service(disable_mfa)
+                .service(regenerate_recovery_codes),
+        ),
+    );
+}
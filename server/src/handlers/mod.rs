@@ -1,7 +1,13 @@
 pub mod auth_handler;
 pub mod cookies_handler;
 pub mod email_handlers;
+pub mod invitations_handler;
+pub mod jobs_handler;
+pub mod jwks_handler;
+pub mod mfa_handler;
 pub mod ping_pong_handler;
 pub mod posts_handler;
+pub mod roles_handler;
+pub mod sessions_handler;
 pub mod temp_registration_handler;
 pub mod users_handler;
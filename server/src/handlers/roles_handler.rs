@@ -0,0 +1,81 @@
+use crate::{
+    errors::roles_errors::RoleError,
+    middlewares::{
+        auth_middleware::auth_middleware_validator,
+        permission_middleware::RequirePermission,
+    },
+    models::roles_models::{UserRolePath, UserRolesPath},
+    repositories::roles_repository::RoleRepository,
+};
+use actix_web::{
+    HttpResponse, Result, delete, get, put,
+    web::{Data, Path, ServiceConfig, scope},
+};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use sqlx::PgPool;
+use validator::Validate;
+
+#[get("", wrap = "RequirePermission(\"roles.read\")")]
+pub async fn get_all_roles(
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, RoleError> {
+    let roles = RoleRepository::get_all(&pool).await?;
+
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+#[get("/users/{user_id}", wrap = "RequirePermission(\"roles.read\")")]
+pub async fn get_user_roles(
+    path: Path<UserRolesPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, RoleError> {
+    path.validate().map_err(RoleError::Validation)?;
+
+    let roles = RoleRepository::get_user_roles(&pool, path.user_id).await?;
+
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+// Role changes reach the user's access token on the next refresh
+#[put(
+    "/users/{user_id}/{role_name}",
+    wrap = "RequirePermission(\"roles.assign\")"
+)]
+pub async fn assign_role(
+    path: Path<UserRolePath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, RoleError> {
+    path.validate().map_err(RoleError::Validation)?;
+
+    RoleRepository::assign_role(&pool, path.user_id, &path.role_name).await?;
+
+    Ok(HttpResponse::Ok().json(()))
+}
+
+#[delete(
+    "/users/{user_id}/{role_name}",
+    wrap = "RequirePermission(\"roles.assign\")"
+)]
+pub async fn revoke_role(
+    path: Path<UserRolePath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, RoleError> {
+    path.validate().map_err(RoleError::Validation)?;
+
+    RoleRepository::revoke_role(&pool, path.user_id, &path.role_name).await?;
+
+    Ok(HttpResponse::Ok().json(()))
+}
+
+pub fn roles_routes(cfg: &mut ServiceConfig) {
+    let auth = HttpAuthentication::bearer(auth_middleware_validator);
+
+    cfg.service(
+        scope("/roles")
+            .wrap(auth)
+            .service(get_all_roles)
+            .service(get_user_roles)
+            .service(assign_role)
+            .service(revoke_role),
+    );
+}
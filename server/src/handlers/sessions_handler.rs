@@ -0,0 +1,116 @@
+use crate::{
+    errors::auth_errors::AuthError,
+    middlewares::{
+        auth_middleware::auth_middleware_validator,
+        permission_middleware::RequirePermission,
+    },
+    models::{
+        auth_models::Claims,
+        sessions_models::{
+            RevokedSessions, SessionPath, UserSessionPath, UserSessionsPath,
+        },
+    },
+    services::auth_services::AuthService,
+};
+use actix_web::{
+    HttpMessage, HttpRequest, HttpResponse, Result, delete, get,
+    web::{Data, Path, ServiceConfig, scope},
+};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use sqlx::PgPool;
+use validator::Validate;
+
+/// Extracts user ID and session ID from the request's JWT.
+fn extract_session(req: &HttpRequest) -> Result<(i32, String), AuthError> {
+    req.extensions()
+        .get::<Claims>()
+        .map(|claims| (claims.sub, claims.sid.clone()))
+        .ok_or(AuthError::Authentication("Missing access token".to_string()))
+}
+
+#[get("")]
+pub async fn list_sessions(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    let (user_id, session_id) = extract_session(&req)?;
+
+    let sessions =
+        AuthService::list_sessions(&pool, user_id, &session_id).await?;
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+#[delete("/{session_id}")]
+pub async fn revoke_session(
+    req: HttpRequest,
+    path: Path<SessionPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    let (user_id, _) = extract_session(&req)?;
+    path.validate().map_err(AuthError::Validation)?;
+
+    AuthService::revoke_session(&pool, user_id, &path.session_id).await?;
+    Ok(HttpResponse::Ok().json("Session revoked successfully"))
+}
+
+#[delete("")]
+pub async fn revoke_all_sessions(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    let (user_id, _) = extract_session(&req)?;
+
+    let revoked = AuthService::revoke_all_sessions(&pool, user_id).await?;
+    Ok(HttpResponse::Ok().json(RevokedSessions { revoked }))
+}
+
+#[get("/users/{user_id}", wrap = "RequirePermission(\"sessions.manage\")")]
+pub async fn list_user_sessions(
+    path: Path<UserSessionsPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    path.validate().map_err(AuthError::Validation)?;
+
+    let sessions = AuthService::list_sessions(&pool, path.user_id, "").await?;
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+#[delete(
+    "/users/{user_id}/{session_id}",
+    wrap = "RequirePermission(\"sessions.manage\")"
+)]
+pub async fn revoke_user_session(
+    path: Path<UserSessionPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    path.validate().map_err(AuthError::Validation)?;
+
+    AuthService::revoke_session(&pool, path.user_id, &path.session_id).await?;
+    Ok(HttpResponse::Ok().json("Session revoked successfully"))
+}
+
+#[delete("/users/{user_id}", wrap = "RequirePermission(\"sessions.manage\")")]
+pub async fn revoke_all_user_sessions(
+    path: Path<UserSessionsPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, AuthError> {
+    path.validate().map_err(AuthError::Validation)?;
+
+    let revoked = AuthService::revoke_all_sessions(&pool, path.user_id).await?;
+    Ok(HttpResponse::Ok().json(RevokedSessions { revoked }))
+}
+
+pub fn sessions_routes(cfg: &mut ServiceConfig) {
+    let auth = HttpAuthentication::bearer(auth_middleware_validator);
+
+    cfg.service(
+        scope("/auth/sessions")
+            .wrap(auth)
+            .service(list_sessions)
+            .service(revoke_all_sessions)
+            .service(list_user_sessions)
+            .service(revoke_all_user_sessions)
+            .service(revoke_user_session)
+            .service(revoke_session),
+    );
+}
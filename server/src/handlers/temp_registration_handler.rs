@@ -1,19 +1,29 @@
 use crate::{
     errors::temp_registration_errors::TempRegistrationError,
-    models::temp_registration::{ConfirmRegistration, CreateTempRegistration},
+    middlewares::rate_limit_middleware::RateLimit,
+    models::{
+        invitation_models::{AcceptInvitation, InvitationTokenQuery},
+        temp_registration::{
+            ConfirmRegistration, CreateTempRegistration, ResendConfirmation,
+        },
+    },
     services::{
         registration_completion_service::RegistrationCompletionService,
         temp_registration_service::TempRegistrationService,
     },
 };
 use actix_web::{
-    HttpResponse, Result, post,
-    web::{Data, Json, ServiceConfig},
+    HttpResponse, Result, get, post,
+    web::{Data, Json, Query, ServiceConfig},
 };
+use configs::Config;
 use sqlx::PgPool;
 use validator::Validate;
 
-#[post("/register/start")]
+#[post(
+    "/register/start",
+    wrap = "RateLimit::per_ip(\"register\", Config::global().rate_limit_register)"
+)]
 pub async fn start_registration(
     registration_data: Json<CreateTempRegistration>,
     pool: Data<PgPool>,
@@ -22,23 +32,48 @@ pub async fn start_registration(
         .validate()
         .map_err(|e| TempRegistrationError::Validation(e.to_string()))?;
 
-    let secret_key = TempRegistrationService::start_registration(
+    TempRegistrationService::start_registration(
         &pool,
         registration_data.into_inner(),
     )
     .await?;
 
+    // The code goes out by email only, that is what verifies the address
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Registration started, check your email for the confirmation code"
+    })))
+}
+
+#[post(
+    "/register/resend",
+    wrap = "RateLimit::per_ip(\"register\", Config::global().rate_limit_register)"
+)]
+pub async fn resend_confirmation(
+    pool: Data<PgPool>,
+    resend_data: Json<ResendConfirmation>,
+) -> Result<HttpResponse, TempRegistrationError> {
+    resend_data
+        .validate()
+        .map_err(|e| TempRegistrationError::Validation(e.to_string()))?;
+
+    TempRegistrationService::resend_code(&pool, &resend_data.email).await?;
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
-        "message": "Registration started successfully",
-        "secret_key": secret_key
+        "message": "A new confirmation code has been sent"
     })))
 }
 
-#[post("/register/complete")]
+#[post(
+    "/register/complete",
+    wrap = "RateLimit::per_ip(\"register\", Config::global().rate_limit_register)"
+)]
 pub async fn complete_registration(
     pool: Data<PgPool>,
     confirmation_data: Json<ConfirmRegistration>,
 ) -> Result<HttpResponse, TempRegistrationError> {
+    confirmation_data
+        .validate()
+        .map_err(|e| TempRegistrationError::Validation(e.to_string()))?;
     let confirmation_data = confirmation_data.into_inner();
 
     let username = RegistrationCompletionService::complete_registration(
@@ -55,6 +90,53 @@ pub async fn complete_registration(
     })))
 }
 
+#[get(
+    "/register/invitation",
+    wrap = "RateLimit::per_ip(\"register\", Config::global().rate_limit_register)"
+)]
+pub async fn invitation_preview(
+    pool: Data<PgPool>,
+    query: Query<InvitationTokenQuery>,
+) -> Result<HttpResponse, TempRegistrationError> {
+    query
+        .validate()
+        .map_err(|e| TempRegistrationError::Validation(e.to_string()))?;
+
+    let preview =
+        TempRegistrationService::invitation_preview(&pool, &query.token)
+            .await?;
+    Ok(HttpResponse::Ok().json(preview))
+}
+
+#[post(
+    "/register/invitation",
+    wrap = "RateLimit::per_ip(\"register\", Config::global().rate_limit_register)"
+)]
+pub async fn accept_invitation(
+    pool: Data<PgPool>,
+    acceptance: Json<AcceptInvitation>,
+) -> Result<HttpResponse, TempRegistrationError> {
+    acceptance
+        .validate()
+        .map_err(|e| TempRegistrationError::Validation(e.to_string()))?;
+
+    let username = RegistrationCompletionService::complete_invitation(
+        &pool,
+        acceptance.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Registration completed successfully",
+        "username": username,
+        "success": true
+    })))
+}
+
 pub fn temp_registration_routes(cfg: &mut ServiceConfig) {
-    cfg.service(start_registration).service(complete_registration);
+    cfg.service(start_registration)
+        .service(resend_confirmation)
+        .service(complete_registration)
+        .service(invitation_preview)
+        .service(accept_invitation);
 }
@@ -1,35 +1,90 @@
 use crate::{
-    errors::users_errors::UserError,
-    models::users_models::{CreateUser, UpdateUser, UserPath},
+    errors::{
+        auth_errors::AuthError,
+        temp_registration_errors::TempRegistrationError,
+        users_errors::UserError,
+    },
+    middlewares::{
+        auth_middleware::auth_middleware_validator,
+        permission_middleware::RequirePermission,
+        rate_limit_middleware::RateLimit,
+    },
+    models::{
+        auth_models::Claims,
+        users_models::{
+            AdminUserView, ChangePasswordRequest, CreateUser, PatchUser,
+            UpdateUser, UserPath, UserView, UsernameAvailability,
+            UsernameAvailabilityQuery, UsersQuery,
+        },
+    },
     repositories::users_repository::UserRepository,
+    services::{
+        auth_services::AuthService,
+        login_attempts_service::LoginAttemptService,
+        temp_registration_service::TempRegistrationService,
+        users_service::UserService,
+    },
+    utils::{
+        password_hasher::PasswordHasher, username_policy::UsernameRejection,
+    },
 };
 use actix_web::{
-    HttpResponse, Result, delete, get, post, put,
-    web::{Data, Json, Path, ServiceConfig},
+    HttpMessage, HttpRequest, HttpResponse, Result, delete, get, patch, post,
+    put,
+    web::{Data, Json, Path, Query, ServiceConfig, scope},
 };
+use actix_web_httpauth::middleware::HttpAuthentication;
+use configs::Config;
 use sqlx::PgPool;
 use validator::Validate;
 
-#[post("/users")]
+fn extract_user_id(req: &HttpRequest) -> Result<i32, AuthError> {
+    req.extensions()
+        .get::<Claims>()
+        .map(|claims| claims.sub)
+        .ok_or(AuthError::Authentication("Missing access token".to_string()))
+}
+
+#[post("", wrap = "RequirePermission(\"users.create\")")]
 pub async fn create_user(
     user_data: Json<CreateUser>,
     pool: Data<PgPool>,
 ) -> Result<HttpResponse, UserError> {
     user_data.validate().map_err(UserError::Validation)?;
-    let user = UserRepository::create(&pool, user_data.into_inner()).await?;
-    Ok(HttpResponse::Ok().json(user))
+
+    let mut user_data = user_data.into_inner();
+    user_data.password = PasswordHasher::hash(&user_data.password).await?;
+
+    let user = UserRepository::create(pool.get_ref(), user_data).await?;
+    Ok(HttpResponse::Ok().json(AdminUserView::from(user)))
 }
 
-#[get("/users")]
+#[get("", wrap = "RequirePermission(\"users.read\")")]
 pub async fn get_all_users(
+    query: Query<UsersQuery>,
     pool: Data<PgPool>,
 ) -> Result<HttpResponse, UserError> {
-    let users = UserRepository::get_all(&pool).await?;
+    query.validate().map_err(UserError::Validation)?;
+
+    let users = UserService::list(&pool, &query).await?;
 
     Ok(HttpResponse::Ok().json(users))
 }
 
-#[get("/users/{user_id}")]
+// Any signed in user, no permission needed for their own account
+#[get("/me")]
+pub async fn get_me(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, UserError> {
+    let user_id = extract_user_id(&req)?;
+
+    let user = UserRepository::find_by_id(&pool, user_id).await?;
+
+    Ok(HttpResponse::Ok().json(UserView::from(user)))
+}
+
+#[get("/{user_id}", wrap = "RequirePermission(\"users.read\")")]
 pub async fn get_user(
     path: Path<UserPath>,
     pool: Data<PgPool>,
@@ -38,9 +93,9 @@ pub async fn get_user(
 
     let user = UserRepository::find_by_id(&pool, path.user_id).await?;
 
-    Ok(HttpResponse::Ok().json(user))
+    Ok(HttpResponse::Ok().json(AdminUserView::from(user)))
 }
-#[put("/users/{user_id}")]
+#[put("/{user_id}", wrap = "RequirePermission(\"users.update\")")]
 pub async fn update_user(
     path: Path<UserPath>,
     user_data: Json<UpdateUser>,
@@ -49,30 +104,234 @@ pub async fn update_user(
     path.validate()?;
     user_data.validate().map_err(UserError::Validation)?;
 
+    let mut user_data = user_data.into_inner();
+    user_data.password = PasswordHasher::hash(&user_data.password).await?;
+
     // User update
     let updated_user =
-        UserRepository::update(&pool, path.user_id, user_data.into_inner())
-            .await?;
+        UserRepository::update(&pool, path.user_id, user_data).await?;
+
+    // The password was replaced, sign the user out everywhere
+    AuthService::revoke_all_sessions(&pool, path.user_id).await?;
+
+    Ok(HttpResponse::Ok().json(AdminUserView::from(updated_user)))
+}
+
+// Only the fields in the body change, the response names the ones that did
+#[patch("/{user_id}", wrap = "RequirePermission(\"users.update\")")]
+pub async fn patch_user(
+    req: HttpRequest,
+    path: Path<UserPath>,
+    user_data: Json<PatchUser>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, UserError> {
+    path.validate().map_err(UserError::Validation)?;
+    user_data.validate().map_err(UserError::Validation)?;
+    let editor_id = extract_user_id(&req)?;
+
+    let update = UserService::patch(
+        &pool,
+        editor_id,
+        path.user_id,
+        user_data.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(update))
+}
+
+#[post(
+    "/me/password",
+    wrap = "RateLimit::per_user(\"password_change\", Config::global().rate_limit_login)"
+)]
+pub async fn change_password(
+    req: HttpRequest,
+    request: Json<ChangePasswordRequest>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, UserError> {
+    request.validate().map_err(UserError::Validation)?;
+    let user_id = extract_user_id(&req)?;
+
+    UserService::change_password(&pool, user_id, &request).await?;
 
-    Ok(HttpResponse::Ok().json(updated_user))
+    Ok(HttpResponse::Ok().json("Password has been changed successfully"))
 }
 
-#[delete("/users/{user_id}")]
+#[delete("/{user_id}", wrap = "RequirePermission(\"users.delete\")")]
 async fn delete_user(
     path: Path<UserPath>,
     pool: Data<PgPool>,
 ) -> Result<HttpResponse, UserError> {
     path.validate().map_err(UserError::Validation)?;
 
+    AuthService::revoke_all_sessions(&pool, path.user_id).await?;
     UserRepository::delete(&pool, path.user_id).await?;
 
     Ok(HttpResponse::Ok().json(()))
 }
 
+#[delete("/{user_id}/lockout", wrap = "RequirePermission(\"users.update\")")]
+pub async fn unlock_user(
+    path: Path<UserPath>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, UserError> {
+    path.validate().map_err(UserError::Validation)?;
+
+    let user = UserRepository::find_by_id(&pool, path.user_id).await?;
+    let unlocked = LoginAttemptService::unlock(&pool, &user).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "unlocked": unlocked })))
+}
+
+// Public, the registration form checks the name while it is typed. Limited
+// like registration, it tells which usernames exist
+#[get(
+    "/users/username-available",
+    wrap = "RateLimit::per_ip(\"register\", Config::global().rate_limit_register)"
+)]
+pub async fn username_available(
+    query: Query<UsernameAvailabilityQuery>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, TempRegistrationError> {
+    query
+        .validate()
+        .map_err(|e| TempRegistrationError::Validation(e.to_string()))?;
+
+    let rejection = TempRegistrationService::username_rejection(
+        &pool,
+        &query.username,
+        None,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(UsernameAvailability {
+        username: query.username.clone(),
+        available: rejection.is_none(),
+        reason: rejection.map(|rejection| rejection.as_ref().to_string()),
+        message: rejection.map(UsernameRejection::message),
+    }))
+}
+
 pub fn users_routes(cfg: &mut ServiceConfig) {
-    cfg.service(create_user)
-        .service(get_user)
-        .service(update_user)
-        .service(get_all_users)
-        .service(delete_user);
+    let auth = HttpAuthentication::bearer(auth_middleware_validator);
+
+    // Registered before the scope, which requires a token
+    cfg.service(username_available).service(
+        scope("/users")
+            .wrap(auth)
+            .service(create_user)
+            .service(get_me)
+            .service(change_password)
+            .service(get_user)
+            .service(update_user)
+            .service(patch_user)
+            .service(get_all_users)
+            .service(delete_user)
+            .service(unlock_user),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        App,
+        dev::Service,
+        http::StatusCode,
+        test::{self, TestRequest},
+    };
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+    use crate::utils::test_support;
+
+    // Stored as the password hash, must not show up in any response
+    const PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$\
+        c2FsdHNhbHRzYWx0$Qq9Sq0bXxuVvYFzWnLr1mQm4CIHg0XHRM6P9zVxJ1rA";
+
+    async fn create_user_row(pool: &PgPool) -> i32 {
+        let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+        let user = UserRepository::create(
+            pool,
+            CreateUser {
+                username: format!("leak{suffix}"),
+                password: PASSWORD_HASH.to_string(),
+                email: format!("leak{suffix}@example.com"),
+                language: None,
+            },
+        )
+        .await
+        .expect("test user");
+        user.id
+    }
+
+    #[actix_web::test]
+    async fn user_responses_have_no_credentials() {
+        test_support::init();
+        let pool = PgPoolOptions::new()
+            .connect(&Config::global().database_url)
+            .await
+            .expect("database");
+        let user_id = create_user_row(&pool).await;
+
+        // Stands in for the auth middleware
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(Claims {
+                        sub: user_id,
+                        exp: 0,
+                        iat: 0,
+                        jti: String::new(),
+                        sid: String::new(),
+                        permissions: vec![
+                            "users.read".to_string(),
+                            "users.create".to_string(),
+                        ],
+                    });
+                    srv.call(req)
+                })
+                .service(
+                    scope("/users")
+                        .service(create_user)
+                        .service(get_me)
+                        .service(get_user)
+                        .service(get_all_users),
+                ),
+        )
+        .await;
+
+        let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+        let requests = [
+            TestRequest::get().uri("/users/me"),
+            TestRequest::get().uri(&format!("/users/{user_id}")),
+            TestRequest::get().uri("/users?q=leak&per_page=100"),
+            TestRequest::post().uri("/users").set_json(serde_json::json!({
+                "username": format!("leak{suffix}"),
+                "password": "hunter2hunter2",
+                "email": format!("leak{suffix}@example.com"),
+            })),
+        ];
+
+        let mut created = None;
+        for request in requests {
+            let response = test::call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body: serde_json::Value = test::read_body_json(response).await;
+            let text = body.to_string();
+            assert!(!text.contains("password"), "password key in {text}");
+            assert!(!text.contains("$argon2"), "password hash in {text}");
+
+            if body["username"] == format!("leak{suffix}") {
+                created = body["id"].as_i64();
+            }
+        }
+
+        UserRepository::delete(&pool, user_id).await.expect("cleanup");
+        let created = created.and_then(|id| i32::try_from(id).ok());
+        UserRepository::delete(&pool, created.expect("created user"))
+            .await
+            .expect("cleanup");
+    }
 }
@@ -2,7 +2,24 @@ use std::sync::Arc;
 
 use crate::{
     handlers::ping_pong_handler::get_ping_pong,
-    services::email_services::{EmailService, LettreEmailService},
+    middlewares::rate_limit_middleware::RateLimit,
+    services::{
+        email_campaign_service::EmailCampaignService,
+        email_outbox_service::EmailOutboxService,
+        email_services::{
+            EmailService, FileEmailService, InMemoryEmailService,
+            LettreEmailService,
+        },
+        job_scheduler_service::JobScheduler,
+        rate_limit_service::{
+            InMemoryRateLimitStore, PostgresRateLimitStore, RateLimitStore,
+        },
+        token_denylist_service::TokenDenylist,
+    },
+    utils::{
+        dkim::DkimSigner, email_templates::EmailTemplates,
+        jwt_keys::JwtKeyStore, username_policy::UsernamePolicy,
+    },
 };
 use actix_web::{
     App, HttpServer,
@@ -31,6 +48,11 @@ async fn main() -> std::io::Result<()> {
 
     // Initialize config
     config::Config::init().expect("Failed to initialize config");
+    JwtKeyStore::init().expect("Failed to load JWT keys");
+    EmailTemplates::init().expect("Failed to load email templates");
+    DkimSigner::init().expect("Failed to load DKIM key");
+    UsernamePolicy::init().expect("Failed to load username blocklist");
+    JobScheduler::init().expect("Failed to parse job schedules");
 
     // Create DB pool
     let database_url = configs::Config::global().database_url.clone();
@@ -44,15 +66,61 @@ async fn main() -> std::io::Result<()> {
 
     // apply_migrations(&pool).await.expect("Failed to apply migrations");
 
+    // Load revoked access tokens before accepting requests
+    TokenDenylist::global().sync(&pool).await;
+    actix_web::rt::spawn(TokenDenylist::run_sync(pool.clone()));
+
+    // Purge expired registrations and refresh tokens on their schedules
+    actix_web::rt::spawn(JobScheduler::run_worker(pool.clone()));
+
     // Create email service
-    let email_service = LettreEmailService::new().map_err(|e| {
-        log::error!("Failed to create email service: {}", e);
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Service error: {}", e),
-        )
-    })?;
-    let email_service1: Arc<dyn EmailService> = Arc::new(email_service);
+    let config = configs::Config::global();
+    let email_service1: Arc<dyn EmailService> =
+        match config.email_transport.as_str() {
+            "smtp" => LettreEmailService::new()
+                .map(|service| Arc::new(service) as Arc<dyn EmailService>),
+            "file" => FileEmailService::new(&config.email_file_dir)
+                .map(|service| Arc::new(service) as Arc<dyn EmailService>),
+            "memory" => Ok(Arc::new(InMemoryEmailService::default()) as _),
+            other => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Unknown EMAIL_TRANSPORT: {other}"),
+                ));
+            }
+        }
+        .map_err(|e| {
+            log::error!("Failed to create email service: {}", e);
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Service error: {}", e),
+            )
+        })?;
+
+    // Deliver emails queued by registration, password reset and lockouts
+    actix_web::rt::spawn(EmailOutboxService::run_worker(
+        pool.clone(),
+        Arc::clone(&email_service1),
+    ));
+
+    // Send running campaigns in throttled batches
+    actix_web::rt::spawn(EmailCampaignService::run_worker(
+        pool.clone(),
+        Arc::clone(&email_service1),
+    ));
+
+    // Create rate limit store
+    let rate_limit_store: Arc<dyn RateLimitStore> =
+        match configs::Config::global().rate_limit_backend.as_str() {
+            "memory" => Arc::new(InMemoryRateLimitStore::default()),
+            "postgres" => Arc::new(PostgresRateLimitStore::new(pool.clone())),
+            other => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Unknown RATE_LIMIT_BACKEND: {other}"),
+                ));
+            }
+        };
 
     // Start HTTP server
     let server_host: String = configs::Config::global().server_host.clone();
@@ -64,14 +132,26 @@ async fn main() -> std::io::Result<()> {
             .wrap(logger)
             .app_data(Data::new(pool.clone()))
             .app_data(Data::from(Arc::clone(&email_service1)))
+            .app_data(Data::from(Arc::clone(&rate_limit_store)))
+            .configure(handlers::jwks_handler::jwks_routes)
             .service(
                 scope("/api")
+                    .wrap(RateLimit::per_ip(
+                        "api",
+                        configs::Config::global().rate_limit_api,
+                    ))
                     .service(get_ping_pong)
                     .configure(handlers::users_handler::users_routes)
+                    .configure(handlers::roles_handler::roles_routes)
                     .configure(handlers::cookies_handler::cookie_routes)
                     .configure(handlers::posts_handler::posts_routes)
                     .configure(handlers::auth_handler::auth_routes)
+                    .configure(handlers::sessions_handler::sessions_routes)
+                    .configure(handlers::jobs_handler::jobs_routes)
+                    .configure(handlers::invitations_handler::invitations_routes)
+                    .configure(handlers::mfa_handler::mfa_routes)
                     .configure(handlers::email_handlers::email_routes)
+                    .configure(handlers::email_handlers::unsubscribe_routes)
                     .configure(handlers::temp_registration_handler::temp_registration_routes),
             )
     })
@@ -1,4 +1,6 @@
+use crate::errors::auth_errors::AuthError;
 use crate::services::auth_services::AuthService;
+use crate::services::token_denylist_service::TokenDenylist;
 use actix_web::HttpMessage;
 use actix_web::{Error, dev::ServiceRequest};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
@@ -12,6 +14,14 @@ pub async fn auth_middleware_validator(
     log::debug!("Validating token: {}", token);
 
     match AuthService::validate_access_token(token) {
+        Ok(claims) if TokenDenylist::global().is_revoked(&claims.jti) => {
+            log::warn!(
+                "Revoked token {} used by user {}",
+                claims.jti,
+                claims.sub
+            );
+            Err((AuthError::TokenRevoked.into(), req))
+        }
         Ok(claims) => {
             req.extensions_mut().insert(claims);
             Ok(req)
@@ -1 +1,3 @@
 pub mod auth_middleware;
+pub mod permission_middleware;
+pub mod rate_limit_middleware;
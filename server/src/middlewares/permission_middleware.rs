@@ -0,0 +1,78 @@
+use std::{
+    future::{Future, Ready, ready},
+    pin::Pin,
+};
+
+use actix_web::{
+    Error, HttpMessage,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+
+use crate::{errors::auth_errors::AuthError, models::auth_models::Claims};
+
+/// Rejects requests whose access token does not carry the given permission.
+///
+/// Must run inside `auth_middleware_validator`, which puts `Claims` into the
+/// request extensions:
+///
+/// ```ignore
+/// #[delete("/{user_id}", wrap = "RequirePermission(\"users.delete\")")]
+/// ```
+pub struct RequirePermission(pub &'static str);
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequirePermissionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePermissionMiddleware { service, permission: self.0 }))
+    }
+}
+
+pub struct RequirePermissionMiddleware<S> {
+    service: S,
+    permission: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let allowed = match req.extensions().get::<Claims>() {
+            Some(claims) => claims.has_permission(self.permission),
+            None => {
+                return Box::pin(ready(Err(AuthError::Authentication(
+                    "Missing access token".to_string(),
+                )
+                .into())));
+            }
+        };
+
+        if !allowed {
+            return Box::pin(ready(Err(AuthError::Forbidden(format!(
+                "Missing permission '{}'",
+                self.permission
+            ))
+            .into())));
+        }
+
+        Box::pin(self.service.call(req))
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    future::{Future, Ready, ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    Error, HttpMessage,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    http::header::HeaderMap,
+    web::Data,
+};
+use configs::RateLimitQuota;
+
+use crate::{
+    errors::rate_limit_errors::RateLimitError,
+    models::{auth_models::Claims, rate_limit_models::RateLimitStatus},
+    services::rate_limit_service::{RateLimitService, RateLimitStore},
+    utils::client_ip::client_ip,
+};
+
+/// What requests are counted together.
+#[derive(Debug, Clone, Copy)]
+pub enum RateLimitKey {
+    Ip,
+    /// User id from `Claims`. Must run inside `auth_middleware_validator`,
+    /// anonymous requests fall back to the client IP.
+    User,
+}
+
+/// Limits requests of a scope or route against a quota, counted in the
+/// `RateLimitStore` registered as app data:
+///
+/// ```ignore
+/// #[post("/login", wrap = "RateLimit::per_ip(\"login\", Config::global().rate_limit_login)")]
+/// ```
+///
+/// Every response carries the `RateLimit-*` headers, rejected requests get
+/// a 429 with `Retry-After`. If the store fails the request is let through.
+pub struct RateLimit {
+    name: &'static str,
+    quota: RateLimitQuota,
+    key: RateLimitKey,
+}
+
+impl RateLimit {
+    pub fn new(
+        name: &'static str,
+        quota: RateLimitQuota,
+        key: RateLimitKey,
+    ) -> Self {
+        RateLimit { name, quota, key }
+    }
+
+    pub fn per_ip(name: &'static str, quota: RateLimitQuota) -> Self {
+        Self::new(name, quota, RateLimitKey::Ip)
+    }
+
+    pub fn per_user(name: &'static str, quota: RateLimitQuota) -> Self {
+        Self::new(name, quota, RateLimitKey::User)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>
+        + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            name: self.name,
+            quota: self.quota,
+            key: self.key,
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    name: &'static str,
+    quota: RateLimitQuota,
+    key: RateLimitKey,
+}
+
+impl<S> RateLimitMiddleware<S> {
+    fn bucket_key(&self, req: &ServiceRequest) -> String {
+        let client = match self.key {
+            RateLimitKey::User => req
+                .extensions()
+                .get::<Claims>()
+                .map(|claims| format!("user:{}", claims.sub)),
+            RateLimitKey::Ip => None,
+        };
+
+        let client = client.unwrap_or_else(|| {
+            let ip = client_ip(req.request())
+                .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+            format!("ip:{ip}")
+        });
+
+        format!("{}:{client}", self.name)
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>
+        + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let quota = self.quota;
+        let key = self.bucket_key(&req);
+        let store = req.app_data::<Data<dyn RateLimitStore>>().cloned();
+
+        Box::pin(async move {
+            let Some(store) = store else {
+                log::warn!("No rate limit store registered, {key} not limited");
+                return service.call(req).await;
+            };
+
+            let status =
+                match RateLimitService::check(&**store, &key, quota).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        log::error!("Rate limit check for {key} failed: {e}");
+                        return service.call(req).await;
+                    }
+                };
+
+            if status.exceeded {
+                log::warn!("Rate limit exceeded for {key}");
+                return Err(RateLimitError::Exceeded(status).into());
+            }
+
+            let mut res = service.call(req).await?;
+            insert_headers(res.headers_mut(), &status);
+            Ok(res)
+        })
+    }
+}
+
+// With nested limits the one closest to running out is reported
+fn insert_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    let reported_remaining = headers
+        .get("ratelimit-remaining")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u32>().ok());
+
+    if reported_remaining.is_some_and(|reported| reported <= status.remaining) {
+        return;
+    }
+
+    for (name, value) in status.headers() {
+        headers.insert(name, value);
+    }
+}
@@ -3,16 +3,27 @@ use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::models::{mfa_models::MfaPending, sessions_models::ClientInfo};
+
 #[derive(Debug, Serialize, Deserialize)]
 
 // JWT Claims structure
 // sub: subject (user id)
 // exp: expiration time (as UTC timestamp)
 // iat: issued at (as UTC timestamp)
+// jti: token id, checked against the revocation denylist
+// sid: session id (refresh token family the token was issued for)
+// permissions: permission names resolved from user roles at issue time
 pub struct Claims {
     pub sub: i32, // user id
     pub exp: i32,
     pub iat: i32,
+    #[serde(default)]
+    pub jti: String,
+    #[serde(default)]
+    pub sid: String,
+    #[serde(default)]
+    pub permissions: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,15 +32,37 @@ pub struct TokenPair {
     pub refresh_token: String,
 }
 
+// Users with 2FA get an mfa_pending token instead of the TokenPair
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(TokenPair),
+    MfaPending(MfaPending),
+}
+
+// Every token issued by rotation shares the family_id of the login that
+// started the chain; rotated_at marks tokens that were already exchanged.
+// A family is what users see as a session, created_at is carried over from
+// the login on every rotation.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RefreshToken {
     pub token: String,
     pub user_id: i32,
+    pub family_id: String,
     pub expires_at: OffsetDateTime,
+    pub rotated_at: Option<OffsetDateTime>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: OffsetDateTime,
 }
 
 impl Claims {
-    pub fn new(user_id: i32) -> Self {
+    pub fn new(
+        user_id: i32,
+        session_id: String,
+        permissions: Vec<String>,
+    ) -> Self {
         let jwt_access_expires = configs::Config::global().jwt_access_expires;
 
         let iat = OffsetDateTime::now_utc();
@@ -39,19 +72,56 @@ impl Claims {
             sub: user_id,
             exp: exp.unix_timestamp() as i32,
             iat: iat.unix_timestamp() as i32,
+            jti: Uuid::new_v4().to_string(),
+            sid: session_id,
+            permissions,
         }
     }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
 }
 
 impl RefreshToken {
-    pub fn new(user_id: i32) -> Self {
+    pub fn new(user_id: i32, client: ClientInfo) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self::issue(user_id, Uuid::new_v4().to_string(), now, client)
+    }
+
+    // Next token of the same family
+    pub fn rotate(&self, client: ClientInfo) -> Self {
+        Self::issue(
+            self.user_id,
+            self.family_id.clone(),
+            self.created_at,
+            client,
+        )
+    }
+
+    fn issue(
+        user_id: i32,
+        family_id: String,
+        created_at: OffsetDateTime,
+        client: ClientInfo,
+    ) -> Self {
         let jwt_refresh_expires = configs::Config::global().jwt_refresh_expires;
 
         let token = Uuid::new_v4().to_string();
-        let expires_at =
-            OffsetDateTime::now_utc() + Duration::seconds(jwt_refresh_expires); // Refresh token expires in 30 days
+        let now = OffsetDateTime::now_utc();
+        let expires_at = now + Duration::seconds(jwt_refresh_expires); // Refresh token expires in 30 days
 
-        RefreshToken { token, user_id, expires_at }
+        RefreshToken {
+            token,
+            user_id,
+            family_id,
+            expires_at,
+            rotated_at: None,
+            user_agent: client.user_agent,
+            ip_address: client.ip_address,
+            created_at,
+            last_used_at: now,
+        }
     }
 }
 
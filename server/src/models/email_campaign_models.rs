@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use strum_macros::AsRefStr;
+use time::OffsetDateTime;
+use validator::Validate;
+
+use crate::models::email_template_models::RenderedEmail;
+
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum CampaignStatus {
+    // Editable, nothing sent yet
+    Draft,
+    Sending,
+    Finished,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum RecipientStatus {
+    Pending,
+    Sent,
+    Failed,
+    // Opted out or cancelled before delivery
+    Skipped,
+}
+
+// Filter over `users`, every field left out matches everyone
+#[derive(Debug, Default, Deserialize, Serialize, Validate)]
+pub struct CampaignSegment {
+    #[validate(range(
+        min = 1,
+        max = 3650,
+        message = "Registered within days must be between 1 and 3650"
+    ))]
+    pub registered_within_days: Option<i32>,
+
+    #[validate(length(min = 1, max = 64, message = "Invalid role name"))]
+    pub role: Option<String>,
+
+    #[validate(length(
+        min = 2,
+        max = 16,
+        message = "Language must be between 2 and 16 chars"
+    ))]
+    pub language: Option<String>,
+}
+
+// Subject and bodies are template sources rendered for every recipient with
+// `username`, `email` and `unsubscribe_url`. They may extend the shared
+// layouts of the email templates.
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateCampaignRequest {
+    #[validate(length(
+        min = 1,
+        max = 255,
+        message = "Name must be between 1 and 255 chars"
+    ))]
+    pub name: String,
+
+    #[validate(length(
+        min = 1,
+        max = 998,
+        message = "Subject must be between 1 and 998 chars"
+    ))]
+    pub subject: String,
+
+    #[validate(length(min = 1, message = "Text body cannot be empty"))]
+    pub text_body: String,
+
+    pub html_body: Option<String>,
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub segment: CampaignSegment,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct Campaign {
+    pub id: i32,
+    pub name: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+    pub segment_registered_within_days: Option<i32>,
+    pub segment_role: Option<String>,
+    pub segment_language: Option<String>,
+    pub status: String,
+    pub created_by: Option<i32>,
+    pub created_at: OffsetDateTime,
+    pub started_at: Option<OffsetDateTime>,
+    pub finished_at: Option<OffsetDateTime>,
+}
+
+impl Campaign {
+    pub fn segment(&self) -> CampaignSegment {
+        CampaignSegment {
+            registered_within_days: self.segment_registered_within_days,
+            role: self.segment_role.clone(),
+            language: self.segment_language.clone(),
+        }
+    }
+}
+
+// List view with delivery progress
+#[derive(Debug, FromRow, Serialize)]
+pub struct CampaignSummary {
+    pub id: i32,
+    pub name: String,
+    pub status: String,
+    pub created_by: Option<i32>,
+    pub created_at: OffsetDateTime,
+    pub started_at: Option<OffsetDateTime>,
+    pub finished_at: Option<OffsetDateTime>,
+    pub pending: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub skipped: i64,
+}
+
+// Claimed by the worker for delivery
+#[derive(Debug, FromRow)]
+pub struct CampaignDelivery {
+    pub user_id: i32,
+    pub email: String,
+    pub username: String,
+    pub language: Option<String>,
+    pub email_opt_out: bool,
+}
+
+// Member of the segment, used for the preview sample
+#[derive(Debug, FromRow)]
+pub struct SegmentMember {
+    pub email: String,
+    pub username: String,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct CampaignRecipient {
+    pub user_id: i32,
+    pub email: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub sent_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CampaignPreview {
+    pub recipients: i64,
+    // First recipient's copy, None for an empty segment
+    pub sample: Option<RenderedEmail>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CampaignPath {
+    #[validate(range(min = 1, message = "Campaign ID must be positive"))]
+    pub campaign_id: i32,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CampaignRecipientsQuery {
+    pub status: Option<String>,
+
+    #[validate(range(
+        min = 1,
+        max = 100,
+        message = "Limit must be between 1 and 100"
+    ))]
+    pub limit: Option<i64>,
+
+    #[validate(range(min = 0, message = "Offset cannot be negative"))]
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UnsubscribeQuery {
+    #[validate(length(min = 1, max = 128, message = "Invalid token"))]
+    pub token: String,
+}
@@ -0,0 +1,109 @@
+// Message handed to `EmailService::send`, built with the chained setters:
+//
+// EmailMessage::new("Weekly report")
+//     .to("ops@example.com")
+//     .cc("audit@example.com")
+//     .text_body("Report attached")
+//     .attachment("report.csv", "text/csv", csv_bytes)
+#[derive(Debug, Clone, Default)]
+pub struct EmailMessage {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub reply_to: Option<String>,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub attachments: Vec<EmailAttachment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+    // Set for inline images, referenced from the HTML as `cid:<content_id>`
+    pub content_id: Option<String>,
+}
+
+impl EmailMessage {
+    pub fn new(subject: impl Into<String>) -> Self {
+        EmailMessage { subject: subject.into(), ..Default::default() }
+    }
+
+    pub fn to(mut self, address: impl Into<String>) -> Self {
+        self.to.push(address.into());
+        self
+    }
+
+    pub fn cc(mut self, address: impl Into<String>) -> Self {
+        self.cc.push(address.into());
+        self
+    }
+
+    pub fn bcc(mut self, address: impl Into<String>) -> Self {
+        self.bcc.push(address.into());
+        self
+    }
+
+    pub fn reply_to(mut self, address: impl Into<String>) -> Self {
+        self.reply_to = Some(address.into());
+        self
+    }
+
+    pub fn text_body(mut self, body: impl Into<String>) -> Self {
+        self.text_body = body.into();
+        self
+    }
+
+    pub fn html_body(mut self, body: impl Into<String>) -> Self {
+        self.html_body = Some(body.into());
+        self
+    }
+
+    pub fn header(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn attachment(
+        mut self,
+        filename: impl Into<String>,
+        content_type: impl Into<String>,
+        content: Vec<u8>,
+    ) -> Self {
+        self.attachments.push(EmailAttachment {
+            filename: filename.into(),
+            content_type: content_type.into(),
+            content,
+            content_id: None,
+        });
+        self
+    }
+
+    pub fn inline_image(
+        mut self,
+        content_id: impl Into<String>,
+        content_type: impl Into<String>,
+        content: Vec<u8>,
+    ) -> Self {
+        let content_id = content_id.into();
+        self.attachments.push(EmailAttachment {
+            filename: content_id.clone(),
+            content_type: content_type.into(),
+            content,
+            content_id: Some(content_id),
+        });
+        self
+    }
+
+    /// Every address the message is delivered to, Bcc included.
+    pub fn recipients(&self) -> impl Iterator<Item = &str> {
+        self.to.iter().chain(&self.cc).chain(&self.bcc).map(String::as_str)
+    }
+}
@@ -1,10 +1,37 @@
-use serde::{Deserialize, Serialize};
-use validator::Validate;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Deserialize, Serialize, Validate)]
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::FromRow;
+use strum_macros::AsRefStr;
+use time::OffsetDateTime;
+use validator::{Validate, ValidateEmail, ValidationError};
+
+#[derive(Debug, Deserialize, Validate)]
 pub struct SendEmailRequest {
-    #[validate(email(message = "Invalid email address"))]
-    pub to: String,
+    // A single address or a list of them
+    #[serde(deserialize_with = "one_or_many")]
+    #[validate(
+        length(min = 1, max = 50, message = "Between 1 and 50 recipients"),
+        custom(function = "validate_addresses")
+    )]
+    pub to: Vec<String>,
+
+    #[serde(default)]
+    #[validate(
+        length(max = 50, message = "At most 50 Cc recipients"),
+        custom(function = "validate_addresses")
+    )]
+    pub cc: Vec<String>,
+
+    #[serde(default)]
+    #[validate(
+        length(max = 50, message = "At most 50 Bcc recipients"),
+        custom(function = "validate_addresses")
+    )]
+    pub bcc: Vec<String>,
+
+    #[validate(email(message = "Invalid reply-to address"))]
+    pub reply_to: Option<String>,
 
     #[validate(length(min = 1, message = "Subject cannot be empty"))]
     pub subject: String,
@@ -13,6 +40,79 @@ pub struct SendEmailRequest {
     pub body: String,
 
     pub is_html: Option<bool>,
+
+    #[serde(default)]
+    #[validate(length(max = 10, message = "At most 10 attachments"), nested)]
+    pub attachments: Vec<AttachmentRequest>,
+
+    // Extra headers such as `List-Id`, those set from the fields above
+    // are refused when the message is built
+    #[serde(default)]
+    #[validate(
+        length(max = 20, message = "At most 20 headers"),
+        custom(function = "validate_headers")
+    )]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct AttachmentRequest {
+    #[validate(length(min = 1, max = 255, message = "Invalid filename"))]
+    pub filename: String,
+
+    #[validate(length(min = 1, max = 127, message = "Invalid content type"))]
+    pub content_type: String,
+
+    // Base64 encoded
+    pub content: String,
+
+    // Makes it an inline image, referenced as `cid:<content_id>`
+    #[validate(length(min = 1, max = 255, message = "Invalid content ID"))]
+    pub content_id: Option<String>,
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(address) => vec![address],
+        OneOrMany::Many(addresses) => addresses,
+    })
+}
+
+fn validate_addresses(addresses: &[String]) -> Result<(), ValidationError> {
+    if addresses.iter().all(ValidateEmail::validate_email) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("email")
+            .with_message("Invalid email address".into()))
+    }
+}
+
+// Values are written as given, a line break would start a new header
+fn validate_headers(
+    headers: &BTreeMap<String, String>,
+) -> Result<(), ValidationError> {
+    let valid = headers.iter().all(|(name, value)| {
+        (1..=76).contains(&name.len())
+            && value.len() <= 998
+            && !value.contains(['\r', '\n'])
+    });
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("headers")
+            .with_message("Invalid header name or value".into()))
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -20,3 +120,44 @@ pub struct SendEmailResponse {
     pub success: bool,
     pub message: String,
 }
+
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum EmailStatus {
+    // Quota reserved, the send has not finished yet
+    Pending,
+    Sent,
+    Failed,
+    Rejected,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct EmailLogEntry {
+    pub id: i32,
+    pub sender_id: Option<i32>,
+    pub recipient: String,
+    pub subject: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct EmailLogQuery {
+    #[validate(range(min = 1, message = "User ID must be positive"))]
+    pub sender_id: Option<i32>,
+
+    pub recipient: Option<String>,
+
+    pub status: Option<String>,
+
+    #[validate(range(
+        min = 1,
+        max = 100,
+        message = "Limit must be between 1 and 100"
+    ))]
+    pub limit: Option<i64>,
+
+    #[validate(range(min = 0, message = "Offset cannot be negative"))]
+    pub offset: Option<i64>,
+}
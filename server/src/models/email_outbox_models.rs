@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use strum_macros::AsRefStr;
+use time::OffsetDateTime;
+use validator::Validate;
+
+use crate::models::email_template_models::RenderedEmail;
+
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum OutboxStatus {
+    Pending,
+    Sent,
+    // Gave up after the last retry, waits for an admin to requeue it
+    Dead,
+}
+
+// Email queued together with the change that triggered it, sent later by
+// the outbox worker
+#[derive(Debug)]
+pub struct NewOutboxEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+}
+
+impl NewOutboxEmail {
+    pub fn new(recipient: &str, email: RenderedEmail) -> Self {
+        NewOutboxEmail {
+            recipient: recipient.to_string(),
+            subject: email.subject,
+            text_body: email.text_body,
+            html_body: email.html_body,
+        }
+    }
+}
+
+// Claimed by the worker for one delivery attempt
+#[derive(Debug, FromRow)]
+pub struct OutboxEmail {
+    pub id: i32,
+    pub recipient: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+    pub attempts: i32,
+}
+
+// Admin view, bodies are left out as they may carry one-time codes
+#[derive(Debug, FromRow, Serialize)]
+pub struct OutboxEntry {
+    pub id: i32,
+    pub recipient: String,
+    pub subject: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+    pub sent_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct OutboxQuery {
+    pub status: Option<String>,
+
+    #[validate(range(
+        min = 1,
+        max = 100,
+        message = "Limit must be between 1 and 100"
+    ))]
+    pub limit: Option<i64>,
+
+    #[validate(range(min = 0, message = "Offset cannot be negative"))]
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct OutboxPath {
+    #[validate(range(min = 1, message = "Email ID must be positive"))]
+    pub email_id: i32,
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Serialize)]
+pub struct RenderedEmail {
+    pub locale: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct TemplatePath {
+    #[validate(length(min = 1, max = 64, message = "Invalid template name"))]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct TemplatePreviewQuery {
+    #[validate(length(
+        min = 2,
+        max = 16,
+        message = "Language must be between 2 and 16 chars"
+    ))]
+    pub language: Option<String>,
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use validator::Validate;
+
+use crate::models::users_models::validate_username_policy;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateInvitationRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+
+    // Granted to the user on acceptance
+    #[validate(length(
+        min = 1,
+        max = 64,
+        message = "Role name must be between 1 and 64 characters"
+    ))]
+    pub role: String,
+
+    // Seconds, INVITATION_EXPIRES when left out
+    #[validate(range(
+        min = 3600,
+        max = 7_776_000,
+        message = "Invitation must expire within 1 hour to 90 days"
+    ))]
+    pub expires_in: Option<i64>,
+
+    // Language of the invitation email, kept for the new user
+    #[validate(length(
+        min = 2,
+        max = 16,
+        message = "Language must be between 2 and 16 chars"
+    ))]
+    pub language: Option<String>,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct Invitation {
+    pub id: i32,
+    pub email: String,
+    pub role: String,
+    pub language: Option<String>,
+    // pending, accepted, revoked or expired
+    pub status: String,
+    pub invited_by: Option<i32>,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub accepted_at: Option<OffsetDateTime>,
+    pub accepted_by: Option<i32>,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+// Pending invitation looked up by its token
+#[derive(Debug, FromRow)]
+pub struct PendingInvitation {
+    pub id: i32,
+    pub email: String,
+    pub role_id: i32,
+    pub role: String,
+    pub language: Option<String>,
+    pub expires_at: OffsetDateTime,
+}
+
+// What the invitee sees before choosing a username and password
+#[derive(Debug, Serialize)]
+pub struct InvitationPreview {
+    pub email: String,
+    pub role: String,
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct InvitationPath {
+    #[validate(range(min = 1, message = "Invitation ID must be positive"))]
+    pub invitation_id: i32,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct InvitationsQuery {
+    pub status: Option<String>,
+
+    #[validate(range(
+        min = 1,
+        max = 100,
+        message = "Limit must be between 1 and 100"
+    ))]
+    pub limit: Option<i64>,
+
+    #[validate(range(min = 0, message = "Offset cannot be negative"))]
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct InvitationTokenQuery {
+    #[validate(length(min = 1, max = 128, message = "Invalid token"))]
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AcceptInvitation {
+    #[validate(length(min = 1, max = 128, message = "Invalid token"))]
+    pub token: String,
+
+    #[validate(custom(function = "validate_username_policy"))]
+    pub username: String,
+
+    #[validate(length(min = 1, message = "Password cannot be empty"))]
+    pub password: String,
+
+    // Overrides the language the invitation was sent in
+    #[validate(length(
+        min = 2,
+        max = 16,
+        message = "Language must be between 2 and 16 chars"
+    ))]
+    pub language: Option<String>,
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use strum_macros::AsRefStr;
+use time::OffsetDateTime;
+use validator::Validate;
+
+/// Maintenance jobs run by the scheduler, the name is the key of their row
+/// in `scheduled_jobs` and of their advisory lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ScheduledJob {
+    PurgeRegistrations,
+    PurgeRefreshTokens,
+}
+
+impl ScheduledJob {
+    pub const ALL: [ScheduledJob; 2] =
+        [ScheduledJob::PurgeRegistrations, ScheduledJob::PurgeRefreshTokens];
+
+    pub fn from_name(name: &str) -> Option<ScheduledJob> {
+        Self::ALL.into_iter().find(|job| job.as_ref() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum JobTrigger {
+    Schedule,
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum JobRunStatus {
+    Succeeded,
+    Failed,
+}
+
+// Outcome of one run, also kept as the job's last run
+#[derive(Debug, Serialize)]
+pub struct JobRun {
+    pub name: String,
+    pub trigger: String,
+    pub status: String,
+    pub error: Option<String>,
+    // Rows purged
+    pub affected: Option<i64>,
+    pub started_at: OffsetDateTime,
+    pub finished_at: OffsetDateTime,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct JobStatus {
+    pub name: String,
+    pub schedule: String,
+    pub next_run_at: OffsetDateTime,
+    // Holds the advisory lock on any instance right now
+    pub running: bool,
+    pub last_trigger: Option<String>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub last_affected: Option<i64>,
+    pub last_started_at: Option<OffsetDateTime>,
+    pub last_finished_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct JobPath {
+    #[validate(length(min = 1, max = 64, message = "Invalid job name"))]
+    pub name: String,
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+
+use strum_macros::AsRefStr;
+use time::OffsetDateTime;
+
+// Failed logins are counted separately per account and per client IP.
+// Logins that match no user are counted under the submitted name.
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum LoginAttemptScope {
+    User,
+    Username,
+    Ip,
+}
+
+/// Account a login is counted against. The user ID keeps the username,
+/// the email and their casings on the same counter.
+#[derive(Debug, Clone)]
+pub enum LoginAccount {
+    User(i32),
+    // Lowercased login of a user that doesn't exist
+    Unknown(String),
+}
+
+impl LoginAccount {
+    pub fn unknown(login: &str) -> LoginAccount {
+        LoginAccount::Unknown(login.to_lowercase())
+    }
+
+    pub fn scope(&self) -> LoginAttemptScope {
+        match self {
+            LoginAccount::User(_) => LoginAttemptScope::User,
+            LoginAccount::Unknown(_) => LoginAttemptScope::Username,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        match self {
+            LoginAccount::User(user_id) => user_id.to_string(),
+            LoginAccount::Unknown(login) => login.clone(),
+        }
+    }
+}
+
+impl fmt::Display for LoginAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoginAccount::User(user_id) => write!(f, "user {user_id}"),
+            LoginAccount::Unknown(login) => write!(f, "unknown login {login}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LoginAttempt {
+    pub failed_attempts: i32,
+    pub last_failed_at: OffsetDateTime,
+    pub locked_until: Option<OffsetDateTime>,
+}
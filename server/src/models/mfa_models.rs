@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use validator::Validate;
+
+#[derive(Debug, FromRow)]
+pub struct MfaSettings {
+    pub user_id: i32,
+    pub secret: String,
+    pub enabled: bool,
+}
+
+// Pending second login step, the token is handed out instead of a TokenPair
+#[derive(Debug, FromRow)]
+pub struct MfaChallenge {
+    pub token: String,
+    pub user_id: i32,
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaPending {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct MfaCodeRequest {
+    #[validate(length(
+        min = 6,
+        max = 32,
+        message = "Code must be between 6 and 32 characters"
+    ))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct MfaVerifyRequest {
+    #[validate(length(
+        min = 36,
+        max = 36,
+        message = "MFA token must be 36 characters long"
+    ))]
+    pub mfa_token: String,
+
+    // TOTP code or one of the recovery codes
+    #[validate(length(
+        min = 6,
+        max = 32,
+        message = "Code must be between 6 and 32 characters"
+    ))]
+    pub code: String,
+}
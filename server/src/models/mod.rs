@@ -1,7 +1,21 @@
 pub mod auth_models;
 pub mod cookies_models;
+pub mod email_campaign_models;
+pub mod email_message_models;
 pub mod email_models;
+pub mod email_outbox_models;
+pub mod email_template_models;
+pub mod invitation_models;
+pub mod job_models;
+pub mod login_attempts_models;
+pub mod mfa_models;
+pub mod password_reset_models;
 pub mod ping_pong_models;
 pub mod posts_models;
+pub mod rate_limit_models;
+pub mod revoked_tokens_models;
+pub mod roles_models;
+pub mod security_events_models;
+pub mod sessions_models;
 pub mod temp_registration;
 pub mod users_models;
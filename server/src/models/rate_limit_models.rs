@@ -0,0 +1,48 @@
+use actix_web::http::header::{HeaderName, HeaderValue};
+
+// Hits counted in the current fixed window and in the one before it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WindowHits {
+    pub current: i64,
+    pub previous: i64,
+}
+
+// Outcome of one request against a quota, reported in the RateLimit-*
+// response headers. `reset` is the number of seconds until the current
+// window ends.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset: u64,
+    pub window: u64,
+    pub exceeded: bool,
+}
+
+impl RateLimitStatus {
+    // Headers from the IETF RateLimit header fields draft
+    pub fn headers(&self) -> [(HeaderName, HeaderValue); 4] {
+        [
+            (
+                HeaderName::from_static("ratelimit-limit"),
+                HeaderValue::from(self.limit),
+            ),
+            (
+                HeaderName::from_static("ratelimit-remaining"),
+                HeaderValue::from(self.remaining),
+            ),
+            (
+                HeaderName::from_static("ratelimit-reset"),
+                HeaderValue::from(self.reset),
+            ),
+            (
+                HeaderName::from_static("ratelimit-policy"),
+                HeaderValue::from_str(&format!(
+                    "{};w={}",
+                    self.limit, self.window
+                ))
+                .expect("Rate limit policy is a valid header value"),
+            ),
+        ]
+    }
+}
@@ -0,0 +1,8 @@
+use time::OffsetDateTime;
+
+// Denylisted access token, kept until the token would have expired anyway
+#[derive(Debug)]
+pub struct RevokedToken {
+    pub jti: String,
+    pub expires_at: OffsetDateTime,
+}
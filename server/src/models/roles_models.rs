@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct Role {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UserRolesPath {
+    #[validate(range(min = 1, message = "User ID must be positive"))]
+    pub user_id: i32,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UserRolePath {
+    #[validate(range(min = 1, message = "User ID must be positive"))]
+    pub user_id: i32,
+
+    #[validate(length(
+        min = 1,
+        max = 64,
+        message = "Role name must be between 1 and 64 characters"
+    ))]
+    pub role_name: String,
+}
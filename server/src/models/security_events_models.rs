@@ -0,0 +1,11 @@
+use strum_macros::{AsRefStr, Display};
+
+#[derive(Debug, Clone, Copy, AsRefStr, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum SecurityEventType {
+    RefreshTokenReuse,
+    PasswordReset,
+    AccountLocked,
+    UserUpdated,
+    PasswordChanged,
+}
@@ -0,0 +1,72 @@
+use std::future::{Ready, ready};
+
+use actix_web::{
+    Error, FromRequest, HttpRequest, dev::Payload, http::header::USER_AGENT,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use validator::Validate;
+
+use crate::utils::client_ip::client_ip;
+
+/// Device metadata of the client that logs in or refreshes a session.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl FromRequest for ClientInfo {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let ip_address = client_ip(req).map(|ip| ip.to_string());
+
+        ready(Ok(ClientInfo { user_agent, ip_address }))
+    }
+}
+
+// Session is the active (not yet rotated) token of a refresh token family
+#[derive(Debug, FromRow, Serialize)]
+pub struct Session {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub current: bool,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SessionPath {
+    #[validate(length(min = 1, max = 255, message = "Invalid session id"))]
+    pub session_id: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UserSessionsPath {
+    #[validate(range(min = 1, message = "User ID must be positive"))]
+    pub user_id: i32,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UserSessionPath {
+    #[validate(range(min = 1, message = "User ID must be positive"))]
+    pub user_id: i32,
+
+    #[validate(length(min = 1, max = 255, message = "Invalid session id"))]
+    pub session_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevokedSessions {
+    pub revoked: u64,
+}
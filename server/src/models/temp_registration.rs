@@ -3,15 +3,24 @@ use sqlx::FromRow;
 use time::OffsetDateTime;
 use validator::Validate;
 
+use crate::models::users_models::validate_username_policy;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct TempRegistration {
     pub id: i32,
     pub email: String,
+    // None for registrations started before usernames were chosen
+    pub username: Option<String>,
     pub password: String,
-    pub secret_key: String,
+    // Argon2 hash of the 6 digit confirmation code
+    pub secret_key_hash: String,
+    pub language: Option<String>,
     pub created_at: OffsetDateTime,
     pub expires_at: OffsetDateTime,
     pub confirmed: bool,
+    // Wrong and right guesses against the current code
+    pub attempts: i32,
+    pub code_sent_at: OffsetDateTime,
 }
 
 #[derive(Debug, Deserialize, Clone, Validate)]
@@ -19,8 +28,19 @@ pub struct CreateTempRegistration {
     #[validate(email(message = "Invalid email format"))]
     pub email: String,
 
+    #[validate(custom(function = "validate_username_policy"))]
+    pub username: String,
+
     #[validate(length(min = 1, message = "Password cannot be empty"))]
     pub password: String,
+
+    // Language of the confirmation email, kept for the new user
+    #[validate(length(
+        min = 2,
+        max = 16,
+        message = "Language must be between 2 and 16 chars"
+    ))]
+    pub language: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone, Validate)]
@@ -35,3 +55,9 @@ pub struct ConfirmRegistration {
     ))]
     pub secret_key: String,
 }
+
+#[derive(Debug, Deserialize, Clone, Validate)]
+pub struct ResendConfirmation {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+}
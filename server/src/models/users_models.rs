@@ -1,49 +1,217 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use derive_more::Display;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
-use time::OffsetDateTime;
-use validator::Validate;
+use strum_macros::AsRefStr;
+use time::{
+    Date, OffsetDateTime,
+    format_description::well_known::{Iso8601, Rfc3339},
+};
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, FromRow, Serialize, Deserialize)]
+use crate::utils::username_policy::UsernamePolicy;
+
+/// Row of `users`, password hash included. Not `Serialize` on purpose,
+/// responses go through `UserView` or `AdminUserView`.
+#[derive(Debug, FromRow, Deserialize)]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub password: String,
     pub email: String,
+    // Preferred language of emails, e.g. "en" or "pt-br"
+    pub language: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+// What users see about themselves
+#[derive(Debug, Serialize)]
+pub struct UserView {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub language: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+impl From<User> for UserView {
+    fn from(user: User) -> Self {
+        UserView {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            language: user.language,
+            created_at: user.created_at,
+        }
+    }
+}
+
+// What the users.* endpoints return
+#[derive(Debug, Serialize)]
+pub struct AdminUserView {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub language: Option<String>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
 }
 
+impl From<User> for AdminUserView {
+    fn from(user: User) -> Self {
+        AdminUserView {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            language: user.language,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Validate, Display)]
-#[display("CreateUser: username={username}, password={password}")]
+#[display("CreateUser: username={username}, password=[redacted]")]
 pub struct CreateUser {
-    #[validate(length(
-        min = 3,
-        max = 25,
-        message = "Username must be between 3 and 25 chars"
-    ))]
+    #[validate(
+        length(
+            min = 3,
+            max = 25,
+            message = "Username must be between 3 and 25 chars"
+        ),
+        custom(function = "validate_username_policy")
+    )]
     pub username: String,
 
     #[validate(length(min = 8))]
     pub password: String,
     #[validate(email(message = "Email must be a valid email address"))]
     pub email: String,
+
+    #[validate(length(
+        min = 2,
+        max = 16,
+        message = "Language must be between 2 and 16 chars"
+    ))]
+    pub language: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate, Display)]
-#[display("UpdateUser: username={username}, password={password}")]
+#[display("UpdateUser: username={username}, password=[redacted]")]
 pub struct UpdateUser {
-    #[validate(length(
-        min = 3,
-        max = 25,
-        message = "Username must be between 3 and 25 chars"
-    ))]
+    #[validate(
+        length(
+            min = 3,
+            max = 25,
+            message = "Username must be between 3 and 25 chars"
+        ),
+        custom(function = "validate_username_policy")
+    )]
     pub username: String,
 
     #[validate(length(min = 8))]
     pub password: String,
     #[validate(email(message = "Email must be a valid email address"))]
     pub email: String,
+
+    #[validate(length(
+        min = 2,
+        max = 16,
+        message = "Language must be between 2 and 16 chars"
+    ))]
+    pub language: Option<String>,
+}
+
+/// Columns `PATCH /users/{id}` may change, named as in the request body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserField {
+    Username,
+    Email,
+    Language,
+}
+
+/// Partial update, fields left out keep their value. Passwords are changed
+/// through `ChangePasswordRequest` only.
+#[derive(Debug, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct PatchUser {
+    #[validate(
+        length(
+            min = 3,
+            max = 25,
+            message = "Username must be between 3 and 25 chars"
+        ),
+        custom(function = "validate_username_policy")
+    )]
+    pub username: Option<String>,
+
+    #[validate(email(message = "Email must be a valid email address"))]
+    pub email: Option<String>,
+
+    // Left out keeps the language, null clears it
+    #[allow(clippy::option_option)]
+    #[serde(default, deserialize_with = "present")]
+    #[validate(length(
+        min = 2,
+        max = 16,
+        message = "Language must be between 2 and 16 chars"
+    ))]
+    pub language: Option<Option<String>>,
+}
+
+impl PatchUser {
+    /// Fields that are sent and differ from the stored user, `None` sets
+    /// the column to NULL.
+    pub fn changes(self, user: &User) -> Vec<(UserField, Option<String>)> {
+        [
+            (
+                UserField::Username,
+                self.username.map(Some),
+                Some(user.username.as_str()),
+            ),
+            (UserField::Email, self.email.map(Some), Some(user.email.as_str())),
+            (UserField::Language, self.language, user.language.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(field, value, current)| {
+            value
+                .filter(|value| value.as_deref() != current)
+                .map(|value| (field, value))
+        })
+        .collect()
+    }
+}
+
+// Only called for a field that is in the body, so `null` becomes
+// `Some(None)` while a missing field stays `None` through `default`
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserUpdate {
+    pub user: AdminUserView,
+    // Request field names, empty if nothing differed
+    pub changed: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1, message = "Current password is required"))]
+    pub current_password: String,
+
+    #[validate(length(
+        min = 8,
+        max = 64,
+        message = "Password must be between 8 and 64 characters"
+    ))]
+    pub new_password: String,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -51,3 +219,520 @@ pub struct UserPath {
     #[validate(range(min = 1, message = "User ID must be positive"))]
     pub user_id: i32,
 }
+
+/// Columns the user list can be sorted by. Only these names ever reach the
+/// ORDER BY clause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserSortField {
+    Id,
+    Username,
+    Email,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl UserSortField {
+    pub const ALL: [UserSortField; 5] = [
+        UserSortField::Id,
+        UserSortField::Username,
+        UserSortField::Email,
+        UserSortField::CreatedAt,
+        UserSortField::UpdatedAt,
+    ];
+
+    pub fn from_name(name: &str) -> Option<UserSortField> {
+        Self::ALL.into_iter().find(|field| field.as_ref() == name)
+    }
+
+    // Value the keyset cursor continues after
+    pub fn cursor_value(self, user: &User) -> String {
+        match self {
+            UserSortField::Id => user.id.to_string(),
+            UserSortField::Username => user.username.clone(),
+            UserSortField::Email => user.email.clone(),
+            UserSortField::CreatedAt => format_timestamp(user.created_at),
+            UserSortField::UpdatedAt => format_timestamp(user.updated_at),
+        }
+    }
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp.format(&Rfc3339).unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// `sort` query value, e.g. `created_at:desc`. The direction defaults to
+/// ascending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserSort {
+    pub field: UserSortField,
+    pub descending: bool,
+}
+
+impl UserSort {
+    pub const DEFAULT: UserSort =
+        UserSort { field: UserSortField::CreatedAt, descending: true };
+
+    pub fn parse(value: &str) -> Option<UserSort> {
+        let (field, direction) =
+            value.split_once(':').unwrap_or((value, "asc"));
+
+        let descending = match direction {
+            "asc" => false,
+            "desc" => true,
+            _ => return None,
+        };
+        Some(UserSort { field: UserSortField::from_name(field)?, descending })
+    }
+}
+
+impl std::fmt::Display for UserSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = if self.descending { "desc" } else { "asc" };
+        write!(f, "{}:{direction}", self.field.as_ref())
+    }
+}
+
+/// Position after the last user of a page. Handed out base64 encoded, it
+/// is only valid with the sort it was made for.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserCursor {
+    pub sort: String,
+    pub value: String,
+    pub id: i32,
+}
+
+impl UserCursor {
+    pub fn after(sort: UserSort, user: &User) -> UserCursor {
+        UserCursor {
+            sort: sort.to_string(),
+            value: sort.field.cursor_value(user),
+            id: user.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    /// None if the cursor is malformed or was made for another sort.
+    pub fn decode(cursor: &str, sort: UserSort) -> Option<UserCursor> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        let cursor: UserCursor = serde_json::from_slice(&json).ok()?;
+
+        if cursor.sort != sort.to_string() {
+            return None;
+        }
+        // Timestamps are cast in SQL, a bad one must not get that far
+        match sort.field {
+            UserSortField::CreatedAt | UserSortField::UpdatedAt => {
+                OffsetDateTime::parse(&cursor.value, &Rfc3339).ok()?;
+            }
+            UserSortField::Id
+            | UserSortField::Username
+            | UserSortField::Email => {}
+        }
+        Some(cursor)
+    }
+}
+
+// Dates cover the whole day, `created_to=2024-01-31` includes the 31st
+fn parse_created_bound(value: &str, end: bool) -> Option<OffsetDateTime> {
+    if let Ok(timestamp) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Some(timestamp);
+    }
+
+    let date = Date::parse(value, &Iso8601::DATE).ok()?;
+    let date = if end { date.next_day()? } else { date };
+    Some(date.midnight().assume_utc())
+}
+
+fn validate_user_sort(sort: &str) -> Result<(), ValidationError> {
+    if UserSort::parse(sort).is_some() {
+        return Ok(());
+    }
+
+    let fields: Vec<&str> =
+        UserSortField::ALL.iter().map(AsRef::as_ref).collect();
+    Err(ValidationError::new("sort").with_message(
+        format!(
+            "Sort must be one of {} with an optional :asc or :desc",
+            fields.join(", ")
+        )
+        .into(),
+    ))
+}
+
+fn validate_created_bound(value: &str) -> Result<(), ValidationError> {
+    match parse_created_bound(value, false) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("date").with_message(
+            "Expected a date (YYYY-MM-DD) or an RFC 3339 timestamp".into(),
+        )),
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UsersQuery {
+    // Offset paging, not combined with a cursor
+    #[validate(range(min = 1, message = "Page must be positive"))]
+    pub page: Option<i64>,
+
+    #[validate(range(
+        min = 1,
+        max = 100,
+        message = "Per page must be between 1 and 100"
+    ))]
+    pub per_page: Option<i64>,
+
+    // next_cursor of the previous page
+    #[validate(length(min = 1, max = 1024, message = "Invalid cursor"))]
+    pub cursor: Option<String>,
+
+    #[validate(custom(function = "validate_user_sort"))]
+    pub sort: Option<String>,
+
+    // Substring of the username or email, case-insensitive
+    #[validate(length(
+        min = 1,
+        max = 255,
+        message = "Search must be between 1 and 255 chars"
+    ))]
+    pub q: Option<String>,
+
+    // Registration time range, from inclusive and to exclusive
+    #[validate(custom(function = "validate_created_bound"))]
+    pub created_from: Option<String>,
+
+    #[validate(custom(function = "validate_created_bound"))]
+    pub created_to: Option<String>,
+}
+
+impl UsersQuery {
+    pub fn sort(&self) -> UserSort {
+        self.sort
+            .as_deref()
+            .and_then(UserSort::parse)
+            .unwrap_or(UserSort::DEFAULT)
+    }
+
+    pub fn filter(&self) -> UserFilter {
+        UserFilter {
+            q: self.q.clone(),
+            created_from: self
+                .created_from
+                .as_deref()
+                .and_then(|value| parse_created_bound(value, false)),
+            created_to: self
+                .created_to
+                .as_deref()
+                .and_then(|value| parse_created_bound(value, true)),
+        }
+    }
+}
+
+// Conditions shared by a page and its total count
+#[derive(Debug, Default)]
+pub struct UserFilter {
+    pub q: Option<String>,
+    pub created_from: Option<OffsetDateTime>,
+    pub created_to: Option<OffsetDateTime>,
+}
+
+impl UserFilter {
+    /// ILIKE pattern for the search text, which is matched literally,
+    /// wildcards included.
+    pub fn search_pattern(&self) -> Option<String> {
+        self.q.as_ref().map(|q| {
+            format!(
+                "%{}%",
+                q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            )
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserPage {
+    pub items: Vec<AdminUserView>,
+    // Users matching the filters across all pages
+    pub total: i64,
+    // None when paging by cursor
+    pub page: Option<i64>,
+    pub per_page: i64,
+    pub sort: String,
+    // None on the last page
+    pub next_cursor: Option<String>,
+}
+
+// Usernames people pick themselves go through the configured policy
+pub fn validate_username_policy(username: &str) -> Result<(), ValidationError> {
+    UsernamePolicy::global().check(username).map_err(|rejection| {
+        ValidationError::new("username_policy")
+            .with_message(rejection.message().into())
+    })
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UsernameAvailabilityQuery {
+    #[validate(length(min = 1, max = 255, message = "Username is required"))]
+    pub username: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsernameAvailability {
+    pub username: String,
+    pub available: bool,
+    // Rejection code and text when not available
+    pub reason: Option<String>,
+    pub message: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_support;
+
+    const CREDENTIAL_KEYS: [&str; 4] = ["password", "hash", "secret", "token"];
+
+    // Handlers cannot send a `User` as JSON while it is not `Serialize`.
+    // With both impls below applying, the `_` is ambiguous and the build
+    // fails, so deriving it on `User` breaks the tests.
+    trait AmbiguousIfSerialize<A> {
+        fn check() {}
+    }
+    impl<T: ?Sized> AmbiguousIfSerialize<()> for T {}
+    impl<T: ?Sized + Serialize> AmbiguousIfSerialize<u8> for T {}
+    const _: fn() = <User as AmbiguousIfSerialize<_>>::check;
+
+    fn user() -> User {
+        User {
+            id: 7,
+            username: "alice".to_string(),
+            password: "$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA"
+                .to_string(),
+            email: "alice@example.com".to_string(),
+            language: Some("en".to_string()),
+            created_at: OffsetDateTime::UNIX_EPOCH,
+            updated_at: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+
+    fn assert_no_credentials(value: &serde_json::Value) {
+        match value {
+            serde_json::Value::Object(fields) => {
+                for (key, field) in fields {
+                    assert!(
+                        !CREDENTIAL_KEYS.iter().any(|word| key.contains(word)),
+                        "credential field `{key}` in {value}"
+                    );
+                    assert_no_credentials(field);
+                }
+            }
+            serde_json::Value::Array(items) => {
+                items.iter().for_each(assert_no_credentials);
+            }
+            serde_json::Value::String(text) => {
+                assert_ne!(text, &user().password, "password hash leaked");
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn user_responses_have_no_credentials() {
+        let page = UserPage {
+            items: vec![AdminUserView::from(user())],
+            total: 1,
+            page: Some(1),
+            per_page: 25,
+            sort: UserSort::DEFAULT.to_string(),
+            next_cursor: Some(
+                UserCursor::after(UserSort::DEFAULT, &user()).encode(),
+            ),
+        };
+
+        for response in [
+            serde_json::to_value(UserView::from(user())),
+            serde_json::to_value(AdminUserView::from(user())),
+            serde_json::to_value(page),
+            serde_json::to_value(UserUpdate {
+                user: AdminUserView::from(user()),
+                changed: vec!["email".to_string()],
+            }),
+        ] {
+            assert_no_credentials(&response.expect("serializable"));
+        }
+    }
+
+    #[test]
+    fn sort_accepts_only_known_fields_and_directions() {
+        assert_eq!(
+            UserSort::parse("created_at:desc"),
+            Some(UserSort {
+                field: UserSortField::CreatedAt,
+                descending: true
+            })
+        );
+        assert_eq!(
+            UserSort::parse("username"),
+            Some(UserSort {
+                field: UserSortField::Username,
+                descending: false
+            })
+        );
+
+        for value in [
+            "",
+            "password",
+            "Username",
+            "id:up",
+            "id:desc:asc",
+            "id; DROP TABLE users",
+        ] {
+            assert_eq!(UserSort::parse(value), None, "{value} accepted");
+        }
+    }
+
+    #[test]
+    fn cursor_is_rejected_for_another_sort() {
+        let sort = UserSort::parse("username:asc").unwrap();
+        let cursor = UserCursor::after(sort, &user()).encode();
+
+        assert!(UserCursor::decode(&cursor, sort).is_some());
+        for other in ["username:desc", "email:asc", "id:asc"] {
+            let other = UserSort::parse(other).unwrap();
+            assert!(UserCursor::decode(&cursor, other).is_none(), "{other}");
+        }
+        assert!(UserCursor::decode("not a cursor", sort).is_none());
+    }
+
+    #[test]
+    fn timestamp_cursor_round_trips() {
+        let created_at =
+            OffsetDateTime::parse("2024-02-29T13:45:12.123456789Z", &Rfc3339)
+                .unwrap();
+        let user = User { created_at, ..user() };
+        let sort = UserSort::parse("created_at:desc").unwrap();
+
+        let cursor =
+            UserCursor::decode(&UserCursor::after(sort, &user).encode(), sort)
+                .expect("valid cursor");
+        assert_eq!(cursor.id, user.id);
+        assert_eq!(
+            OffsetDateTime::parse(&cursor.value, &Rfc3339),
+            Ok(created_at)
+        );
+
+        // A value that is not a timestamp never reaches the SQL cast
+        let forged = UserCursor {
+            sort: sort.to_string(),
+            value: "yesterday".into(),
+            id: 1,
+        };
+        assert!(UserCursor::decode(&forged.encode(), sort).is_none());
+    }
+
+    #[test]
+    fn search_pattern_matches_wildcards_literally() {
+        let pattern = |q: &str| {
+            UserFilter { q: Some(q.to_string()), ..UserFilter::default() }
+                .search_pattern()
+        };
+
+        assert_eq!(pattern("alice"), Some("%alice%".to_string()));
+        assert_eq!(pattern("50%_off"), Some(r"%50\%\_off%".to_string()));
+        assert_eq!(pattern(r"a\b"), Some(r"%a\\b%".to_string()));
+        assert_eq!(UserFilter::default().search_pattern(), None);
+    }
+
+    #[test]
+    fn patched_username_goes_through_the_policy() {
+        test_support::init();
+        let patch = |username: &str| PatchUser {
+            username: Some(username.to_string()),
+            email: None,
+            language: None,
+        };
+
+        assert!(patch("alice_2").validate().is_ok());
+        for username in ["9lives", "ali ce", "al!ce"] {
+            let errors = patch(username).validate().unwrap_err();
+            assert!(
+                errors.field_errors().contains_key("username"),
+                "{username} accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn patched_language_is_kept_cleared_or_replaced() {
+        let changes = |body: &str| {
+            let patch: PatchUser = serde_json::from_str(body).unwrap();
+            patch.validate().map(|()| patch.changes(&user()))
+        };
+
+        assert_eq!(changes("{}").unwrap(), []);
+        assert_eq!(changes(r#"{"language": "en"}"#).unwrap(), []);
+        assert_eq!(
+            changes(r#"{"language": null}"#).unwrap(),
+            [(UserField::Language, None)]
+        );
+        assert_eq!(
+            changes(r#"{"language": "pt-br"}"#).unwrap(),
+            [(UserField::Language, Some("pt-br".to_string()))]
+        );
+        assert!(changes(r#"{"language": "x"}"#).is_err());
+    }
+
+    #[test]
+    fn created_and_replaced_usernames_go_through_the_policy() {
+        test_support::init();
+        let create = |username: &str| CreateUser {
+            username: username.to_string(),
+            password: "hunter2hunter2".to_string(),
+            email: "alice@example.com".to_string(),
+            language: None,
+        };
+        let update = |username: &str| UpdateUser {
+            username: username.to_string(),
+            password: "hunter2hunter2".to_string(),
+            email: "alice@example.com".to_string(),
+            language: None,
+        };
+
+        assert!(create("alice_2").validate().is_ok());
+        assert!(update("alice_2").validate().is_ok());
+        // With an `@` the login would be taken for an email
+        for username in ["al@ce", "9lives", "ali ce"] {
+            for result in
+                [create(username).validate(), update(username).validate()]
+            {
+                let errors = result.unwrap_err();
+                assert!(
+                    errors.field_errors().contains_key("username"),
+                    "{username} accepted"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn display_redacts_passwords() {
+        let create = CreateUser {
+            username: "alice".to_string(),
+            password: "hunter2hunter2".to_string(),
+            email: "alice@example.com".to_string(),
+            language: None,
+        };
+        let update = UpdateUser {
+            username: "alice".to_string(),
+            password: "hunter2hunter2".to_string(),
+            email: "alice@example.com".to_string(),
+            language: None,
+        };
+
+        assert!(!create.to_string().contains("hunter2"));
+        assert!(!update.to_string().contains("hunter2"));
+    }
+}
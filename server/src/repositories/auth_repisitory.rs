@@ -1,20 +1,33 @@
-use sqlx::PgPool;
-use time::OffsetDateTime;
+use sqlx::{PgExecutor, PgPool};
 
 use crate::{
-    errors::auth_errors::AuthError, models::auth_models::RefreshToken,
+    errors::auth_errors::AuthError,
+    models::{
+        auth_models::{Claims, RefreshToken},
+        sessions_models::Session,
+    },
 };
 
 pub struct AuthRepository;
 
 impl AuthRepository {
-    pub async fn validate_refresh_token(
+    pub async fn find_refresh_token(
         pool: &PgPool,
         token: &str,
-    ) -> Result<i32, AuthError> {
-        let result = sqlx::query!(
+    ) -> Result<RefreshToken, AuthError> {
+        let result = sqlx::query_as!(
+            RefreshToken,
             r#"
-            SELECT user_id, expires_at
+            SELECT
+                token,
+                user_id,
+                family_id,
+                expires_at,
+                rotated_at,
+                user_agent,
+                ip_address,
+                created_at,
+                last_used_at
             FROM refresh_tokens
             WHERE token = $1
             "#,
@@ -25,20 +38,8 @@ impl AuthRepository {
 
         match result {
             Ok(Some(record)) => {
-                let expires_at: OffsetDateTime = record.expires_at;
-                if expires_at < OffsetDateTime::now_utc() {
-                    log::warn!(
-                        "Refresh token expired for user {}",
-                        record.user_id
-                    );
-                    Err(AuthError::TokenExpired)
-                } else {
-                    log::debug!(
-                        "Refresh token validated for user {}",
-                        record.user_id
-                    );
-                    Ok(record.user_id)
-                }
+                log::debug!("Refresh token found for user {}", record.user_id);
+                Ok(record)
             }
             Ok(None) => {
                 log::warn!("Refresh token not found: {}", token);
@@ -54,32 +55,55 @@ impl AuthRepository {
         }
     }
 
-    pub async fn delete_refresh_token(
+    /// Marks the token as exchanged. Returns `false` if it was already
+    /// rotated, e.g. by a concurrent request with the same token.
+    pub async fn mark_refresh_token_rotated(
         pool: &PgPool,
         token: &str,
-    ) -> Result<(), AuthError> {
+    ) -> Result<bool, AuthError> {
         let result = sqlx::query!(
             r#"
-                DELETE FROM refresh_tokens
-                WHERE token = $1
-                "#,
+            UPDATE refresh_tokens
+            SET rotated_at = NOW()
+            WHERE token = $1 AND rotated_at IS NULL
+            "#,
             token
         )
         .execute(pool)
         .await;
 
         match result {
-            Ok(res) if res.rows_affected() > 0 => {
-                log::info!("Refresh token deleted: {}", token);
-                Ok(())
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => {
+                log::error!("Database error when rotating refresh token: {e}");
+                Err(AuthError::Authentication(e.to_string()))
             }
-            Ok(_) => {
-                log::warn!("Refresh token not found for deletion: {}", token);
-                Err(AuthError::RefreshTokenNotFound)
+        }
+    }
+
+    pub async fn revoke_token_family(
+        pool: &PgPool,
+        family_id: &str,
+    ) -> Result<u64, AuthError> {
+        let result = sqlx::query!(
+            "DELETE FROM refresh_tokens WHERE family_id = $1",
+            family_id
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) => {
+                log::info!(
+                    "Refresh token family {} revoked ({} tokens)",
+                    family_id,
+                    res.rows_affected()
+                );
+                Ok(res.rows_affected())
             }
             Err(e) => {
                 log::error!(
-                    "Database error when deleting refresh token: {}",
+                    "Database error when revoking refresh token family: {}",
                     e
                 );
                 Err(AuthError::Authentication(e.to_string()))
@@ -87,18 +111,39 @@ impl AuthRepository {
         }
     }
 
+    // The access token issued alongside is remembered so it can be
+    // denylisted when the session is revoked
     pub async fn save_refresh_token(
         pool: &PgPool,
         token: &RefreshToken,
+        access_claims: &Claims,
     ) -> Result<(), AuthError> {
         let result = sqlx::query!(
             r#"
-            INSERT INTO refresh_tokens (token, user_id, expires_at)
-            VALUES ($1, $2, $3)
+            INSERT INTO refresh_tokens (
+                token,
+                user_id,
+                family_id,
+                expires_at,
+                user_agent,
+                ip_address,
+                created_at,
+                last_used_at,
+                access_jti,
+                access_expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, to_timestamp($10))
             "#,
             token.token,
             token.user_id as i32,
-            token.expires_at
+            token.family_id,
+            token.expires_at,
+            token.user_agent,
+            token.ip_address,
+            token.created_at,
+            token.last_used_at,
+            access_claims.jti,
+            f64::from(access_claims.exp)
         )
         .execute(pool)
         .await;
@@ -119,5 +164,136 @@ impl AuthRepository {
         }
     }
 
-    // ===========================
+    // ======== Sessions ========
+
+    pub async fn list_sessions(
+        pool: &PgPool,
+        user_id: i32,
+        current_session_id: &str,
+    ) -> Result<Vec<Session>, AuthError> {
+        sqlx::query_as!(
+            Session,
+            r#"
+            SELECT
+                family_id AS id,
+                user_agent,
+                ip_address,
+                created_at,
+                last_used_at,
+                expires_at,
+                family_id = $2 AS "current!"
+            FROM refresh_tokens
+            WHERE user_id = $1 AND rotated_at IS NULL AND expires_at > NOW()
+            ORDER BY last_used_at DESC
+            "#,
+            user_id,
+            current_session_id
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Database error when listing sessions of user {user_id}: {e}"
+            );
+            AuthError::Database(e)
+        })
+    }
+
+    pub async fn revoke_session(
+        pool: &PgPool,
+        user_id: i32,
+        session_id: &str,
+    ) -> Result<(), AuthError> {
+        let result = sqlx::query!(
+            "DELETE FROM refresh_tokens WHERE user_id = $1 AND family_id = $2",
+            user_id,
+            session_id
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) if res.rows_affected() > 0 => {
+                log::info!("Session {session_id} of user {user_id} revoked");
+                Ok(())
+            }
+            Ok(_) => {
+                log::warn!("Session {session_id} of user {user_id} not found");
+                Err(AuthError::SessionNotFound)
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when revoking session {session_id}: {e}"
+                );
+                Err(AuthError::Database(e))
+            }
+        }
+    }
+
+    pub async fn revoke_all_sessions(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<u64, AuthError> {
+        let result = sqlx::query!(
+            "DELETE FROM refresh_tokens WHERE user_id = $1",
+            user_id
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) => {
+                log::info!("All sessions of user {user_id} revoked");
+                Ok(res.rows_affected())
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when revoking sessions of user {user_id}: {e}"
+                );
+                Err(AuthError::Database(e))
+            }
+        }
+    }
+
+    // ======== Maintenance ========
+
+    /// Deletes expired refresh tokens. Rotated ones are kept while their
+    /// session is alive, presenting one again still revokes the family.
+    pub async fn purge_expired_refresh_tokens<'e, E>(
+        executor: E,
+    ) -> Result<u64, AuthError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens t
+            WHERE t.expires_at <= NOW()
+                AND NOT EXISTS (
+                    SELECT 1 FROM refresh_tokens live
+                    WHERE live.family_id = t.family_id
+                        AND live.rotated_at IS NULL
+                        AND live.expires_at > NOW()
+                )
+            "#
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(res) => {
+                log::info!(
+                    "Purged {} expired refresh tokens",
+                    res.rows_affected()
+                );
+                Ok(res.rows_affected())
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when purging expired refresh tokens: {e}"
+                );
+                Err(AuthError::Database(e))
+            }
+        }
+    }
 }
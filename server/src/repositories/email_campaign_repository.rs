@@ -0,0 +1,570 @@
+use sqlx::{PgExecutor, PgPool};
+use time::OffsetDateTime;
+
+use crate::{
+    errors::email_campaign_errors::CampaignError,
+    models::email_campaign_models::{
+        Campaign, CampaignDelivery, CampaignRecipient, CampaignRecipientsQuery,
+        CampaignSegment, CampaignStatus, CampaignSummary,
+        CreateCampaignRequest, RecipientStatus, SegmentMember,
+    },
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+pub struct EmailCampaignRepository;
+
+impl EmailCampaignRepository {
+    pub async fn create(
+        pool: &PgPool,
+        created_by: i32,
+        request: &CreateCampaignRequest,
+    ) -> Result<Campaign, CampaignError> {
+        let result = sqlx::query_as!(
+            Campaign,
+            r#"
+            INSERT INTO email_campaigns (name, subject, text_body, html_body,
+                segment_registered_within_days, segment_role,
+                segment_language, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, name, subject, text_body, html_body,
+                segment_registered_within_days, segment_role,
+                segment_language, status, created_by, created_at,
+                started_at, finished_at
+            "#,
+            request.name,
+            request.subject,
+            request.text_body,
+            request.html_body,
+            request.segment.registered_within_days,
+            request.segment.role,
+            request.segment.language,
+            created_by
+        )
+        .fetch_one(pool)
+        .await;
+
+        match result {
+            Ok(campaign) => {
+                log::info!(
+                    "Campaign #{} created by user {created_by}",
+                    campaign.id
+                );
+                Ok(campaign)
+            }
+            Err(e) => {
+                log::error!("Database error when creating campaign: {e}");
+                Err(CampaignError::Database(e))
+            }
+        }
+    }
+
+    pub async fn find_all(
+        pool: &PgPool,
+    ) -> Result<Vec<CampaignSummary>, CampaignError> {
+        let result = sqlx::query_as!(
+            CampaignSummary,
+            r#"
+            SELECT c.id, c.name, c.status, c.created_by, c.created_at,
+                c.started_at, c.finished_at,
+                COUNT(r.user_id) FILTER (WHERE r.status = 'pending')
+                    AS "pending!",
+                COUNT(r.user_id) FILTER (WHERE r.status = 'sent') AS "sent!",
+                COUNT(r.user_id) FILTER (WHERE r.status = 'failed')
+                    AS "failed!",
+                COUNT(r.user_id) FILTER (WHERE r.status = 'skipped')
+                    AS "skipped!"
+            FROM email_campaigns c
+            LEFT JOIN email_campaign_recipients r ON r.campaign_id = c.id
+            GROUP BY c.id
+            ORDER BY c.created_at DESC, c.id DESC
+            "#
+        )
+        .fetch_all(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when reading campaigns: {e}");
+            CampaignError::Database(e)
+        })
+    }
+
+    pub async fn find_by_id(
+        pool: &PgPool,
+        campaign_id: i32,
+    ) -> Result<Campaign, CampaignError> {
+        let result = sqlx::query_as!(
+            Campaign,
+            r#"
+            SELECT id, name, subject, text_body, html_body,
+                segment_registered_within_days, segment_role,
+                segment_language, status, created_by, created_at,
+                started_at, finished_at
+            FROM email_campaigns
+            WHERE id = $1
+            "#,
+            campaign_id
+        )
+        .fetch_optional(pool)
+        .await;
+
+        match result {
+            Ok(Some(campaign)) => Ok(campaign),
+            Ok(None) => Err(CampaignError::NotFound(campaign_id)),
+            Err(e) => {
+                log::error!(
+                    "Database error when reading campaign #{campaign_id}: {e}"
+                );
+                Err(CampaignError::Database(e))
+            }
+        }
+    }
+
+    pub async fn is_sending(
+        pool: &PgPool,
+        campaign_id: i32,
+    ) -> Result<bool, CampaignError> {
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM email_campaigns WHERE id = $1 AND status = $2
+            ) AS "sending!"
+            "#,
+            campaign_id,
+            CampaignStatus::Sending.as_ref()
+        )
+        .fetch_one(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!(
+                "Database error when reading campaign #{campaign_id}: {e}"
+            );
+            CampaignError::Database(e)
+        })
+    }
+
+    pub async fn find_by_status(
+        pool: &PgPool,
+        status: CampaignStatus,
+    ) -> Result<Vec<Campaign>, CampaignError> {
+        let result = sqlx::query_as!(
+            Campaign,
+            r#"
+            SELECT id, name, subject, text_body, html_body,
+                segment_registered_within_days, segment_role,
+                segment_language, status, created_by, created_at,
+                started_at, finished_at
+            FROM email_campaigns
+            WHERE status = $1
+            ORDER BY started_at, id
+            "#,
+            status.as_ref()
+        )
+        .fetch_all(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when reading campaigns: {e}");
+            CampaignError::Database(e)
+        })
+    }
+
+    /// Users the segment currently matches, opted-out users excluded.
+    pub async fn count_segment(
+        pool: &PgPool,
+        segment: &CampaignSegment,
+    ) -> Result<i64, CampaignError> {
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM users u
+            WHERE NOT u.email_opt_out
+                AND ($1::INT IS NULL
+                    OR u.created_at >= NOW() - make_interval(days => $1))
+                AND ($2::TEXT IS NULL OR EXISTS (
+                    SELECT 1 FROM user_roles ur
+                    JOIN roles r ON r.id = ur.role_id
+                    WHERE ur.user_id = u.id AND r.name = $2))
+                AND ($3::TEXT IS NULL OR LOWER(u.language) = LOWER($3)
+                    OR LOWER(u.language) LIKE LOWER($3) || '-%')
+            "#,
+            segment.registered_within_days,
+            segment.role,
+            segment.language
+        )
+        .fetch_one(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when counting campaign segment: {e}");
+            CampaignError::Database(e)
+        })
+    }
+
+    pub async fn first_segment_member(
+        pool: &PgPool,
+        segment: &CampaignSegment,
+    ) -> Result<Option<SegmentMember>, CampaignError> {
+        let result = sqlx::query_as!(
+            SegmentMember,
+            r#"
+            SELECT u.email, u.username, u.language
+            FROM users u
+            WHERE NOT u.email_opt_out
+                AND ($1::INT IS NULL
+                    OR u.created_at >= NOW() - make_interval(days => $1))
+                AND ($2::TEXT IS NULL OR EXISTS (
+                    SELECT 1 FROM user_roles ur
+                    JOIN roles r ON r.id = ur.role_id
+                    WHERE ur.user_id = u.id AND r.name = $2))
+                AND ($3::TEXT IS NULL OR LOWER(u.language) = LOWER($3)
+                    OR LOWER(u.language) LIKE LOWER($3) || '-%')
+            ORDER BY u.id
+            LIMIT 1
+            "#,
+            segment.registered_within_days,
+            segment.role,
+            segment.language
+        )
+        .fetch_optional(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when reading campaign segment: {e}");
+            CampaignError::Database(e)
+        })
+    }
+
+    /// Moves a draft to sending. Returns `false` if it is not a draft.
+    pub async fn mark_sending<'e, E>(
+        executor: E,
+        campaign_id: i32,
+    ) -> Result<bool, CampaignError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_campaigns
+            SET status = $2, started_at = NOW()
+            WHERE id = $1 AND status = $3
+            "#,
+            campaign_id,
+            CampaignStatus::Sending.as_ref(),
+            CampaignStatus::Draft.as_ref()
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => {
+                log::error!(
+                    "Database error when starting campaign #{campaign_id}: {e}"
+                );
+                Err(CampaignError::Database(e))
+            }
+        }
+    }
+
+    /// Snapshots the segment into the recipient list, so users matching it
+    /// later are not mailed. Returns the number of recipients.
+    pub async fn add_recipients<'e, E>(
+        executor: E,
+        campaign_id: i32,
+        segment: &CampaignSegment,
+    ) -> Result<u64, CampaignError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO email_campaign_recipients (campaign_id, user_id, email)
+            SELECT $1, u.id, u.email
+            FROM users u
+            WHERE NOT u.email_opt_out
+                AND ($2::INT IS NULL
+                    OR u.created_at >= NOW() - make_interval(days => $2))
+                AND ($3::TEXT IS NULL OR EXISTS (
+                    SELECT 1 FROM user_roles ur
+                    JOIN roles r ON r.id = ur.role_id
+                    WHERE ur.user_id = u.id AND r.name = $3))
+                AND ($4::TEXT IS NULL OR LOWER(u.language) = LOWER($4)
+                    OR LOWER(u.language) LIKE LOWER($4) || '-%')
+            "#,
+            campaign_id,
+            segment.registered_within_days,
+            segment.role,
+            segment.language
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(res) => {
+                log::info!(
+                    "Campaign #{campaign_id} queued for {} recipients",
+                    res.rows_affected()
+                );
+                Ok(res.rows_affected())
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when adding recipients to campaign \
+                     #{campaign_id}: {e}"
+                );
+                Err(CampaignError::Database(e))
+            }
+        }
+    }
+
+    /// Cancels a draft or sending campaign. Returns `false` if it is
+    /// neither.
+    pub async fn cancel<'e, E>(
+        executor: E,
+        campaign_id: i32,
+    ) -> Result<bool, CampaignError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_campaigns
+            SET status = $2, finished_at = NOW()
+            WHERE id = $1 AND status IN ($3, $4)
+            "#,
+            campaign_id,
+            CampaignStatus::Cancelled.as_ref(),
+            CampaignStatus::Draft.as_ref(),
+            CampaignStatus::Sending.as_ref()
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => {
+                log::error!(
+                    "Database error when cancelling campaign #{campaign_id}: \
+                     {e}"
+                );
+                Err(CampaignError::Database(e))
+            }
+        }
+    }
+
+    /// Skips the recipients no worker holds a lease on. Leased ones are
+    /// left to the worker, which sees the campaign is no longer sending.
+    pub async fn skip_pending<'e, E>(
+        executor: E,
+        campaign_id: i32,
+    ) -> Result<u64, CampaignError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_campaign_recipients
+            SET status = $2
+            WHERE campaign_id = $1 AND status = $3
+                AND (claimed_until IS NULL OR claimed_until < NOW())
+            "#,
+            campaign_id,
+            RecipientStatus::Skipped.as_ref(),
+            RecipientStatus::Pending.as_ref()
+        )
+        .execute(executor)
+        .await;
+
+        result.map(|res| res.rows_affected()).map_err(|e| {
+            log::error!(
+                "Database error when skipping recipients of campaign \
+                 #{campaign_id}: {e}"
+            );
+            CampaignError::Database(e)
+        })
+    }
+
+    /// Takes up to `limit` pending recipients, hidden from other workers
+    /// until `lease_until` so a worker dying mid-batch only delays them.
+    pub async fn claim_batch(
+        pool: &PgPool,
+        campaign_id: i32,
+        limit: i64,
+        lease_until: OffsetDateTime,
+    ) -> Result<Vec<CampaignDelivery>, CampaignError> {
+        let result = sqlx::query_as!(
+            CampaignDelivery,
+            r#"
+            UPDATE email_campaign_recipients r
+            SET claimed_until = $3
+            FROM users u
+            WHERE u.id = r.user_id AND r.campaign_id = $1
+                AND r.user_id IN (
+                    SELECT user_id FROM email_campaign_recipients
+                    WHERE campaign_id = $1 AND status = 'pending'
+                        AND (claimed_until IS NULL OR claimed_until < NOW())
+                    ORDER BY user_id
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+            RETURNING r.user_id, r.email, u.username, u.language,
+                u.email_opt_out
+            "#,
+            campaign_id,
+            limit,
+            lease_until
+        )
+        .fetch_all(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!(
+                "Database error when claiming recipients of campaign \
+                 #{campaign_id}: {e}"
+            );
+            CampaignError::Database(e)
+        })
+    }
+
+    pub async fn mark_recipient(
+        pool: &PgPool,
+        campaign_id: i32,
+        user_id: i32,
+        status: RecipientStatus,
+        error: Option<&str>,
+        unsubscribe_token_hash: Option<&str>,
+    ) -> Result<(), CampaignError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_campaign_recipients
+            SET status = $3::TEXT, error = $4, unsubscribe_token_hash = $5,
+                claimed_until = NULL,
+                sent_at = CASE WHEN $3 = 'sent' THEN NOW() END
+            WHERE campaign_id = $1 AND user_id = $2 AND status = 'pending'
+            "#,
+            campaign_id,
+            user_id,
+            status.as_ref(),
+            error,
+            unsubscribe_token_hash
+        )
+        .execute(pool)
+        .await;
+
+        result.map(|_| ()).map_err(|e| {
+            log::error!(
+                "Database error when updating recipient {user_id} of \
+                 campaign #{campaign_id}: {e}"
+            );
+            CampaignError::Database(e)
+        })
+    }
+
+    /// Finishes a sending campaign once no recipient is pending. Returns
+    /// `true` if it did.
+    pub async fn finish_if_done(
+        pool: &PgPool,
+        campaign_id: i32,
+    ) -> Result<bool, CampaignError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_campaigns c
+            SET status = $2, finished_at = NOW()
+            WHERE c.id = $1 AND c.status = $3 AND NOT EXISTS (
+                SELECT 1 FROM email_campaign_recipients r
+                WHERE r.campaign_id = c.id AND r.status = $4
+            )
+            "#,
+            campaign_id,
+            CampaignStatus::Finished.as_ref(),
+            CampaignStatus::Sending.as_ref(),
+            RecipientStatus::Pending.as_ref()
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) => {
+                let finished = res.rows_affected() > 0;
+                if finished {
+                    log::info!("Campaign #{campaign_id} finished");
+                }
+                Ok(finished)
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when finishing campaign #{campaign_id}: \
+                     {e}"
+                );
+                Err(CampaignError::Database(e))
+            }
+        }
+    }
+
+    pub async fn find_recipients(
+        pool: &PgPool,
+        campaign_id: i32,
+        query: &CampaignRecipientsQuery,
+    ) -> Result<Vec<CampaignRecipient>, CampaignError> {
+        let result = sqlx::query_as!(
+            CampaignRecipient,
+            r#"
+            SELECT user_id, email, status, error, sent_at
+            FROM email_campaign_recipients
+            WHERE campaign_id = $1 AND ($2::TEXT IS NULL OR status = $2)
+            ORDER BY user_id
+            LIMIT $3 OFFSET $4
+            "#,
+            campaign_id,
+            query.status,
+            query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+            query.offset.unwrap_or(0)
+        )
+        .fetch_all(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!(
+                "Database error when reading recipients of campaign \
+                 #{campaign_id}: {e}"
+            );
+            CampaignError::Database(e)
+        })
+    }
+
+    /// Opts out the user a campaign email with this unsubscribe token was
+    /// sent to. Returns their ID, None for an unknown token.
+    pub async fn unsubscribe(
+        pool: &PgPool,
+        token_hash: &str,
+    ) -> Result<Option<i32>, CampaignError> {
+        let result = sqlx::query_scalar!(
+            r#"
+            UPDATE users
+            SET email_opt_out = TRUE
+            WHERE id = (
+                SELECT user_id FROM email_campaign_recipients
+                WHERE unsubscribe_token_hash = $1
+            )
+            RETURNING id
+            "#,
+            token_hash
+        )
+        .fetch_optional(pool)
+        .await;
+
+        match result {
+            Ok(Some(user_id)) => {
+                log::info!("User {user_id} unsubscribed from campaign emails");
+                Ok(Some(user_id))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                log::error!("Database error when unsubscribing: {e}");
+                Err(CampaignError::Database(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use sqlx::{PgExecutor, PgPool};
+use time::OffsetDateTime;
+
+use crate::{
+    errors::email_errors::EmailError,
+    models::email_models::{EmailLogEntry, EmailLogQuery, EmailStatus},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+// First key of the quota advisory locks, the second is the sender's ID
+const EMAIL_QUOTA_LOCK_CLASS: i32 = 0x454D_5154;
+
+pub struct EmailLogRepository;
+
+impl EmailLogRepository {
+    /// Stores one send attempt. Failures are only logged so a broken log
+    /// never hides whether the email itself went out.
+    pub async fn record(
+        pool: &PgPool,
+        sender_id: Option<i32>,
+        recipient: &str,
+        subject: &str,
+        status: EmailStatus,
+        error: Option<&str>,
+    ) {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO email_log (sender_id, recipient, subject, status, error)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            sender_id,
+            recipient,
+            subject,
+            status.as_ref(),
+            error
+        )
+        .execute(pool)
+        .await;
+
+        if let Err(e) = result {
+            log::error!("Failed to record email to {recipient} in log: {e}");
+        }
+    }
+
+    /// Reserves quota for the recipients by logging them as pending, or
+    /// returns None if that would exceed `quota` since `since`. The count
+    /// and the insert happen under a per-sender lock, so parallel requests
+    /// cannot all pass. Returns the IDs of the pending rows.
+    pub async fn reserve(
+        pool: &PgPool,
+        sender_id: i32,
+        recipients: &[String],
+        subject: &str,
+        quota: i64,
+        since: OffsetDateTime,
+    ) -> Result<Option<Vec<i32>>, EmailError> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock($1, $2)",
+            EMAIL_QUOTA_LOCK_CLASS,
+            sender_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let used = Self::count_sent_since(&mut *tx, sender_id, since).await?;
+        if used + i64::try_from(recipients.len()).unwrap_or(i64::MAX) > quota {
+            return Ok(None);
+        }
+
+        let result = sqlx::query_scalar!(
+            r#"
+            INSERT INTO email_log (sender_id, recipient, subject, status)
+            SELECT $1, recipient, $3, $4
+            FROM UNNEST($2::TEXT[]) AS recipient
+            RETURNING id
+            "#,
+            sender_id,
+            recipients,
+            subject,
+            EmailStatus::Pending.as_ref()
+        )
+        .fetch_all(&mut *tx)
+        .await;
+
+        match result {
+            Ok(ids) => {
+                tx.commit().await?;
+                Ok(Some(ids))
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when reserving quota of user {sender_id}: {e}"
+                );
+                Err(EmailError::Database(e))
+            }
+        }
+    }
+
+    /// Settles reserved rows with the outcome of the send. Failures are only
+    /// logged, the rows then keep counting as pending.
+    pub async fn finish(
+        pool: &PgPool,
+        ids: &[i32],
+        status: EmailStatus,
+        error: Option<&str>,
+    ) {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_log
+            SET status = $2, error = $3
+            WHERE id = ANY($1)
+            "#,
+            ids,
+            status.as_ref(),
+            error
+        )
+        .execute(pool)
+        .await;
+
+        if let Err(e) = result {
+            log::error!("Failed to record email outcome in log: {e}");
+        }
+    }
+
+    // Rejected attempts never reached SMTP and do not use up the quota,
+    // pending ones may still do
+    async fn count_sent_since<'e, E>(
+        executor: E,
+        sender_id: i32,
+        since: OffsetDateTime,
+    ) -> Result<i64, EmailError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM email_log
+            WHERE sender_id = $1
+                AND created_at > $2
+                AND status IN ('pending', 'sent', 'failed')
+            "#,
+            sender_id,
+            since
+        )
+        .fetch_one(executor)
+        .await;
+
+        result.map_err(|e| {
+            log::error!(
+                "Database error when counting emails of user {sender_id}: {e}"
+            );
+            EmailError::Database(e)
+        })
+    }
+
+    pub async fn find(
+        pool: &PgPool,
+        query: &EmailLogQuery,
+    ) -> Result<Vec<EmailLogEntry>, EmailError> {
+        let result = sqlx::query_as!(
+            EmailLogEntry,
+            r#"
+            SELECT id, sender_id, recipient, subject, status, error, created_at
+            FROM email_log
+            WHERE ($1::INTEGER IS NULL OR sender_id = $1)
+                AND ($2::TEXT IS NULL OR recipient = LOWER($2))
+                AND ($3::TEXT IS NULL OR status = $3)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            query.sender_id,
+            query.recipient,
+            query.status,
+            query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+            query.offset.unwrap_or(0)
+        )
+        .fetch_all(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when reading email log: {e}");
+            EmailError::Database(e)
+        })
+    }
+}
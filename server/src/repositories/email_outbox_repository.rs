@@ -0,0 +1,214 @@
+use sqlx::{Error as SqlxError, PgExecutor, PgPool};
+use time::OffsetDateTime;
+
+use crate::{
+    errors::email_errors::EmailError,
+    models::email_outbox_models::{
+        NewOutboxEmail, OutboxEmail, OutboxEntry, OutboxQuery, OutboxStatus,
+    },
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+pub struct EmailOutboxRepository;
+
+impl EmailOutboxRepository {
+    /// Queues an email. Pass the transaction of the change that triggers it
+    /// so the email is only sent if that change is committed.
+    pub async fn enqueue<'e, E>(
+        executor: E,
+        email: &NewOutboxEmail,
+    ) -> Result<i32, SqlxError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query_scalar!(
+            r#"
+            INSERT INTO email_outbox (recipient, subject, text_body, html_body)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+            email.recipient,
+            email.subject,
+            email.text_body,
+            email.html_body
+        )
+        .fetch_one(executor)
+        .await;
+
+        match result {
+            Ok(id) => {
+                log::info!("Email #{id} to {} queued", email.recipient);
+                Ok(id)
+            }
+            Err(e) => {
+                log::error!("Database error when queueing email: {e}");
+                Err(e)
+            }
+        }
+    }
+
+    /// Takes up to `limit` due emails and counts the attempt. They are not
+    /// due again before `lease_until`, so a worker dying mid-send only delays
+    /// them and concurrent workers never pick the same row.
+    pub async fn claim_due(
+        pool: &PgPool,
+        limit: i64,
+        lease_until: OffsetDateTime,
+    ) -> Result<Vec<OutboxEmail>, EmailError> {
+        let result = sqlx::query_as!(
+            OutboxEmail,
+            r#"
+            UPDATE email_outbox
+            SET attempts = attempts + 1, next_attempt_at = $2
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, text_body, html_body, attempts
+            "#,
+            limit,
+            lease_until
+        )
+        .fetch_all(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when claiming queued emails: {e}");
+            EmailError::Database(e)
+        })
+    }
+
+    // Bodies are dropped once delivered, they may contain one-time codes
+    pub async fn mark_sent(pool: &PgPool, id: i32) -> Result<(), EmailError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = $2, sent_at = NOW(), last_error = NULL,
+                text_body = '', html_body = NULL
+            WHERE id = $1
+            "#,
+            id,
+            OutboxStatus::Sent.as_ref()
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(_) => {
+                log::info!("Queued email #{id} sent");
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when marking email #{id} sent: {e}"
+                );
+                Err(EmailError::Database(e))
+            }
+        }
+    }
+
+    /// Records a failed attempt. Without `retry_at` the email is moved to
+    /// the dead letters.
+    pub async fn mark_failed(
+        pool: &PgPool,
+        id: i32,
+        error: &str,
+        retry_at: Option<OffsetDateTime>,
+    ) -> Result<(), EmailError> {
+        let status = if retry_at.is_some() {
+            OutboxStatus::Pending
+        } else {
+            OutboxStatus::Dead
+        };
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = $2, last_error = $3,
+                next_attempt_at = COALESCE($4, next_attempt_at)
+            WHERE id = $1
+            "#,
+            id,
+            status.as_ref(),
+            error,
+            retry_at
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(_) => {
+                log::warn!("Queued email #{id} failed ({error})");
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when marking email #{id} failed: {e}"
+                );
+                Err(EmailError::Database(e))
+            }
+        }
+    }
+
+    pub async fn find(
+        pool: &PgPool,
+        query: &OutboxQuery,
+    ) -> Result<Vec<OutboxEntry>, EmailError> {
+        let result = sqlx::query_as!(
+            OutboxEntry,
+            r#"
+            SELECT id, recipient, subject, status, attempts, last_error,
+                next_attempt_at, created_at, sent_at
+            FROM email_outbox
+            WHERE ($1::TEXT IS NULL OR status = $1)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            query.status,
+            query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+            query.offset.unwrap_or(0)
+        )
+        .fetch_all(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when reading email outbox: {e}");
+            EmailError::Database(e)
+        })
+    }
+
+    /// Puts a dead email back in the queue with a fresh set of attempts.
+    /// Returns `false` if there is no dead email with that id.
+    pub async fn requeue(pool: &PgPool, id: i32) -> Result<bool, EmailError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = $2, attempts = 0, next_attempt_at = NOW()
+            WHERE id = $1 AND status = $3
+            "#,
+            id,
+            OutboxStatus::Pending.as_ref(),
+            OutboxStatus::Dead.as_ref()
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) => {
+                let requeued = res.rows_affected() > 0;
+                if requeued {
+                    log::info!("Dead email #{id} requeued");
+                }
+                Ok(requeued)
+            }
+            Err(e) => {
+                log::error!("Database error when requeueing email #{id}: {e}");
+                Err(EmailError::Database(e))
+            }
+        }
+    }
+}
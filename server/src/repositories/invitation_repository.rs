@@ -0,0 +1,294 @@
+use sqlx::{PgExecutor, PgPool};
+use time::OffsetDateTime;
+
+use crate::{
+    errors::invitation_errors::InvitationError,
+    models::invitation_models::{
+        CreateInvitationRequest, Invitation, InvitationsQuery,
+        PendingInvitation,
+    },
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+pub struct InvitationRepository;
+
+impl InvitationRepository {
+    /// Stores the invitation for the role named in the request. Returns its
+    /// ID, None if there is no such role.
+    pub async fn create<'e, E>(
+        executor: E,
+        invited_by: i32,
+        request: &CreateInvitationRequest,
+        token_hash: &str,
+        expires_at: OffsetDateTime,
+    ) -> Result<Option<i32>, InvitationError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query_scalar!(
+            r#"
+            INSERT INTO invitations (
+                email, token_hash, role_id, language, invited_by, expires_at
+            )
+            SELECT $1, $2, r.id, $4, $5, $6
+            FROM roles r
+            WHERE r.name = $3
+            RETURNING id
+            "#,
+            request.email,
+            token_hash,
+            request.role,
+            request.language,
+            invited_by,
+            expires_at
+        )
+        .fetch_optional(executor)
+        .await;
+
+        match result {
+            Ok(id) => {
+                if let Some(id) = id {
+                    log::info!(
+                        "Invitation #{id} for {} created by user {invited_by}",
+                        request.email
+                    );
+                }
+                Ok(id)
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when creating invitation for {}: {e}",
+                    request.email
+                );
+                Err(InvitationError::Database(e))
+            }
+        }
+    }
+
+    /// Revokes the pending invitations of the email, a new invitation
+    /// replaces them.
+    pub async fn revoke_pending_for_email<'e, E>(
+        executor: E,
+        email: &str,
+    ) -> Result<u64, InvitationError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE invitations
+            SET revoked_at = NOW()
+            WHERE LOWER(email) = LOWER($1)
+                AND accepted_at IS NULL AND revoked_at IS NULL
+            "#,
+            email
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(res) => Ok(res.rows_affected()),
+            Err(e) => {
+                log::error!(
+                    "Database error when revoking invitations of {email}: {e}"
+                );
+                Err(InvitationError::Database(e))
+            }
+        }
+    }
+
+    pub async fn find_all(
+        pool: &PgPool,
+        query: &InvitationsQuery,
+    ) -> Result<Vec<Invitation>, InvitationError> {
+        let result = sqlx::query_as!(
+            Invitation,
+            r#"
+            SELECT
+                i.id,
+                i.email,
+                r.name AS role,
+                i.language,
+                CASE
+                    WHEN i.accepted_at IS NOT NULL THEN 'accepted'
+                    WHEN i.revoked_at IS NOT NULL THEN 'revoked'
+                    WHEN i.expires_at <= NOW() THEN 'expired'
+                    ELSE 'pending'
+                END AS "status!",
+                i.invited_by,
+                i.created_at,
+                i.expires_at,
+                i.accepted_at,
+                i.accepted_by,
+                i.revoked_at
+            FROM invitations i
+            JOIN roles r ON r.id = i.role_id
+            WHERE $1::TEXT IS NULL OR $1 = CASE
+                WHEN i.accepted_at IS NOT NULL THEN 'accepted'
+                WHEN i.revoked_at IS NOT NULL THEN 'revoked'
+                WHEN i.expires_at <= NOW() THEN 'expired'
+                ELSE 'pending'
+            END
+            ORDER BY i.created_at DESC, i.id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            query.status,
+            query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+            query.offset.unwrap_or(0)
+        )
+        .fetch_all(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when listing invitations: {e}");
+            InvitationError::Database(e)
+        })
+    }
+
+    pub async fn find_by_id<'e, E>(
+        executor: E,
+        invitation_id: i32,
+    ) -> Result<Invitation, InvitationError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query_as!(
+            Invitation,
+            r#"
+            SELECT
+                i.id,
+                i.email,
+                r.name AS role,
+                i.language,
+                CASE
+                    WHEN i.accepted_at IS NOT NULL THEN 'accepted'
+                    WHEN i.revoked_at IS NOT NULL THEN 'revoked'
+                    WHEN i.expires_at <= NOW() THEN 'expired'
+                    ELSE 'pending'
+                END AS "status!",
+                i.invited_by,
+                i.created_at,
+                i.expires_at,
+                i.accepted_at,
+                i.accepted_by,
+                i.revoked_at
+            FROM invitations i
+            JOIN roles r ON r.id = i.role_id
+            WHERE i.id = $1
+            "#,
+            invitation_id
+        )
+        .fetch_optional(executor)
+        .await;
+
+        match result {
+            Ok(Some(invitation)) => Ok(invitation),
+            Ok(None) => Err(InvitationError::NotFound(invitation_id)),
+            Err(e) => {
+                log::error!(
+                    "Database error when finding invitation #{invitation_id}: {e}"
+                );
+                Err(InvitationError::Database(e))
+            }
+        }
+    }
+
+    /// Revokes a pending invitation. Returns `false` if it was accepted or
+    /// revoked already.
+    pub async fn revoke(
+        pool: &PgPool,
+        invitation_id: i32,
+    ) -> Result<bool, InvitationError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE invitations
+            SET revoked_at = NOW()
+            WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL
+            "#,
+            invitation_id
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) if res.rows_affected() > 0 => {
+                log::info!("Invitation #{invitation_id} revoked");
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(e) => {
+                log::error!(
+                    "Database error when revoking invitation #{invitation_id}: {e}"
+                );
+                Err(InvitationError::Database(e))
+            }
+        }
+    }
+
+    /// Pending, unexpired invitation with this token. The row stays locked
+    /// until the end of the transaction, so it is accepted once.
+    pub async fn find_pending_by_token<'e, E>(
+        executor: E,
+        token_hash: &str,
+    ) -> Result<Option<PendingInvitation>, InvitationError>
+    where
+        E: PgExecutor<'e>,
+    {
+        sqlx::query_as!(
+            PendingInvitation,
+            r#"
+            SELECT i.id, i.email, i.role_id, r.name AS role, i.language,
+                i.expires_at
+            FROM invitations i
+            JOIN roles r ON r.id = i.role_id
+            WHERE i.token_hash = $1 AND i.accepted_at IS NULL
+                AND i.revoked_at IS NULL AND i.expires_at > NOW()
+            FOR UPDATE OF i
+            "#,
+            token_hash
+        )
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| {
+            log::error!("Database error when finding invitation by token: {e}");
+            InvitationError::Database(e)
+        })
+    }
+
+    pub async fn mark_accepted<'e, E>(
+        executor: E,
+        invitation_id: i32,
+        user_id: i32,
+    ) -> Result<(), InvitationError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE invitations
+            SET accepted_at = NOW(), accepted_by = $2
+            WHERE id = $1
+            "#,
+            invitation_id,
+            user_id
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(_) => {
+                log::info!(
+                    "Invitation #{invitation_id} accepted by user {user_id}"
+                );
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when accepting invitation #{invitation_id}: {e}"
+                );
+                Err(InvitationError::Database(e))
+            }
+        }
+    }
+}
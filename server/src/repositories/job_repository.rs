@@ -0,0 +1,225 @@
+use sqlx::{PgExecutor, PgPool};
+use time::OffsetDateTime;
+
+use crate::{
+    errors::job_errors::JobError,
+    models::job_models::{JobRun, JobStatus},
+};
+
+// First key of the job advisory locks, the second is hashtext(name).
+// Keeps them apart from single-key locks taken elsewhere.
+const JOB_LOCK_CLASS: i32 = 0x4A4F_4253;
+
+pub struct JobRepository;
+
+impl JobRepository {
+    /// Adds the job or updates its schedule. The next run is only moved
+    /// when the schedule changed, so restarts don't postpone jobs.
+    pub async fn register(
+        pool: &PgPool,
+        name: &str,
+        schedule: &str,
+        next_run_at: OffsetDateTime,
+    ) -> Result<(), JobError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO scheduled_jobs (name, schedule, next_run_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (name) DO UPDATE SET
+                schedule = EXCLUDED.schedule,
+                next_run_at = CASE
+                    WHEN scheduled_jobs.schedule = EXCLUDED.schedule
+                        THEN scheduled_jobs.next_run_at
+                    ELSE EXCLUDED.next_run_at
+                END
+            "#,
+            name,
+            schedule,
+            next_run_at
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(_) => {
+                log::info!("Job {name} scheduled as '{schedule}'");
+                Ok(())
+            }
+            Err(e) => {
+                log::error!("Database error when registering job {name}: {e}");
+                Err(JobError::Database(e))
+            }
+        }
+    }
+
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<JobStatus>, JobError> {
+        sqlx::query_as!(
+            JobStatus,
+            r#"
+            SELECT
+                j.name,
+                j.schedule,
+                j.next_run_at,
+                EXISTS(
+                    SELECT 1 FROM pg_locks l
+                    WHERE l.locktype = 'advisory'
+                        AND l.classid = $1::INTEGER::OID
+                        AND l.objid = hashtext(j.name)::OID
+                        AND l.objsubid = 2
+                ) AS "running!",
+                j.last_trigger,
+                j.last_status,
+                j.last_error,
+                j.last_affected,
+                j.last_started_at,
+                j.last_finished_at
+            FROM scheduled_jobs j
+            ORDER BY j.name
+            "#,
+            JOB_LOCK_CLASS
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error when listing jobs: {e}");
+            JobError::Database(e)
+        })
+    }
+
+    pub async fn find_by_name(
+        pool: &PgPool,
+        name: &str,
+    ) -> Result<JobStatus, JobError> {
+        let result = sqlx::query_as!(
+            JobStatus,
+            r#"
+            SELECT
+                j.name,
+                j.schedule,
+                j.next_run_at,
+                EXISTS(
+                    SELECT 1 FROM pg_locks l
+                    WHERE l.locktype = 'advisory'
+                        AND l.classid = $1::INTEGER::OID
+                        AND l.objid = hashtext(j.name)::OID
+                        AND l.objsubid = 2
+                ) AS "running!",
+                j.last_trigger,
+                j.last_status,
+                j.last_error,
+                j.last_affected,
+                j.last_started_at,
+                j.last_finished_at
+            FROM scheduled_jobs j
+            WHERE j.name = $2
+            "#,
+            JOB_LOCK_CLASS,
+            name
+        )
+        .fetch_optional(pool)
+        .await;
+
+        match result {
+            Ok(Some(job)) => Ok(job),
+            Ok(None) => Err(JobError::NotFound(name.to_string())),
+            Err(e) => {
+                log::error!("Database error when finding job {name}: {e}");
+                Err(JobError::Database(e))
+            }
+        }
+    }
+
+    /// Takes the job's advisory lock until the end of the transaction.
+    /// Returns `false` if another connection holds it.
+    pub async fn try_lock<'e, E>(
+        executor: E,
+        name: &str,
+    ) -> Result<bool, JobError>
+    where
+        E: PgExecutor<'e>,
+    {
+        sqlx::query_scalar!(
+            r#"SELECT pg_try_advisory_xact_lock($1, hashtext($2)) AS "locked!""#,
+            JOB_LOCK_CLASS,
+            name
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(|e| {
+            log::error!("Database error when locking job {name}: {e}");
+            JobError::Database(e)
+        })
+    }
+
+    /// Moves the next run forward if the job is due. Returns `false` if it
+    /// isn't, e.g. because another instance has just run it.
+    pub async fn claim_due<'e, E>(
+        executor: E,
+        name: &str,
+        next_run_at: OffsetDateTime,
+    ) -> Result<bool, JobError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE scheduled_jobs
+            SET next_run_at = $2
+            WHERE name = $1 AND next_run_at <= NOW()
+            "#,
+            name,
+            next_run_at
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => {
+                log::error!("Database error when claiming job {name}: {e}");
+                Err(JobError::Database(e))
+            }
+        }
+    }
+
+    pub async fn record_run<'e, E>(
+        executor: E,
+        run: &JobRun,
+    ) -> Result<(), JobError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE scheduled_jobs
+            SET last_trigger = $2,
+                last_status = $3,
+                last_error = $4,
+                last_affected = $5,
+                last_started_at = $6,
+                last_finished_at = $7
+            WHERE name = $1
+            "#,
+            run.name,
+            run.trigger,
+            run.status,
+            run.error,
+            run.affected,
+            run.started_at,
+            run.finished_at
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                log::error!(
+                    "Database error when recording run of job {}: {e}",
+                    run.name
+                );
+                Err(JobError::Database(e))
+            }
+        }
+    }
+}
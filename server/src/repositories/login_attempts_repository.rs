@@ -0,0 +1,142 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::{
+    errors::auth_errors::AuthError,
+    models::login_attempts_models::{LoginAttempt, LoginAttemptScope},
+};
+
+pub struct LoginAttemptRepository;
+
+impl LoginAttemptRepository {
+    pub async fn find(
+        pool: &PgPool,
+        scope: LoginAttemptScope,
+        key: &str,
+    ) -> Result<Option<LoginAttempt>, AuthError> {
+        sqlx::query_as!(
+            LoginAttempt,
+            r#"
+            SELECT failed_attempts, last_failed_at, locked_until
+            FROM login_attempts
+            WHERE scope = $1 AND key = $2
+            "#,
+            scope.as_ref(),
+            key
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error when reading login attempts: {e}");
+            AuthError::Database(e)
+        })
+    }
+
+    /// Counts one more failure. Failures made before `window_start` are
+    /// forgotten, which also ends an expired lockout.
+    pub async fn record_failure(
+        pool: &PgPool,
+        scope: LoginAttemptScope,
+        key: &str,
+        window_start: OffsetDateTime,
+    ) -> Result<LoginAttempt, AuthError> {
+        let result = sqlx::query_as!(
+            LoginAttempt,
+            r#"
+            INSERT INTO login_attempts (scope, key, failed_attempts)
+            VALUES ($1, $2, 1)
+            ON CONFLICT (scope, key) DO UPDATE SET
+                failed_attempts = CASE
+                    WHEN login_attempts.last_failed_at < $3
+                    THEN 1
+                    ELSE login_attempts.failed_attempts + 1
+                END,
+                locked_until = CASE
+                    WHEN login_attempts.last_failed_at < $3
+                    THEN NULL
+                    ELSE login_attempts.locked_until
+                END,
+                last_failed_at = NOW()
+            RETURNING failed_attempts, last_failed_at, locked_until
+            "#,
+            scope.as_ref(),
+            key,
+            window_start
+        )
+        .fetch_one(pool)
+        .await;
+
+        match result {
+            Ok(attempt) => {
+                log::warn!(
+                    "Failed login #{} for {} {key}",
+                    attempt.failed_attempts,
+                    scope.as_ref()
+                );
+                Ok(attempt)
+            }
+            Err(e) => {
+                log::error!("Database error when recording failed login: {e}");
+                Err(AuthError::Database(e))
+            }
+        }
+    }
+
+    pub async fn lock(
+        pool: &PgPool,
+        scope: LoginAttemptScope,
+        key: &str,
+        locked_until: OffsetDateTime,
+    ) -> Result<(), AuthError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE login_attempts
+            SET locked_until = $3
+            WHERE scope = $1 AND key = $2
+            "#,
+            scope.as_ref(),
+            key,
+            locked_until
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(_) => {
+                log::warn!(
+                    "Login locked for {} {key} until {locked_until}",
+                    scope.as_ref()
+                );
+                Ok(())
+            }
+            Err(e) => {
+                log::error!("Database error when locking login: {e}");
+                Err(AuthError::Database(e))
+            }
+        }
+    }
+
+    /// Clears every given key of the scope. Returns `false` if there was
+    /// nothing to clear.
+    pub async fn clear(
+        pool: &PgPool,
+        scope: LoginAttemptScope,
+        keys: &[String],
+    ) -> Result<bool, AuthError> {
+        let result = sqlx::query!(
+            "DELETE FROM login_attempts WHERE scope = $1 AND key = ANY($2)",
+            scope.as_ref(),
+            keys
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => {
+                log::error!("Database error when clearing login attempts: {e}");
+                Err(AuthError::Database(e))
+            }
+        }
+    }
+}
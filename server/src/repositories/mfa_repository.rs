@@ -0,0 +1,289 @@
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    errors::auth_errors::AuthError,
+    models::mfa_models::{MfaChallenge, MfaSettings},
+};
+
+pub struct MfaRepository;
+
+impl MfaRepository {
+    pub async fn find_by_user(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<Option<MfaSettings>, AuthError> {
+        sqlx::query_as!(
+            MfaSettings,
+            r#"
+            SELECT user_id, secret, enabled
+            FROM user_mfa
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Database error when finding MFA of user {user_id}: {e}"
+            );
+            AuthError::Database(e)
+        })
+    }
+
+    pub async fn is_enabled(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<bool, AuthError> {
+        Ok(Self::find_by_user(pool, user_id)
+            .await?
+            .is_some_and(|mfa| mfa.enabled))
+    }
+
+    // Replaces a previous unfinished enrollment, never an enabled one
+    pub async fn save_pending_secret(
+        pool: &PgPool,
+        user_id: i32,
+        secret: &str,
+    ) -> Result<(), AuthError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_mfa (user_id, secret, enabled)
+            VALUES ($1, $2, FALSE)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, created_at = NOW()
+            WHERE user_mfa.enabled = FALSE
+            "#,
+            user_id,
+            secret
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) if res.rows_affected() > 0 => {
+                log::info!("MFA enrollment started for user {user_id}");
+                Ok(())
+            }
+            Ok(_) => Err(AuthError::MfaAlreadyEnabled),
+            Err(e) => {
+                log::error!(
+                    "Database error when saving MFA secret of user {user_id}: {e}"
+                );
+                Err(AuthError::Database(e))
+            }
+        }
+    }
+
+    pub async fn enable(
+        pool: &PgPool,
+        user_id: i32,
+        step: i64,
+    ) -> Result<(), AuthError> {
+        sqlx::query!(
+            r#"
+            UPDATE user_mfa
+            SET enabled = TRUE, enabled_at = NOW(), last_used_step = $2
+            WHERE user_id = $1
+            "#,
+            user_id,
+            step
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Database error when enabling MFA of user {user_id}: {e}"
+            );
+            AuthError::Database(e)
+        })?;
+
+        log::info!("MFA enabled for user {user_id}");
+        Ok(())
+    }
+
+    /// Records the TOTP time step as used. Returns `false` if this or a
+    /// later step was already accepted, so a code cannot be replayed.
+    pub async fn consume_step(
+        pool: &PgPool,
+        user_id: i32,
+        step: i64,
+    ) -> Result<bool, AuthError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_mfa
+            SET last_used_step = $2
+            WHERE user_id = $1
+                AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(pool)
+        .await
+        .map_err(AuthError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete(pool: &PgPool, user_id: i32) -> Result<(), AuthError> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM mfa_recovery_codes WHERE user_id = $1",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!("DELETE FROM user_mfa WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        log::info!("MFA disabled for user {user_id}");
+        Ok(())
+    }
+
+    // ======== Recovery codes ========
+
+    pub async fn replace_recovery_codes(
+        pool: &PgPool,
+        user_id: i32,
+        code_hashes: &[String],
+    ) -> Result<(), AuthError> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM mfa_recovery_codes WHERE user_id = $1",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO mfa_recovery_codes (user_id, code_hash)
+            SELECT $1, code_hash FROM UNNEST($2::VARCHAR[]) AS code_hash
+            "#,
+            user_id,
+            code_hashes
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        log::info!("Recovery codes regenerated for user {user_id}");
+        Ok(())
+    }
+
+    pub async fn use_recovery_code(
+        pool: &PgPool,
+        user_id: i32,
+        code_hash: &str,
+    ) -> Result<bool, AuthError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE mfa_recovery_codes
+            SET used_at = NOW()
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            code_hash
+        )
+        .execute(pool)
+        .await
+        .map_err(AuthError::Database)?;
+
+        if result.rows_affected() > 0 {
+            log::info!("Recovery code used by user {user_id}");
+        }
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ======== Login challenges ========
+
+    pub async fn create_challenge(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<MfaChallenge, AuthError> {
+        let expires_at = OffsetDateTime::now_utc()
+            + Duration::seconds(
+                configs::Config::global().mfa_challenge_expires,
+            );
+
+        sqlx::query_as!(
+            MfaChallenge,
+            r#"
+            INSERT INTO mfa_challenges (token, user_id, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING token, user_id, expires_at
+            "#,
+            Uuid::new_v4().to_string(),
+            user_id,
+            expires_at
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Database error when creating MFA challenge for user {user_id}: {e}"
+            );
+            AuthError::Database(e)
+        })
+    }
+
+    pub async fn find_challenge(
+        pool: &PgPool,
+        token: &str,
+    ) -> Result<MfaChallenge, AuthError> {
+        sqlx::query_as!(
+            MfaChallenge,
+            r#"
+            SELECT token, user_id, expires_at
+            FROM mfa_challenges
+            WHERE token = $1
+            "#,
+            token
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(AuthError::Database)?
+        .ok_or(AuthError::MfaChallengeNotFound)
+    }
+
+    pub async fn increment_challenge_attempts(
+        pool: &PgPool,
+        token: &str,
+    ) -> Result<i32, AuthError> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE mfa_challenges
+            SET attempts = attempts + 1
+            WHERE token = $1
+            RETURNING attempts
+            "#,
+            token
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(AuthError::Database)?
+        .ok_or(AuthError::MfaChallengeNotFound)
+    }
+
+    /// Returns `false` if the challenge was already consumed.
+    pub async fn delete_challenge(
+        pool: &PgPool,
+        token: &str,
+    ) -> Result<bool, AuthError> {
+        let result =
+            sqlx::query!("DELETE FROM mfa_challenges WHERE token = $1", token)
+                .execute(pool)
+                .await
+                .map_err(AuthError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
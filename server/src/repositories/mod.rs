@@ -1,4 +1,16 @@
 pub mod auth_repisitory;
+pub mod email_campaign_repository;
+pub mod email_log_repository;
+pub mod email_outbox_repository;
+pub mod invitation_repository;
+pub mod job_repository;
+pub mod login_attempts_repository;
+pub mod mfa_repository;
+pub mod password_reset_repository;
 pub mod posts_repository;
+pub mod rate_limit_repository;
+pub mod revoked_tokens_repository;
+pub mod roles_repository;
+pub mod security_events_repository;
 pub mod temp_registration_repository;
 pub mod users_repository;
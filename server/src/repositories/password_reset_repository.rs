@@ -0,0 +1,88 @@
+use sqlx::{PgExecutor, PgPool};
+use time::OffsetDateTime;
+
+use crate::errors::auth_errors::AuthError;
+
+pub struct PasswordResetRepository;
+
+impl PasswordResetRepository {
+    pub async fn create<'e, E>(
+        executor: E,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), AuthError>
+    where
+        E: PgExecutor<'e>,
+    {
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens (user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            token_hash,
+            expires_at
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Database error when creating reset token for user {user_id}: {e}"
+            );
+            AuthError::Database(e)
+        })?;
+
+        log::info!("Password reset token created for user {user_id}");
+        Ok(())
+    }
+
+    /// Marks a valid token as used and returns its user. Fails for unknown,
+    /// expired or already used tokens, so each token works only once.
+    pub async fn consume(
+        pool: &PgPool,
+        token_hash: &str,
+    ) -> Result<i32, AuthError> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE password_reset_tokens
+            SET used_at = NOW()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING user_id
+            "#,
+            token_hash
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error when consuming reset token: {e}");
+            AuthError::Database(e)
+        })?
+        .ok_or(AuthError::InvalidResetToken)
+    }
+
+    // Other links that are still in the user's mailbox stop working
+    pub async fn invalidate_all(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<(), AuthError> {
+        sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens
+            SET used_at = NOW()
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Database error when invalidating reset tokens of user {user_id}: {e}"
+            );
+            AuthError::Database(e)
+        })?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::{
+    errors::rate_limit_errors::RateLimitError,
+    models::rate_limit_models::WindowHits,
+};
+
+pub struct RateLimitRepository;
+
+impl RateLimitRepository {
+    /// Counts a hit in the window starting at `window_start` (unix seconds)
+    /// and reads the previous window in the same statement.
+    pub async fn hit(
+        pool: &PgPool,
+        key: &str,
+        window_start: i64,
+        window: i64,
+        expires_at: OffsetDateTime,
+    ) -> Result<WindowHits, RateLimitError> {
+        let result = sqlx::query_as!(
+            WindowHits,
+            r#"
+            WITH current AS (
+                INSERT INTO rate_limit_counters (key, window_start, hits, expires_at)
+                VALUES ($1, $2, 1, $4)
+                ON CONFLICT (key, window_start)
+                DO UPDATE SET hits = rate_limit_counters.hits + 1
+                RETURNING hits
+            )
+            SELECT
+                (SELECT hits FROM current) AS "current!",
+                COALESCE(
+                    (
+                        SELECT hits FROM rate_limit_counters
+                        WHERE key = $1 AND window_start = $2 - $3
+                    ),
+                    0
+                ) AS "previous!"
+            "#,
+            key,
+            window_start,
+            window,
+            expires_at
+        )
+        .fetch_one(pool)
+        .await;
+
+        result.map_err(|e| {
+            log::error!("Database error when counting rate limit hit: {e}");
+            RateLimitError::Database(e)
+        })
+    }
+
+    pub async fn purge_expired(pool: &PgPool) -> Result<u64, RateLimitError> {
+        let result = sqlx::query!(
+            "DELETE FROM rate_limit_counters WHERE expires_at < NOW()"
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,116 @@
+use sqlx::{Error as SqlxError, PgPool};
+use time::OffsetDateTime;
+
+use crate::models::revoked_tokens_models::RevokedToken;
+
+pub struct RevokedTokenRepository;
+
+impl RevokedTokenRepository {
+    /// Denylists the access tokens issued for one session. Every refresh
+    /// token row remembers the access token issued alongside it.
+    pub async fn revoke_family(
+        pool: &PgPool,
+        user_id: i32,
+        family_id: &str,
+    ) -> Result<Vec<RevokedToken>, SqlxError> {
+        let result = sqlx::query_as!(
+            RevokedToken,
+            r#"
+            INSERT INTO revoked_access_tokens (jti, user_id, expires_at)
+            SELECT access_jti, user_id, access_expires_at
+            FROM refresh_tokens
+            WHERE user_id = $1
+                AND family_id = $2
+                AND access_jti IS NOT NULL
+                AND access_expires_at > NOW()
+            ON CONFLICT (jti) DO NOTHING
+            RETURNING jti, expires_at
+            "#,
+            user_id,
+            family_id
+        )
+        .fetch_all(pool)
+        .await;
+
+        match result {
+            Ok(tokens) => {
+                log::info!(
+                    "{} access tokens of session {family_id} revoked",
+                    tokens.len()
+                );
+                Ok(tokens)
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when revoking access tokens of session {family_id}: {e}"
+                );
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn revoke_user(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<Vec<RevokedToken>, SqlxError> {
+        let result = sqlx::query_as!(
+            RevokedToken,
+            r#"
+            INSERT INTO revoked_access_tokens (jti, user_id, expires_at)
+            SELECT access_jti, user_id, access_expires_at
+            FROM refresh_tokens
+            WHERE user_id = $1
+                AND access_jti IS NOT NULL
+                AND access_expires_at > NOW()
+            ON CONFLICT (jti) DO NOTHING
+            RETURNING jti, expires_at
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await;
+
+        match result {
+            Ok(tokens) => {
+                log::info!(
+                    "{} access tokens of user {user_id} revoked",
+                    tokens.len()
+                );
+                Ok(tokens)
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when revoking access tokens of user {user_id}: {e}"
+                );
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn find_revoked_since(
+        pool: &PgPool,
+        since: OffsetDateTime,
+    ) -> Result<Vec<RevokedToken>, SqlxError> {
+        sqlx::query_as!(
+            RevokedToken,
+            r#"
+            SELECT jti, expires_at
+            FROM revoked_access_tokens
+            WHERE revoked_at > $1 AND expires_at > NOW()
+            "#,
+            since
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn purge_expired(pool: &PgPool) -> Result<u64, SqlxError> {
+        let result = sqlx::query!(
+            "DELETE FROM revoked_access_tokens WHERE expires_at < NOW()"
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
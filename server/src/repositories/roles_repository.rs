@@ -0,0 +1,224 @@
+use crate::{errors::roles_errors::RoleError, models::roles_models::Role};
+use sqlx::{PgExecutor, PgPool};
+
+pub struct RoleRepository;
+
+impl RoleRepository {
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Role>, RoleError> {
+        let result = sqlx::query_as!(
+            Role,
+            r#"
+            SELECT
+                r.id,
+                r.name,
+                r.description,
+                COALESCE(
+                    array_agg(p.name ORDER BY p.name)
+                        FILTER (WHERE p.name IS NOT NULL),
+                    '{}'
+                ) AS "permissions!"
+            FROM roles r
+            LEFT JOIN role_permissions rp ON rp.role_id = r.id
+            LEFT JOIN permissions p ON p.id = rp.permission_id
+            GROUP BY r.id
+            ORDER BY r.name
+            "#
+        )
+        .fetch_all(pool)
+        .await;
+
+        match result {
+            Ok(roles) => {
+                log::info!("Roles successfully found");
+                Ok(roles)
+            }
+            Err(e) => {
+                log::error!("Database error when finding roles: {e}");
+                Err(RoleError::Database(e))
+            }
+        }
+    }
+
+    pub async fn get_user_roles(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<Vec<Role>, RoleError> {
+        let result = sqlx::query_as!(
+            Role,
+            r#"
+            SELECT
+                r.id,
+                r.name,
+                r.description,
+                COALESCE(
+                    array_agg(p.name ORDER BY p.name)
+                        FILTER (WHERE p.name IS NOT NULL),
+                    '{}'
+                ) AS "permissions!"
+            FROM user_roles ur
+            JOIN roles r ON r.id = ur.role_id
+            LEFT JOIN role_permissions rp ON rp.role_id = r.id
+            LEFT JOIN permissions p ON p.id = rp.permission_id
+            WHERE ur.user_id = $1
+            GROUP BY r.id
+            ORDER BY r.name
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await;
+
+        match result {
+            Ok(roles) => Ok(roles),
+            Err(e) => {
+                log::error!(
+                    "Database error when finding roles of user {user_id}: {e}"
+                );
+                Err(RoleError::Database(e))
+            }
+        }
+    }
+
+    /// Distinct permission names granted to the user through all roles.
+    pub async fn get_user_permissions(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<Vec<String>, RoleError> {
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT p.name
+            FROM user_roles ur
+            JOIN role_permissions rp ON rp.role_id = ur.role_id
+            JOIN permissions p ON p.id = rp.permission_id
+            WHERE ur.user_id = $1
+            ORDER BY p.name
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await;
+
+        match result {
+            Ok(permissions) => Ok(permissions),
+            Err(e) => {
+                log::error!(
+                    "Database error when finding permissions of user {user_id}: {e}"
+                );
+                Err(RoleError::Database(e))
+            }
+        }
+    }
+
+    pub async fn assign_role(
+        pool: &PgPool,
+        user_id: i32,
+        role_name: &str,
+    ) -> Result<(), RoleError> {
+        let role_id = Self::find_role_id(pool, role_name).await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_roles (user_id, role_id)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, role_id) DO NOTHING
+            "#,
+            user_id,
+            role_id
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(_) => {
+                log::info!("Role '{role_name}' assigned to user {user_id}");
+                Ok(())
+            }
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                log::error!("User {user_id} not found during role assignment");
+                Err(RoleError::UserNotFound)
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when assigning role '{role_name}' to user {user_id}: {e}"
+                );
+                Err(RoleError::Database(e))
+            }
+        }
+    }
+
+    /// Grants a role by ID, for callers that already hold a transaction,
+    /// e.g. accepting an invitation.
+    pub async fn assign_role_id<'e, E>(
+        executor: E,
+        user_id: i32,
+        role_id: i32,
+    ) -> Result<(), RoleError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_roles (user_id, role_id)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, role_id) DO NOTHING
+            "#,
+            user_id,
+            role_id
+        )
+        .execute(executor)
+        .await;
+
+        match result {
+            Ok(_) => {
+                log::info!("Role #{role_id} assigned to user {user_id}");
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when assigning role #{role_id} to user {user_id}: {e}"
+                );
+                Err(RoleError::Database(e))
+            }
+        }
+    }
+
+    pub async fn revoke_role(
+        pool: &PgPool,
+        user_id: i32,
+        role_name: &str,
+    ) -> Result<(), RoleError> {
+        let role_id = Self::find_role_id(pool, role_name).await?;
+
+        let result = sqlx::query!(
+            "DELETE FROM user_roles WHERE user_id = $1 AND role_id = $2",
+            user_id,
+            role_id
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(_) => {
+                log::info!("Role '{role_name}' revoked from user {user_id}");
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when revoking role '{role_name}' from user {user_id}: {e}"
+                );
+                Err(RoleError::Database(e))
+            }
+        }
+    }
+
+    async fn find_role_id(
+        pool: &PgPool,
+        role_name: &str,
+    ) -> Result<i32, RoleError> {
+        sqlx::query_scalar!("SELECT id FROM roles WHERE name = $1", role_name)
+            .fetch_optional(pool)
+            .await
+            .map_err(RoleError::Database)?
+            .ok_or_else(|| RoleError::NotFound(role_name.to_string()))
+    }
+}
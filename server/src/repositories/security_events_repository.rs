@@ -0,0 +1,36 @@
+use sqlx::PgPool;
+
+use crate::models::security_events_models::SecurityEventType;
+
+pub struct SecurityEventRepository;
+
+impl SecurityEventRepository {
+    /// Stores a security event. Failures are only logged: auditing must
+    /// never change the outcome of the request that triggered it.
+    pub async fn record(
+        pool: &PgPool,
+        user_id: Option<i32>,
+        event_type: SecurityEventType,
+        details: &str,
+    ) {
+        log::warn!(
+            "Security event {event_type} for user {user_id:?}: {details}"
+        );
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO security_events (user_id, event_type, details)
+            VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            event_type.as_ref(),
+            details
+        )
+        .execute(pool)
+        .await;
+
+        if let Err(e) = result {
+            log::error!("Failed to record security event {event_type}: {e}");
+        }
+    }
+}
@@ -2,32 +2,53 @@ use crate::{
     errors::temp_registration_errors::TempRegistrationError,
     models::temp_registration::{CreateTempRegistration, TempRegistration},
 };
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use time::{Duration, OffsetDateTime};
 
 pub struct TempRegistrationRepository;
 
 impl TempRegistrationRepository {
-    pub async fn create(
-        pool: &PgPool,
+    /// Starts a registration, replacing an earlier one for the same email
+    /// together with its code and attempts if that code was sent at least
+    /// `cooldown` ago. Returns None if it was not.
+    pub async fn create<'e, E>(
+        executor: E,
         registration_data: CreateTempRegistration,
-        secret_key: String,
-    ) -> Result<TempRegistration, TempRegistrationError> {
+        secret_key_hash: String,
+        cooldown: Duration,
+    ) -> Result<Option<TempRegistration>, TempRegistrationError>
+    where
+        E: PgExecutor<'e>,
+    {
         let expires_at = OffsetDateTime::now_utc() + Duration::hours(24);
 
         let registration = sqlx::query_as!(
             TempRegistration,
             r#"
-            INSERT INTO temp_registrations (email, password, secret_key, created_at, expires_at, confirmed)
-            VALUES ($1, $2, $3, NOW(), $4, FALSE)
-            RETURNING id, email, password, secret_key, created_at, expires_at, confirmed
+            INSERT INTO temp_registrations (email, username, password, secret_key_hash, language, created_at, expires_at, confirmed, attempts, code_sent_at)
+            VALUES ($1, $2, $3, $4, $5, NOW(), $6, FALSE, 0, NOW())
+            ON CONFLICT (email) DO UPDATE
+            SET username = EXCLUDED.username,
+                password = EXCLUDED.password,
+                secret_key_hash = EXCLUDED.secret_key_hash,
+                language = EXCLUDED.language,
+                created_at = NOW(),
+                expires_at = EXCLUDED.expires_at,
+                confirmed = FALSE,
+                attempts = 0,
+                code_sent_at = NOW()
+            WHERE temp_registrations.code_sent_at <= NOW() - $7 * INTERVAL '1 second'
+            RETURNING id, email, username, password, secret_key_hash, language, created_at, expires_at, confirmed, attempts, code_sent_at
             "#,
             registration_data.email,
+            registration_data.username,
             registration_data.password,
-            secret_key,
-            expires_at
+            secret_key_hash,
+            registration_data.language,
+            expires_at,
+            cooldown.as_seconds_f64()
         )
-        .fetch_one(pool)
+        .fetch_optional(executor)
         .await
         .map_err(TempRegistrationError::Database)?;
 
@@ -44,11 +65,15 @@ impl TempRegistrationRepository {
             SELECT 
                 id, 
                 email, 
+                username,
                 password, 
-                secret_key, 
+                secret_key_hash, 
+                language, 
                 created_at, 
                 expires_at, 
-                confirmed
+                confirmed,
+                attempts,
+                code_sent_at
             FROM temp_registrations WHERE email = $1
             "#,
             email
@@ -60,50 +85,80 @@ impl TempRegistrationRepository {
         Ok(registration)
     }
 
-    pub async fn find_valid_by_email_and_key(
-        pool: &PgPool,
+    /// Counts a confirmation attempt and returns the registration with the
+    /// new count. Counting before the code is checked keeps parallel
+    /// guesses within the limit. Inside a transaction the row stays locked
+    /// until it ends, so concurrent completions run one after the other.
+    pub async fn record_attempt<'e, E>(
+        executor: E,
         email: &str,
-        secret_key: &str,
-    ) -> Result<TempRegistration, TempRegistrationError> {
+    ) -> Result<TempRegistration, TempRegistrationError>
+    where
+        E: PgExecutor<'e>,
+    {
         let registration = sqlx::query_as!(
             TempRegistration,
             r#"
-            SELECT 
-                id, 
-                email, 
-                password, 
-                secret_key, 
-                created_at, 
-                expires_at, 
-                confirmed
-            FROM temp_registrations 
-            WHERE email = $1 AND secret_key = $2 AND expires_at > NOW()
+            UPDATE temp_registrations
+            SET attempts = attempts + 1
+            WHERE email = $1
+            RETURNING id, email, username, password, secret_key_hash, language, created_at, expires_at, confirmed, attempts, code_sent_at
             "#,
-            email,
-            secret_key
+            email
         )
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await
         .map_err(TempRegistrationError::Database)?;
 
         registration.ok_or(TempRegistrationError::NotFound)
     }
 
-    pub async fn mark_as_confirmed(
-        pool: &PgPool,
+    /// Replaces the code of a pending registration last sent at least
+    /// `cooldown` ago. Attempts carry over, so resending does not buy more
+    /// guesses. Returns `false` if there is no such registration.
+    pub async fn update_code<'e, E>(
+        executor: E,
         email: &str,
-        secret_key: &str,
-    ) -> Result<bool, TempRegistrationError> {
+        secret_key_hash: &str,
+        cooldown: Duration,
+    ) -> Result<bool, TempRegistrationError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE temp_registrations
+            SET secret_key_hash = $2, code_sent_at = NOW()
+            WHERE email = $1 AND expires_at > NOW() AND confirmed = FALSE
+                AND code_sent_at <= NOW() - $3 * INTERVAL '1 second'
+            "#,
+            email,
+            secret_key_hash,
+            cooldown.as_seconds_f64()
+        )
+        .execute(executor)
+        .await
+        .map_err(TempRegistrationError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn mark_as_confirmed<'e, E>(
+        executor: E,
+        email: &str,
+    ) -> Result<bool, TempRegistrationError>
+    where
+        E: PgExecutor<'e>,
+    {
         let result = sqlx::query!(
             r#"
             UPDATE temp_registrations 
             SET confirmed = TRUE 
-            WHERE email = $1 AND secret_key = $2 AND expires_at > NOW()
+            WHERE email = $1 AND expires_at > NOW()
             "#,
-            email,
-            secret_key
+            email
         )
-        .execute(pool)
+        .execute(executor)
         .await
         .map_err(TempRegistrationError::Database)?;
 
@@ -115,46 +170,79 @@ impl TempRegistrationRepository {
         email: &str,
     ) -> Result<bool, TempRegistrationError> {
         if let Some(existing) = Self::find_by_email(pool, email).await? {
-            let one_minute_ago =
-                OffsetDateTime::now_utc() - Duration::minutes(1);
-            Ok(existing.created_at <= one_minute_ago)
+            let cooldown = Duration::seconds(
+                configs::Config::global().registration_resend_cooldown,
+            );
+            Ok(existing.code_sent_at <= OffsetDateTime::now_utc() - cooldown)
         } else {
             Ok(true) // Записи нет, можно создавать
         }
     }
 
-    pub async fn delete_by_email(
-        pool: &PgPool,
+    pub async fn delete_by_email<'e, E>(
+        executor: E,
         email: &str,
-    ) -> Result<(), TempRegistrationError> {
+    ) -> Result<(), TempRegistrationError>
+    where
+        E: PgExecutor<'e>,
+    {
         sqlx::query!(
             r#"
             DELETE FROM temp_registrations WHERE email = $1
             "#,
             email
         )
-        .execute(pool)
+        .execute(executor)
         .await
         .map_err(TempRegistrationError::Database)?;
 
         Ok(())
     }
 
-    pub async fn cleanup_expired(
-        pool: &PgPool,
-    ) -> Result<u64, TempRegistrationError> {
+    pub async fn cleanup_expired<'e, E>(
+        executor: E,
+    ) -> Result<u64, TempRegistrationError>
+    where
+        E: PgExecutor<'e>,
+    {
         let result = sqlx::query!(
             r#"
             DELETE FROM temp_registrations WHERE expires_at <= NOW()
             "#
         )
-        .execute(pool)
+        .execute(executor)
         .await
         .map_err(TempRegistrationError::Database)?;
 
         Ok(result.rows_affected())
     }
 
+    /// Whether a pending registration claims the username, leaving out the
+    /// one of `except_email`.
+    pub async fn is_username_pending(
+        pool: &PgPool,
+        username: &str,
+        except_email: Option<&str>,
+    ) -> Result<bool, TempRegistrationError> {
+        let pending = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM temp_registrations
+                WHERE LOWER(username) = LOWER($1)
+                    AND ($2::TEXT IS NULL OR email <> $2)
+                    AND expires_at > NOW() AND confirmed = FALSE
+            ) AS "pending!"
+            "#,
+            username,
+            except_email
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(TempRegistrationError::Database)?;
+
+        Ok(pending)
+    }
+
     pub async fn is_email_in_registration(
         pool: &PgPool,
         email: &str,
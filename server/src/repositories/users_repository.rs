@@ -1,28 +1,37 @@
 use crate::{
     errors::users_errors::UserError,
-    models::users_models::{CreateUser, UpdateUser, User},
+    models::users_models::{
+        CreateUser, UpdateUser, User, UserCursor, UserField, UserFilter,
+        UserSort, UserSortField,
+    },
 };
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool, Postgres, QueryBuilder};
 
 pub struct UserRepository;
 
 impl UserRepository {
-    pub async fn create(
-        pool: &PgPool,
+    /// Creates a user. A taken username is reported as `UsernameTaken`, a
+    /// taken email as `AlreadyExists`.
+    pub async fn create<'e, E>(
+        executor: E,
         user_data: CreateUser,
-    ) -> Result<User, UserError> {
+    ) -> Result<User, UserError>
+    where
+        E: PgExecutor<'e>,
+    {
         let result = sqlx::query_as!(
             User,
             r#"
-            INSERT INTO users (username, email, password, created_at, updated_at)
-            VALUES ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
-            RETURNING id, username, email, password, created_at, updated_at
+            INSERT INTO users (username, email, password, language, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, username, email, password, language, created_at, updated_at
             "#,
             user_data.username,
             user_data.email,
             user_data.password,
+            user_data.language,
         )
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await;
 
         match result {
@@ -42,6 +51,26 @@ impl UserRepository {
                 );
                 Err(UserError::NotFound)
             }
+            Err(sqlx::Error::Database(e))
+                if e.is_unique_violation()
+                    && matches!(
+                        e.constraint(),
+                        Some("users_username_key" | "idx_users_username_lower")
+                    ) =>
+            {
+                log::warn!(
+                    "User {} not created, username taken",
+                    user_data.username
+                );
+                Err(UserError::UsernameTaken)
+            }
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                log::warn!(
+                    "User {} not created, email taken",
+                    user_data.username
+                );
+                Err(UserError::AlreadyExists)
+            }
             Err(e) => {
                 log::error!(
                     "Database error when creating user {}: {}",
@@ -53,17 +82,71 @@ impl UserRepository {
         }
     }
 
-    pub async fn get_all(pool: &PgPool) -> Result<Vec<User>, UserError> {
-        let result = sqlx::query_as!(
-            User, 
-            "SELECT id, username, email, password, created_at, updated_at FROM users"
-        )
-        .fetch_all(pool)
-        .await;
+    /// One page of users matching the filter, continuing after the cursor
+    /// when there is one. The SQL is assembled from `UserSortField` names,
+    /// everything the client sent is bound.
+    pub async fn find_page(
+        pool: &PgPool,
+        filter: &UserFilter,
+        sort: UserSort,
+        after: Option<&UserCursor>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, UserError> {
+        let mut builder = QueryBuilder::<Postgres>::new(
+            "SELECT id, username, email, password, language, created_at, updated_at FROM users WHERE TRUE",
+        );
+        Self::push_filter(&mut builder, filter);
+
+        let column = sort.field.as_ref();
+        let (comparison, direction) =
+            if sort.descending { ("<", "DESC") } else { (">", "ASC") };
+
+        if let Some(cursor) = after {
+            match sort.field {
+                UserSortField::Id => {
+                    builder
+                        .push(format!(" AND id {comparison} "))
+                        .push_bind(cursor.id);
+                }
+                UserSortField::Username | UserSortField::Email => {
+                    builder
+                        .push(format!(" AND ({column}, id) {comparison} ("))
+                        .push_bind(cursor.value.clone())
+                        .push(", ")
+                        .push_bind(cursor.id)
+                        .push(")");
+                }
+                UserSortField::CreatedAt | UserSortField::UpdatedAt => {
+                    builder
+                        .push(format!(" AND ({column}, id) {comparison} ("))
+                        .push_bind(cursor.value.clone())
+                        .push("::TIMESTAMPTZ, ")
+                        .push_bind(cursor.id)
+                        .push(")");
+                }
+            }
+        }
+
+        // IDs break ties, so pages neither overlap nor skip rows
+        if sort.field == UserSortField::Id {
+            builder.push(format!(" ORDER BY id {direction}"));
+        } else {
+            builder.push(format!(
+                " ORDER BY {column} {direction}, id {direction}"
+            ));
+        }
+        builder
+            .push(" LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        let result = builder.build_query_as::<User>().fetch_all(pool).await;
 
         match result {
             Ok(users) => {
-                log::info!("Users successfully found");
+                log::info!("Users successfully found, sorted by {sort}");
                 Ok(users)
             }
             Err(e) => {
@@ -73,13 +156,48 @@ impl UserRepository {
         }
     }
 
+    pub async fn count(
+        pool: &PgPool,
+        filter: &UserFilter,
+    ) -> Result<i64, UserError> {
+        let mut builder = QueryBuilder::<Postgres>::new(
+            "SELECT COUNT(*) FROM users WHERE TRUE",
+        );
+        Self::push_filter(&mut builder, filter);
+
+        builder.build_query_scalar::<i64>().fetch_one(pool).await.map_err(|e| {
+            log::error!("Database error when counting users: {e}");
+            UserError::Database(e)
+        })
+    }
+
+    fn push_filter(
+        builder: &mut QueryBuilder<'_, Postgres>,
+        filter: &UserFilter,
+    ) {
+        if let Some(pattern) = filter.search_pattern() {
+            builder
+                .push(" AND (username ILIKE ")
+                .push_bind(pattern.clone())
+                .push(" OR email ILIKE ")
+                .push_bind(pattern)
+                .push(")");
+        }
+        if let Some(created_from) = filter.created_from {
+            builder.push(" AND created_at >= ").push_bind(created_from);
+        }
+        if let Some(created_to) = filter.created_to {
+            builder.push(" AND created_at < ").push_bind(created_to);
+        }
+    }
+
     pub async fn find_by_id(
         pool: &PgPool,
         user_id: i32,
     ) -> Result<User, UserError> {
         let result = sqlx::query_as!(
             User,
-            "SELECT id, username, email, password, created_at, updated_at FROM users WHERE id = $1",
+            "SELECT id, username, email, password, language, created_at, updated_at FROM users WHERE id = $1",
             user_id
         )
         .fetch_optional(pool)
@@ -105,14 +223,14 @@ impl UserRepository {
         }
     }
 
-    pub async fn find_by_username(
+    pub async fn find_by_email(
         pool: &PgPool,
-        username: &str,
+        email: &str,
     ) -> Result<User, UserError> {
         let result = sqlx::query_as!(
             User,
-            "SELECT id, username, email, password, created_at, updated_at FROM users WHERE username = $1",
-            username
+            "SELECT id, username, email, password, language, created_at, updated_at FROM users WHERE email = $1",
+            email
         )
         .fetch_optional(pool)
         .await;
@@ -120,50 +238,76 @@ impl UserRepository {
         match result {
             Ok(Some(user)) => {
                 log::info!(
-                    "User {} successfully found '{}'",
-                    username,
+                    "User with email {} successfully found '{}'",
+                    email,
                     user.username
                 );
                 Ok(user)
             }
             Ok(None) => {
-                log::error!("User {username} not found");
+                log::error!("User with email {email} not found");
                 Err(UserError::NotFound)
             }
             Err(e) => {
-                log::error!("Database error when finding user {username}: {e}");
+                log::error!("Database error when finding user with email {email}: {e}");
                 Err(UserError::Database(e))
             }
         }
     }
 
-    pub async fn find_by_email(
+    /// Finds the user logging in, by email if the login has an `@` and by
+    /// username otherwise. Both ignore case.
+    pub async fn find_by_login(
         pool: &PgPool,
-        email: &str,
+        login: &str,
     ) -> Result<User, UserError> {
         let result = sqlx::query_as!(
             User,
-            "SELECT id, username, email, password, created_at, updated_at FROM users WHERE email = $1",
-            email
+            r#"
+            SELECT id, username, email, password, language, created_at, updated_at
+            FROM users
+            WHERE CASE WHEN STRPOS($1, '@') > 0
+                THEN LOWER(email) = LOWER($1)
+                ELSE LOWER(username) = LOWER($1)
+            END
+            LIMIT 1
+            "#,
+            login
         )
         .fetch_optional(pool)
         .await;
 
         match result {
-            Ok(Some(user)) => {
-                log::info!(
-                    "User with email {} successfully found '{}'",
-                    email,
-                    user.username
-                );
-                Ok(user)
-            }
+            Ok(Some(user)) => Ok(user),
             Ok(None) => {
-                log::error!("User with email {email} not found");
+                log::error!("User {login} not found");
                 Err(UserError::NotFound)
             }
             Err(e) => {
-                log::error!("Database error when finding user with email {email}: {e}");
+                log::error!("Database error when finding user {login}: {e}");
+                Err(UserError::Database(e))
+            }
+        }
+    }
+
+    // Ignores case, "Alice" and "alice" cannot both exist
+    pub async fn is_username_taken(
+        pool: &PgPool,
+        username: &str,
+    ) -> Result<bool, UserError> {
+        let result = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(username) = LOWER($1)) AS "taken!""#,
+            username
+        )
+        .fetch_one(pool)
+        .await;
+
+        match result {
+            Ok(taken) => Ok(taken),
+            Err(e) => {
+                log::error!(
+                    "Database error when checking username {username}: {e}"
+                );
                 Err(UserError::Database(e))
             }
         }
@@ -199,13 +343,14 @@ impl UserRepository {
             User,
             r#"
             UPDATE users 
-            SET username = $1, email = $2, password = $3, updated_at = CURRENT_TIMESTAMP 
-            WHERE id = $4 
-            RETURNING id, username, email, password, created_at, updated_at
+            SET username = $1, email = $2, password = $3, language = $4, updated_at = CURRENT_TIMESTAMP 
+            WHERE id = $5 
+            RETURNING id, username, email, password, language, created_at, updated_at
             "#,
             user_data.username,
             user_data.email,
             user_data.password,
+            user_data.language,
             user_id,
         )
         .fetch_optional(pool)
@@ -232,6 +377,134 @@ impl UserRepository {
         }
     }
 
+    /// The user, locked until the end of the transaction so concurrent
+    /// updates see each other's changes.
+    pub async fn lock_by_id<'e, E>(
+        executor: E,
+        user_id: i32,
+    ) -> Result<User, UserError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let result = sqlx::query_as!(
+            User,
+            "SELECT id, username, email, password, language, created_at, updated_at FROM users WHERE id = $1 FOR UPDATE",
+            user_id
+        )
+        .fetch_optional(executor)
+        .await;
+
+        match result {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => {
+                log::error!("User {user_id} not found");
+                Err(UserError::NotFound)
+            }
+            Err(e) => {
+                log::error!("Database error when locking user {user_id}: {e}");
+                Err(UserError::Database(e))
+            }
+        }
+    }
+
+    /// Sets only the given columns. Column names come from `UserField`,
+    /// the values are bound.
+    pub async fn update_fields<'e, E>(
+        executor: E,
+        user_id: i32,
+        changes: &[(UserField, Option<String>)],
+    ) -> Result<User, UserError>
+    where
+        E: PgExecutor<'e>,
+    {
+        let mut builder = QueryBuilder::<Postgres>::new("UPDATE users SET ");
+        let mut assignments = builder.separated(", ");
+        for (field, value) in changes {
+            assignments
+                .push(format!("{} = ", field.as_ref()))
+                .push_bind_unseparated(value.clone());
+        }
+        assignments.push("updated_at = CURRENT_TIMESTAMP");
+        builder
+            .push(" WHERE id = ")
+            .push_bind(user_id)
+            .push(" RETURNING id, username, email, password, language, created_at, updated_at");
+
+        let result =
+            builder.build_query_as::<User>().fetch_optional(executor).await;
+
+        match result {
+            Ok(Some(user)) => {
+                log::info!(
+                    "User {user_id} successfully updated: {}",
+                    changes
+                        .iter()
+                        .map(|(field, _)| field.as_ref())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                Ok(user)
+            }
+            Ok(None) => {
+                log::error!("User {user_id} not found during update");
+                Err(UserError::NotFound)
+            }
+            Err(sqlx::Error::Database(e))
+                if e.is_unique_violation()
+                    && matches!(
+                        e.constraint(),
+                        Some("users_username_key" | "idx_users_username_lower")
+                    ) =>
+            {
+                log::warn!("User {user_id} not updated, username taken");
+                Err(UserError::UsernameTaken)
+            }
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                log::warn!("User {user_id} not updated, email taken");
+                Err(UserError::AlreadyExists)
+            }
+            Err(e) => {
+                log::error!("Database error when updating user {user_id}: {e}");
+                Err(UserError::Database(e))
+            }
+        }
+    }
+
+    pub async fn update_password(
+        pool: &PgPool,
+        user_id: i32,
+        password_hash: &str,
+    ) -> Result<(), UserError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password = $1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            "#,
+            password_hash,
+            user_id,
+        )
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(res) if res.rows_affected() > 0 => {
+                log::info!("Password of user {user_id} successfully updated");
+                Ok(())
+            }
+            Ok(_) => {
+                log::error!("User {user_id} not found during password update");
+                Err(UserError::NotFound)
+            }
+            Err(e) => {
+                log::error!(
+                    "Database error when updating password of user {user_id}: {e}"
+                );
+                Err(UserError::Database(e))
+            }
+        }
+    }
+
     pub async fn delete(pool: &PgPool, user_id: i32) -> Result<(), UserError> {
         let result = sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
             .execute(pool)
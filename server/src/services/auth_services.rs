@@ -1,16 +1,34 @@
 use crate::{
     errors::auth_errors::AuthError,
-    models::auth_models::{
-        Claims, LoginRequest, RefreshRequest, RefreshToken, TokenPair,
+    models::{
+        auth_models::{
+            Claims, LoginRequest, LoginResponse, RefreshRequest, RefreshToken,
+            TokenPair,
+        },
+        login_attempts_models::LoginAccount,
+        security_events_models::SecurityEventType,
+        sessions_models::{ClientInfo, Session},
     },
     repositories::{
-        auth_repisitory::AuthRepository, users_repository::UserRepository,
+        auth_repisitory::AuthRepository, mfa_repository::MfaRepository,
+        roles_repository::RoleRepository,
+        security_events_repository::SecurityEventRepository,
+        users_repository::UserRepository,
+    },
+    services::{
+        login_attempts_service::LoginAttemptService, mfa_services::MfaService,
+        token_denylist_service::TokenDenylist,
+    },
+    utils::{
+        jwt_keys::JwtKeyStore,
+        password_hasher::{PasswordHasher, PasswordVerification},
     },
 };
 use jsonwebtoken::{
-    DecodingKey, EncodingKey, Header, Validation, decode, encode,
+    Validation, decode, decode_header, encode, errors::ErrorKind,
 };
 use sqlx::PgPool;
+use time::OffsetDateTime;
 
 pub struct AuthService;
 
@@ -18,133 +36,267 @@ impl AuthService {
     pub async fn login(
         pool: &PgPool,
         credentials: LoginRequest,
-    ) -> Result<TokenPair, AuthError> {
-        let user_id = Self::authenticate_user(
+        client: ClientInfo,
+    ) -> Result<LoginResponse, AuthError> {
+        let ip_address = client.ip_address.as_deref();
+
+        // Failures count per account, whether it logs in by username or email
+        let (account, username) =
+            match UserRepository::find_by_login(pool, &credentials.username)
+                .await
+            {
+                Ok(user) => (LoginAccount::User(user.id), user.username),
+                Err(_) => (
+                    LoginAccount::unknown(&credentials.username),
+                    credentials.username.clone(),
+                ),
+            };
+
+        LoginAttemptService::check(pool, &account, ip_address).await?;
+
+        let user_id = match Self::authenticate_user(
             pool,
-            &credentials.username,
+            &username,
             &credentials.password,
         )
-        .await?;
+        .await
+        {
+            Ok(user_id) => user_id,
+            Err(e @ AuthError::Authentication(_)) => {
+                LoginAttemptService::record_failure(pool, &account, ip_address)
+                    .await?;
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
 
-        // Generate tokens
-        let token_pair = Self::generate_token_pair(user_id)?;
-        let refresh_token = RefreshToken::new(user_id);
+        // Tokens are issued by MfaService::verify after the second step,
+        // failures stay counted until it passes
+        if MfaRepository::is_enabled(pool, user_id).await? {
+            let challenge = MfaService::create_challenge(pool, user_id).await?;
+            return Ok(LoginResponse::MfaPending(challenge));
+        }
 
-        // Save refresh token in DB
-        AuthRepository::save_refresh_token(pool, &refresh_token).await?;
+        LoginAttemptService::record_success(pool, &account).await?;
 
-        Ok(TokenPair {
-            access_token: token_pair.access_token,
-            refresh_token: refresh_token.token,
-        })
+        // New session
+        let token_pair =
+            Self::issue_token_pair(pool, RefreshToken::new(user_id, client))
+                .await?;
+        Ok(LoginResponse::Tokens(token_pair))
     }
 
     pub async fn refresh(
         pool: &PgPool,
         token_data: RefreshRequest,
+        client: ClientInfo,
     ) -> Result<TokenPair, AuthError> {
-        // Is refresh token valid
-        let user_id = AuthRepository::validate_refresh_token(
-            pool,
-            &token_data.refresh_token,
-        )
-        .await?;
+        let stored =
+            AuthRepository::find_refresh_token(pool, &token_data.refresh_token)
+                .await?;
 
-        // Delete user refresh token
-        AuthRepository::delete_refresh_token(pool, &token_data.refresh_token)
-            .await?;
+        // A rotated token presented again means it was copied: either the
+        // legitimate client or an attacker holds a newer token of the family
+        if stored.rotated_at.is_some() {
+            return Err(Self::revoke_reused_family(pool, &stored).await);
+        }
 
-        // Generate new pairs of tokens
-        let token_pair = Self::generate_token_pair(user_id)?;
-        let new_refresh_token = RefreshToken::new(user_id);
+        // Is refresh token valid
+        if stored.expires_at < OffsetDateTime::now_utc() {
+            log::warn!("Refresh token expired for user {}", stored.user_id);
+            return Err(AuthError::TokenExpired);
+        }
 
-        // Save new refresh token
-        AuthRepository::save_refresh_token(pool, &new_refresh_token).await?;
+        // Lost the race against a concurrent refresh with the same token
+        if !AuthRepository::mark_refresh_token_rotated(pool, &stored.token)
+            .await?
+        {
+            return Err(Self::revoke_reused_family(pool, &stored).await);
+        }
 
-        Ok(TokenPair {
-            access_token: token_pair.access_token,
-            refresh_token: new_refresh_token.token,
-        })
+        // Generate new pairs of tokens in the same session
+        Self::issue_token_pair(pool, stored.rotate(client)).await
     }
 
     pub async fn logout(
         pool: &PgPool,
         token_data: RefreshRequest,
     ) -> Result<(), AuthError> {
-        AuthRepository::delete_refresh_token(pool, &token_data.refresh_token)
+        let stored =
+            AuthRepository::find_refresh_token(pool, &token_data.refresh_token)
+                .await?;
+
+        TokenDenylist::global()
+            .revoke_family(pool, stored.user_id, &stored.family_id)
+            .await?;
+        AuthRepository::revoke_token_family(pool, &stored.family_id).await?;
+        Ok(())
+    }
+
+    async fn revoke_reused_family(
+        pool: &PgPool,
+        stored: &RefreshToken,
+    ) -> AuthError {
+        SecurityEventRepository::record(
+            pool,
+            Some(stored.user_id),
+            SecurityEventType::RefreshTokenReuse,
+            &format!("Refresh token family {} revoked", stored.family_id),
+        )
+        .await;
+
+        if let Err(e) = TokenDenylist::global()
+            .revoke_family(pool, stored.user_id, &stored.family_id)
             .await
+        {
+            return AuthError::Database(e);
+        }
+
+        match AuthRepository::revoke_token_family(pool, &stored.family_id).await
+        {
+            Ok(_) => AuthError::RefreshTokenReused,
+            Err(e) => e,
+        }
+    }
+
+    pub async fn list_sessions(
+        pool: &PgPool,
+        user_id: i32,
+        current_session_id: &str,
+    ) -> Result<Vec<Session>, AuthError> {
+        AuthRepository::list_sessions(pool, user_id, current_session_id).await
+    }
+
+    pub async fn revoke_session(
+        pool: &PgPool,
+        user_id: i32,
+        session_id: &str,
+    ) -> Result<(), AuthError> {
+        TokenDenylist::global()
+            .revoke_family(pool, user_id, session_id)
+            .await?;
+        AuthRepository::revoke_session(pool, user_id, session_id).await
     }
 
+    // Also denylists the access tokens already issued, used whenever a
+    // user must be signed out everywhere (password change, deletion)
+    pub async fn revoke_all_sessions(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<u64, AuthError> {
+        TokenDenylist::global().revoke_user(pool, user_id).await?;
+        AuthRepository::revoke_all_sessions(pool, user_id).await
+    }
+
+    /// Checks the password of the user with `login` as username or email.
     pub async fn authenticate_user(
         pool: &PgPool,
-        username: &str,
+        login: &str,
         password: &str,
     ) -> Result<i32, AuthError> {
-        if username.is_empty() || password.is_empty() {
+        if login.is_empty() || password.is_empty() {
             return Err(AuthError::Authentication(
                 "Username and password are required".to_string(),
             ));
         }
 
-        let user = UserRepository::find_by_username(pool, username)
-            .await
-            .map_err(|e| {
+        let user =
+            UserRepository::find_by_login(pool, login).await.map_err(|e| {
                 AuthError::Authentication(format!(
                     "Authentication failed: {}",
                     e
                 ))
             })?;
 
-        if username == user.username && password == user.password {
-            Ok(user.id)
-        } else {
-            Err(AuthError::Authentication("Invalid credentials".to_string()))
+        match PasswordHasher::verify(password, &user.password).await? {
+            PasswordVerification::Valid => Ok(user.id),
+            PasswordVerification::ValidNeedsRehash => {
+                Self::upgrade_password_hash(pool, user.id, password).await;
+                Ok(user.id)
+            }
+            PasswordVerification::Invalid => Err(AuthError::Authentication(
+                "Invalid credentials".to_string(),
+            )),
         }
     }
 
-    pub fn generate_token_pair(user_id: i32) -> Result<TokenPair, AuthError> {
-        let claims = Claims::new(user_id);
-        let refresh_token = RefreshToken::new(user_id);
+    // Re-hashes legacy plaintext or outdated hashes after a successful
+    // login. Failures are only logged so they never block the login itself.
+    async fn upgrade_password_hash(
+        pool: &PgPool,
+        user_id: i32,
+        password: &str,
+    ) {
+        let result = match PasswordHasher::hash(password).await {
+            Ok(hash) => UserRepository::update_password(pool, user_id, &hash)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(()) => log::info!("Password hash upgraded for user {user_id}"),
+            Err(e) => log::error!(
+                "Failed to upgrade password hash for user {user_id}: {e}"
+            ),
+        }
+    }
 
-        let jwt_access_secret =
-            configs::Config::global().jwt_access_secret.as_bytes();
-        log::debug!("jwt_access_secret: {:?}", jwt_access_secret);
+    // Saves the refresh token and signs an access token for its session,
+    // resolving permissions again so role changes are picked up
+    pub async fn issue_token_pair(
+        pool: &PgPool,
+        refresh_token: RefreshToken,
+    ) -> Result<TokenPair, AuthError> {
+        let permissions =
+            RoleRepository::get_user_permissions(pool, refresh_token.user_id)
+                .await?;
+        let claims = Claims::new(
+            refresh_token.user_id,
+            refresh_token.family_id.clone(),
+            permissions,
+        );
+        let access_token = Self::generate_access_token(&claims)?;
 
-        let access_token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(jwt_access_secret),
-        )
-        .map_err(AuthError::InvalidToken)?;
+        // Save refresh token in DB
+        AuthRepository::save_refresh_token(pool, &refresh_token, &claims)
+            .await?;
 
         Ok(TokenPair { access_token, refresh_token: refresh_token.token })
     }
 
+    pub fn generate_access_token(claims: &Claims) -> Result<String, AuthError> {
+        let keys = JwtKeyStore::global();
+
+        encode(&keys.header(), claims, keys.signing_key())
+            .map_err(AuthError::InvalidToken)
+    }
+
     pub fn validate_access_token(token: &str) -> Result<Claims, AuthError> {
-        let jwt_access_secret =
-            configs::Config::global().jwt_access_secret.as_bytes();
+        let header = decode_header(token)?;
+        let (algorithm, key) = JwtKeyStore::global()
+            .verification_key(header.kid.as_deref())
+            .ok_or_else(|| {
+                log::warn!("Unknown JWT key id: {:?}", header.kid);
+                AuthError::InvalidToken(ErrorKind::InvalidSignature.into())
+            })?;
 
-        decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(jwt_access_secret),
-            &Validation::default(),
-        )
-        .map(|token_data| {
-            log::debug!(
-                "Access token validated for user {}",
-                token_data.claims.sub
-            );
-            token_data.claims
-        })
-        .map_err(|e| match e.kind() {
-            jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
-                log::warn!("Access token expired");
-                AuthError::TokenExpired
-            }
-            _ => {
-                log::warn!("Invalid access token: {}", e);
-                AuthError::InvalidToken(e)
-            }
-        })
+        decode::<Claims>(token, key, &Validation::new(algorithm))
+            .map(|token_data| {
+                log::debug!(
+                    "Access token validated for user {}",
+                    token_data.claims.sub
+                );
+                token_data.claims
+            })
+            .map_err(|e| {
+                if let ErrorKind::ExpiredSignature = e.kind() {
+                    log::warn!("Access token expired");
+                    AuthError::TokenExpired
+                } else {
+                    log::warn!("Invalid access token: {e}");
+                    AuthError::InvalidToken(e)
+                }
+            })
     }
 }
@@ -0,0 +1,337 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    errors::{
+        email_campaign_errors::CampaignError,
+        email_template_errors::EmailTemplateError,
+    },
+    models::{
+        email_campaign_models::{
+            Campaign, CampaignDelivery, CampaignPreview, CampaignStatus,
+            CreateCampaignRequest, RecipientStatus,
+        },
+        email_message_models::EmailMessage,
+        email_template_models::RenderedEmail,
+    },
+    repositories::email_campaign_repository::EmailCampaignRepository,
+    services::email_services::EmailService,
+    utils::{
+        email_templates::EmailTemplates, secret_generator::SecretGenerator,
+        token_hasher::TokenHasher,
+    },
+};
+
+const UNSUBSCRIBE_TOKEN_LENGTH: usize = 48;
+// How long a claimed recipient stays hidden from other workers
+const LEASE: Duration = Duration::minutes(10);
+
+pub struct EmailCampaignService;
+
+impl EmailCampaignService {
+    pub async fn create(
+        pool: &PgPool,
+        created_by: i32,
+        request: &CreateCampaignRequest,
+    ) -> Result<Campaign, CampaignError> {
+        // Template mistakes surface now rather than once per recipient
+        Self::render_source(
+            &request.subject,
+            &request.text_body,
+            request.html_body.as_deref(),
+            None,
+            "sample",
+            "sample@example.com",
+            &Self::unsubscribe_url("sample"),
+        )?;
+
+        EmailCampaignRepository::create(pool, created_by, request).await
+    }
+
+    /// Counts who the campaign would reach if sent now and renders the copy
+    /// the first of them would get.
+    pub async fn preview(
+        pool: &PgPool,
+        campaign_id: i32,
+    ) -> Result<CampaignPreview, CampaignError> {
+        let campaign =
+            EmailCampaignRepository::find_by_id(pool, campaign_id).await?;
+        let segment = campaign.segment();
+
+        let recipients =
+            EmailCampaignRepository::count_segment(pool, &segment).await?;
+        let sample =
+            match EmailCampaignRepository::first_segment_member(pool, &segment)
+                .await?
+            {
+                Some(member) => Some(Self::render(
+                    &campaign,
+                    member.language.as_deref(),
+                    &member.username,
+                    &member.email,
+                    &Self::unsubscribe_url("preview"),
+                )?),
+                None => None,
+            };
+
+        Ok(CampaignPreview { recipients, sample })
+    }
+
+    /// Fixes the recipient list and hands the campaign to the worker.
+    /// Returns the number of recipients.
+    pub async fn start(
+        pool: &PgPool,
+        campaign_id: i32,
+    ) -> Result<u64, CampaignError> {
+        let campaign =
+            EmailCampaignRepository::find_by_id(pool, campaign_id).await?;
+
+        let mut tx = pool.begin().await?;
+        if !EmailCampaignRepository::mark_sending(&mut *tx, campaign_id).await?
+        {
+            return Err(CampaignError::InvalidState(campaign.status));
+        }
+        let recipients = EmailCampaignRepository::add_recipients(
+            &mut *tx,
+            campaign_id,
+            &campaign.segment(),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(recipients)
+    }
+
+    /// Stops a campaign, recipients not reached yet are skipped.
+    pub async fn cancel(
+        pool: &PgPool,
+        campaign_id: i32,
+    ) -> Result<(), CampaignError> {
+        let campaign =
+            EmailCampaignRepository::find_by_id(pool, campaign_id).await?;
+
+        let mut tx = pool.begin().await?;
+        if !EmailCampaignRepository::cancel(&mut *tx, campaign_id).await? {
+            return Err(CampaignError::InvalidState(campaign.status));
+        }
+        let skipped =
+            EmailCampaignRepository::skip_pending(&mut *tx, campaign_id)
+                .await?;
+        tx.commit().await?;
+
+        log::info!("Campaign #{campaign_id} cancelled, {skipped} skipped");
+        Ok(())
+    }
+
+    pub async fn unsubscribe(
+        pool: &PgPool,
+        token: &str,
+    ) -> Result<(), CampaignError> {
+        EmailCampaignRepository::unsubscribe(pool, &TokenHasher::hash(token))
+            .await?
+            .map(|_| ())
+            .ok_or(CampaignError::InvalidUnsubscribeToken)
+    }
+
+    /// Background worker sending campaigns. Every interval it delivers at
+    /// most one batch across all running campaigns, oldest first, which
+    /// keeps the sending rate under what the relay accepts.
+    pub async fn run_worker(
+        pool: PgPool,
+        email_service: Arc<dyn EmailService>,
+    ) {
+        let interval = configs::Config::global().email_campaign_batch_interval;
+        let mut ticker =
+            tokio::time::interval(std::time::Duration::from_secs(interval));
+
+        loop {
+            ticker.tick().await;
+
+            if let Err(e) = Self::process_batch(&pool, &*email_service).await {
+                log::error!("Email campaign worker failed: {e}");
+            }
+        }
+    }
+
+    async fn process_batch(
+        pool: &PgPool,
+        email_service: &dyn EmailService,
+    ) -> Result<(), CampaignError> {
+        let mut budget = configs::Config::global().email_campaign_batch_size;
+        let campaigns = EmailCampaignRepository::find_by_status(
+            pool,
+            CampaignStatus::Sending,
+        )
+        .await?;
+
+        for campaign in campaigns {
+            if budget > 0 {
+                let deliveries = EmailCampaignRepository::claim_batch(
+                    pool,
+                    campaign.id,
+                    budget,
+                    OffsetDateTime::now_utc() + LEASE,
+                )
+                .await?;
+                budget -= i64::try_from(deliveries.len()).unwrap_or(budget);
+
+                for delivery in &deliveries {
+                    Self::deliver(pool, email_service, &campaign, delivery)
+                        .await?;
+                }
+            }
+
+            EmailCampaignRepository::finish_if_done(pool, campaign.id).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn deliver(
+        pool: &PgPool,
+        email_service: &dyn EmailService,
+        campaign: &Campaign,
+        delivery: &CampaignDelivery,
+    ) -> Result<(), CampaignError> {
+        // Opted out after the campaign was started, or it was cancelled
+        // after this batch was claimed
+        if delivery.email_opt_out
+            || !EmailCampaignRepository::is_sending(pool, campaign.id).await?
+        {
+            return EmailCampaignRepository::mark_recipient(
+                pool,
+                campaign.id,
+                delivery.user_id,
+                RecipientStatus::Skipped,
+                None,
+                None,
+            )
+            .await;
+        }
+
+        let token = SecretGenerator::generate_alphanumeric_code(
+            UNSUBSCRIBE_TOKEN_LENGTH,
+        );
+        let unsubscribe_url = Self::unsubscribe_url(&token);
+
+        let result = match Self::render(
+            campaign,
+            delivery.language.as_deref(),
+            &delivery.username,
+            &delivery.email,
+            &unsubscribe_url,
+        ) {
+            Ok(rendered) => {
+                // One-click unsubscribe from the mail client (RFC 8058)
+                let mut message = EmailMessage::new(rendered.subject)
+                    .to(&delivery.email)
+                    .text_body(rendered.text_body)
+                    .header("List-Unsubscribe", format!("<{unsubscribe_url}>"))
+                    .header(
+                        "List-Unsubscribe-Post",
+                        "List-Unsubscribe=One-Click",
+                    );
+                message.html_body = rendered.html_body;
+
+                email_service.send(&message).await.map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(()) => {
+                // The email is out, a failed update must not stop the rest
+                // of the batch
+                if let Err(e) = EmailCampaignRepository::mark_recipient(
+                    pool,
+                    campaign.id,
+                    delivery.user_id,
+                    RecipientStatus::Sent,
+                    None,
+                    Some(&TokenHasher::hash(&token)),
+                )
+                .await
+                {
+                    log::error!(
+                        "Campaign #{} to {} sent but not recorded: {e}",
+                        campaign.id,
+                        delivery.email
+                    );
+                }
+                Ok(())
+            }
+            Err(error) => {
+                log::warn!(
+                    "Campaign #{} to {} failed: {error}",
+                    campaign.id,
+                    delivery.email
+                );
+                EmailCampaignRepository::mark_recipient(
+                    pool,
+                    campaign.id,
+                    delivery.user_id,
+                    RecipientStatus::Failed,
+                    Some(&error),
+                    None,
+                )
+                .await
+            }
+        }
+    }
+
+    fn render(
+        campaign: &Campaign,
+        language: Option<&str>,
+        username: &str,
+        email: &str,
+        unsubscribe_url: &str,
+    ) -> Result<RenderedEmail, CampaignError> {
+        Self::render_source(
+            &campaign.subject,
+            &campaign.text_body,
+            campaign.html_body.as_deref(),
+            language,
+            username,
+            email,
+            unsubscribe_url,
+        )
+    }
+
+    // Errors in the sources are the author's, reported as bad input
+    fn render_source(
+        subject: &str,
+        text_body: &str,
+        html_body: Option<&str>,
+        language: Option<&str>,
+        username: &str,
+        email: &str,
+        unsubscribe_url: &str,
+    ) -> Result<RenderedEmail, CampaignError> {
+        EmailTemplates::global()
+            .render_source(
+                subject,
+                text_body,
+                html_body,
+                language,
+                json!({
+                    "username": username,
+                    "email": email,
+                    "unsubscribe_url": unsubscribe_url,
+                }),
+            )
+            .map_err(|e| match e {
+                EmailTemplateError::Render(e) => {
+                    CampaignError::InvalidTemplate(format!("{e:#}"))
+                }
+                other => CampaignError::Template(other),
+            })
+    }
+
+    fn unsubscribe_url(token: &str) -> String {
+        let base = &configs::Config::global().email_unsubscribe_url;
+        format!("{base}?token={token}")
+    }
+}
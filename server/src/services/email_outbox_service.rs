@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    errors::email_errors::EmailError,
+    models::{
+        email_message_models::EmailMessage, email_outbox_models::OutboxEmail,
+    },
+    repositories::email_outbox_repository::EmailOutboxRepository,
+    services::email_services::EmailService,
+};
+
+// Emails claimed per round trip
+const BATCH_SIZE: i64 = 20;
+// How long a claimed email stays hidden from other workers
+const LEASE: Duration = Duration::minutes(10);
+// Upper bound of the retry backoff
+const MAX_RETRY_DELAY: Duration = Duration::hours(6);
+
+pub struct EmailOutboxService;
+
+impl EmailOutboxService {
+    /// Background worker delivering queued emails. Polls on the configured
+    /// interval and drains the queue one batch at a time.
+    pub async fn run_worker(
+        pool: PgPool,
+        email_service: Arc<dyn EmailService>,
+    ) {
+        let interval = configs::Config::global().email_outbox_poll_interval;
+        let mut ticker =
+            tokio::time::interval(std::time::Duration::from_secs(interval));
+
+        loop {
+            ticker.tick().await;
+
+            loop {
+                match Self::process_batch(&pool, &*email_service).await {
+                    Ok(claimed) if claimed == BATCH_SIZE => {}
+                    Ok(_) => break,
+                    Err(e) => {
+                        log::error!("Email outbox worker failed: {e}");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns how many emails were claimed
+    async fn process_batch(
+        pool: &PgPool,
+        email_service: &dyn EmailService,
+    ) -> Result<i64, EmailError> {
+        let now = OffsetDateTime::now_utc();
+        let emails =
+            EmailOutboxRepository::claim_due(pool, BATCH_SIZE, now + LEASE)
+                .await?;
+        let claimed = i64::try_from(emails.len()).unwrap_or(BATCH_SIZE);
+
+        for email in emails {
+            Self::deliver(pool, email_service, &email).await?;
+        }
+
+        Ok(claimed)
+    }
+
+    async fn deliver(
+        pool: &PgPool,
+        email_service: &dyn EmailService,
+        email: &OutboxEmail,
+    ) -> Result<(), EmailError> {
+        // Automated mail, keeps out-of-office replies away (RFC 3834)
+        let mut message = EmailMessage::new(&email.subject)
+            .to(&email.recipient)
+            .text_body(&email.text_body)
+            .header("Auto-Submitted", "auto-generated");
+        message.html_body.clone_from(&email.html_body);
+
+        let result = email_service.send(&message).await;
+
+        match result {
+            Ok(()) => EmailOutboxRepository::mark_sent(pool, email.id).await,
+            Err(e) => {
+                let max_attempts =
+                    configs::Config::global().email_outbox_max_attempts;
+                let retry_at = (email.attempts < max_attempts).then(|| {
+                    OffsetDateTime::now_utc() + Self::backoff(email.attempts)
+                });
+
+                if retry_at.is_none() {
+                    log::error!(
+                        "Giving up on email #{} to {} after {} attempts",
+                        email.id,
+                        email.recipient,
+                        email.attempts
+                    );
+                }
+
+                EmailOutboxRepository::mark_failed(
+                    pool,
+                    email.id,
+                    &e.to_string(),
+                    retry_at,
+                )
+                .await
+            }
+        }
+    }
+
+    // Base delay doubled after every failed attempt, up to the cap
+    fn backoff(attempts: i32) -> Duration {
+        let base = configs::Config::global().email_outbox_retry_base;
+        let exponent = u32::try_from(attempts - 1).unwrap_or(0);
+        let seconds = 1_i64
+            .checked_shl(exponent)
+            .and_then(|factor| factor.checked_mul(base))
+            .unwrap_or(i64::MAX)
+            .min(MAX_RETRY_DELAY.whole_seconds());
+
+        Duration::seconds(seconds)
+    }
+}
@@ -0,0 +1,195 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    errors::email_errors::EmailError,
+    models::{
+        email_message_models::EmailMessage,
+        email_models::{EmailStatus, SendEmailRequest},
+    },
+    repositories::email_log_repository::EmailLogRepository,
+    services::email_services::{EmailService, header_name},
+};
+
+pub struct EmailRelayService;
+
+impl EmailRelayService {
+    // Sends an email on behalf of an API user: recipient rules and the
+    // daily quota are checked first and every recipient of every attempt
+    // ends up in email_log, logged as pending while the send runs
+    pub async fn send(
+        pool: &PgPool,
+        email_service: &dyn EmailService,
+        sender_id: i32,
+        request: &SendEmailRequest,
+    ) -> Result<(), EmailError> {
+        let message = Self::build_message(request)?;
+        let recipients: Vec<String> = message
+            .recipients()
+            .map(|address| address.trim().to_lowercase())
+            .collect();
+
+        if let Some(recipient) = recipients
+            .iter()
+            .find(|recipient| !Self::is_recipient_allowed(recipient))
+        {
+            Self::record(
+                pool,
+                sender_id,
+                &recipients,
+                &request.subject,
+                EmailStatus::Rejected,
+                Some("Recipient not allowed"),
+            )
+            .await;
+            return Err(EmailError::RecipientNotAllowed(recipient.clone()));
+        }
+
+        // Each recipient counts against the quota
+        let quota = configs::Config::global().email_daily_quota;
+        let since = OffsetDateTime::now_utc() - Duration::days(1);
+        let Some(reserved) = EmailLogRepository::reserve(
+            pool,
+            sender_id,
+            &recipients,
+            &request.subject,
+            quota,
+            since,
+        )
+        .await?
+        else {
+            Self::record(
+                pool,
+                sender_id,
+                &recipients,
+                &request.subject,
+                EmailStatus::Rejected,
+                Some("Daily quota exceeded"),
+            )
+            .await;
+            return Err(EmailError::QuotaExceeded(quota));
+        };
+
+        let result = email_service.send(&message).await;
+
+        let (status, error) = match &result {
+            Ok(()) => (EmailStatus::Sent, None),
+            Err(e) => (EmailStatus::Failed, Some(e.to_string())),
+        };
+        EmailLogRepository::finish(pool, &reserved, status, error.as_deref())
+            .await;
+
+        result
+    }
+
+    fn build_message(
+        request: &SendEmailRequest,
+    ) -> Result<EmailMessage, EmailError> {
+        let mut message = EmailMessage::new(&request.subject);
+        for address in &request.to {
+            message = message.to(address);
+        }
+        for address in &request.cc {
+            message = message.cc(address);
+        }
+        for address in &request.bcc {
+            message = message.bcc(address);
+        }
+        if let Some(address) = &request.reply_to {
+            message = message.reply_to(address);
+        }
+
+        // Refused before the send counts against the quota
+        for (name, value) in &request.headers {
+            header_name(name)?;
+            message = message.header(name, value);
+        }
+
+        message = if request.is_html.unwrap_or(false) {
+            message.html_body(&request.body)
+        } else {
+            message.text_body(&request.body)
+        };
+
+        let max_size = configs::Config::global().email_attachments_max_size;
+        let mut total_size = 0;
+        for attachment in &request.attachments {
+            let content =
+                STANDARD.decode(&attachment.content).map_err(|_| {
+                    EmailError::InvalidAttachment(format!(
+                        "{} is not valid base64",
+                        attachment.filename
+                    ))
+                })?;
+
+            total_size += content.len();
+            if total_size > max_size {
+                return Err(EmailError::AttachmentsTooLarge(max_size));
+            }
+
+            message = match &attachment.content_id {
+                Some(content_id) => message.inline_image(
+                    content_id,
+                    &attachment.content_type,
+                    content,
+                ),
+                None => message.attachment(
+                    &attachment.filename,
+                    &attachment.content_type,
+                    content,
+                ),
+            };
+        }
+
+        Ok(message)
+    }
+
+    async fn record(
+        pool: &PgPool,
+        sender_id: i32,
+        recipients: &[String],
+        subject: &str,
+        status: EmailStatus,
+        error: Option<&str>,
+    ) {
+        for recipient in recipients {
+            EmailLogRepository::record(
+                pool,
+                Some(sender_id),
+                recipient,
+                subject,
+                status,
+                error,
+            )
+            .await;
+        }
+    }
+
+    // Deny rules win over allow rules, an empty allow list allows everyone.
+    // Rules containing '@' match a full address, others match a domain
+    // and its subdomains.
+    fn is_recipient_allowed(recipient: &str) -> bool {
+        let config = configs::Config::global();
+        let matches = |rules: &[String]| {
+            rules.iter().any(|rule| Self::matches_rule(recipient, rule))
+        };
+
+        !matches(&config.email_recipient_deny)
+            && (config.email_recipient_allow.is_empty()
+                || matches(&config.email_recipient_allow))
+    }
+
+    fn matches_rule(recipient: &str, rule: &str) -> bool {
+        if rule.contains('@') {
+            return recipient == rule;
+        }
+
+        recipient.rsplit_once('@').is_some_and(|(_, domain)| {
+            domain == rule
+                || domain
+                    .strip_suffix(rule)
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
@@ -1,39 +1,42 @@
 use async_trait::async_trait;
+use std::sync::Mutex;
+
 use lettre::{
-    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
-    message::{MessageBuilder, MultiPart, SinglePart},
+    AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message,
+    Tokio1Executor,
+    message::{
+        Attachment, Mailbox, MessageBuilder, MultiPart, SinglePart,
+        header::{ContentType, HeaderName, HeaderValue},
+    },
     transport::smtp::authentication::Credentials,
 };
 
-use crate::errors::email_errors::EmailError;
+use crate::{
+    errors::email_errors::EmailError,
+    models::email_message_models::{EmailAttachment, EmailMessage},
+    utils::dkim::DkimSigner,
+};
+
+// Set from the message fields or by the transport, a custom header must
+// not replace them
+const RESERVED_HEADERS: [&str; 12] = [
+    "from",
+    "sender",
+    "to",
+    "cc",
+    "bcc",
+    "reply-to",
+    "subject",
+    "date",
+    "mime-version",
+    "content-type",
+    "message-id",
+    "dkim-signature",
+];
 
 #[async_trait]
 pub trait EmailService: Send + Sync {
-    async fn send_email(
-        &self,
-        to: &str,
-        subject: &str,
-        text_body: &str,
-        html_body: Option<&str>,
-    ) -> Result<(), EmailError>;
-
-    async fn send_text_email(
-        &self,
-        to: &str,
-        subject: &str,
-        body: &str,
-    ) -> Result<(), EmailError> {
-        self.send_email(to, subject, body, None).await
-    }
-
-    async fn send_html_email(
-        &self,
-        to: &str,
-        subject: &str,
-        html_body: &str,
-    ) -> Result<(), EmailError> {
-        self.send_email(to, subject, "", Some(html_body)).await
-    }
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError>;
 }
 
 pub struct LettreEmailService {
@@ -47,6 +50,12 @@ impl LettreEmailService {
         let email_host = configs::Config::global().email_host.clone();
         let email_port = configs::Config::global().email_port;
 
+        if email_host.is_empty() {
+            return Err(EmailError::Configuration(
+                "EMAIL_HOST is required for the smtp transport".to_string(),
+            ));
+        }
+
         let creds = Credentials::new(email_user, email_password);
 
         // Build the SMTP transport
@@ -64,87 +73,310 @@ impl LettreEmailService {
 
         Ok(Self { transporter })
     }
+}
 
-    fn validate_email_address(address: &str) -> Result<(), EmailError> {
-        if address.is_empty() {
-            return Err(EmailError::EmailValidation(
-                "Email address cannot be empty".to_string(),
-            ));
-        }
+#[async_trait]
+impl EmailService for LettreEmailService {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        let email = build_message(message)?;
 
-        if !address.contains('@') {
-            return Err(EmailError::EmailValidation(format!(
-                "Invalid email format: {}",
-                address
-            )));
-        }
+        self.transporter.send(email).await.map_err(EmailError::Smtp)?;
 
+        log::info!("Email successfully sent to {}", message.to.join(", "));
         Ok(())
     }
 }
 
+/// Writes every message as an `.eml` file into a directory instead of
+/// sending it, for running the email flows without an SMTP server.
+pub struct FileEmailService {
+    transporter: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileEmailService {
+    pub fn new(dir: &str) -> Result<Self, EmailError> {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            EmailError::Configuration(format!(
+                "Failed to create email directory {dir}: {e}"
+            ))
+        })?;
+
+        Ok(Self { transporter: AsyncFileTransport::new(dir) })
+    }
+}
+
 #[async_trait]
-impl EmailService for LettreEmailService {
-    async fn send_email(
-        &self,
-        to: &str,
-        subject: &str,
-        text_body: &str,
-        html_body: Option<&str>,
-    ) -> Result<(), EmailError> {
-        let email_from = configs::Config::global().email_from.clone();
-
-        Self::validate_email_address(to)?;
-        Self::validate_email_address(&email_from)?;
-
-        if subject.trim().is_empty() {
-            return Err(EmailError::EmptySubject);
-        }
+impl EmailService for FileEmailService {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        let email = build_message(message)?;
 
-        if text_body.trim().is_empty()
-            && html_body.map(|h| h.trim().is_empty()).unwrap_or(true)
-        {
-            return Err(EmailError::EmptyBody);
-        }
+        let id = self
+            .transporter
+            .send(email)
+            .await
+            .map_err(|e| EmailError::SendFailed(e.to_string()))?;
 
-        let email = {
-            let mut builder = MessageBuilder::new();
-
-            let from_address =
-                email_from.parse().map_err(EmailError::AddressParse)?;
-            builder = builder.from(from_address);
-
-            let to_address = to.parse().map_err(EmailError::AddressParse)?;
-            builder = builder.to(to_address);
-
-            builder = builder.subject(subject);
-
-            //Body of message building
-            if let Some(html) = html_body {
-                builder.multipart(
-                    MultiPart::alternative()
-                        .singlepart(
-                            SinglePart::builder()
-                                .header(lettre::message::header::ContentType::TEXT_PLAIN)
-                                .body(text_body.to_string()),
-                        )
-                        .singlepart(
-                            SinglePart::builder()
-                                .header(lettre::message::header::ContentType::TEXT_HTML)
-                                .body(html.to_string()),
-                        ),
-                )
-                .map_err(EmailError::MessageBuild)?
-            } else {
-                builder
-                    .body(text_body.to_string())
-                    .map_err(EmailError::MessageBuild)?
-            }
-        };
+        log::info!("Email to {} written to {id}.eml", message.to.join(", "));
+        Ok(())
+    }
+}
 
-        self.transporter.send(email).await.map_err(EmailError::Smtp)?;
+/// Keeps sent messages in memory so tests can assert on them. Messages go
+/// through the same checks as the real transports.
+#[derive(Default)]
+pub struct InMemoryEmailService {
+    messages: Mutex<Vec<EmailMessage>>,
+}
 
-        log::info!("Email successfully sent to {}", to);
+impl InMemoryEmailService {
+    #[cfg(test)]
+    pub fn messages(&self) -> Vec<EmailMessage> {
+        self.messages.lock().expect("Email capture lock poisoned").clone()
+    }
+}
+
+#[async_trait]
+impl EmailService for InMemoryEmailService {
+    async fn send(&self, message: &EmailMessage) -> Result<(), EmailError> {
+        build_message(message)?;
+
+        self.messages
+            .lock()
+            .expect("Email capture lock poisoned")
+            .push(message.clone());
+
+        log::info!(
+            "Email to {} captured in memory: {}",
+            message.to.join(", "),
+            message.subject
+        );
         Ok(())
     }
 }
+
+fn validate_email_address(address: &str) -> Result<(), EmailError> {
+    if address.is_empty() {
+        return Err(EmailError::EmailValidation(
+            "Email address cannot be empty".to_string(),
+        ));
+    }
+
+    if !address.contains('@') {
+        return Err(EmailError::EmailValidation(format!(
+            "Invalid email format: {address}"
+        )));
+    }
+
+    Ok(())
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, EmailError> {
+    validate_email_address(address)?;
+    address.parse().map_err(EmailError::AddressParse)
+}
+
+fn build_message(message: &EmailMessage) -> Result<Message, EmailError> {
+    let email_from = configs::Config::global().email_from.clone();
+
+    if message.to.is_empty() {
+        return Err(EmailError::EmailValidation(
+            "At least one recipient is required".to_string(),
+        ));
+    }
+
+    if message.subject.trim().is_empty() {
+        return Err(EmailError::EmptySubject);
+    }
+
+    if message.text_body.trim().is_empty()
+        && message.html_body.as_deref().is_none_or(|h| h.trim().is_empty())
+    {
+        return Err(EmailError::EmptyBody);
+    }
+
+    let mut builder = MessageBuilder::new().from(parse_mailbox(&email_from)?);
+
+    for address in &message.to {
+        builder = builder.to(parse_mailbox(address)?);
+    }
+    for address in &message.cc {
+        builder = builder.cc(parse_mailbox(address)?);
+    }
+    for address in &message.bcc {
+        builder = builder.bcc(parse_mailbox(address)?);
+    }
+    if let Some(address) = &message.reply_to {
+        builder = builder.reply_to(parse_mailbox(address)?);
+    }
+
+    builder = builder.subject(&message.subject);
+
+    let mut email = match build_body(message)? {
+        Body::Single(part) => builder.singlepart(part),
+        Body::Multi(part) => builder.multipart(part),
+    }
+    .map_err(EmailError::MessageBuild)?;
+
+    for (name, value) in &message.headers {
+        let name = header_name(name)?;
+        email.headers_mut().insert_raw(HeaderValue::new(name, value.clone()));
+    }
+
+    // Last, the signature covers the final headers
+    if let Some(signer) = DkimSigner::global() {
+        signer.sign(&mut email);
+    }
+
+    Ok(email)
+}
+
+/// Name of a custom header, rejected if it is malformed or one the message
+/// fields or the transport set.
+pub fn header_name(name: &str) -> Result<HeaderName, EmailError> {
+    if RESERVED_HEADERS.contains(&name.to_lowercase().as_str()) {
+        return Err(EmailError::InvalidHeader(name.to_string()));
+    }
+    HeaderName::new_from_ascii(name.to_string())
+        .map_err(|_| EmailError::InvalidHeader(name.to_string()))
+}
+
+enum Body {
+    Single(SinglePart),
+    Multi(MultiPart),
+}
+
+// multipart/mixed [
+//     multipart/related [ multipart/alternative [ text, html ], images ],
+//     attachments
+// ]
+// with every level left out when it would hold a single part
+fn build_body(message: &EmailMessage) -> Result<Body, EmailError> {
+    let text = SinglePart::builder()
+        .header(ContentType::TEXT_PLAIN)
+        .body(message.text_body.clone());
+
+    let mut body = match &message.html_body {
+        Some(html) => Body::Multi(
+            MultiPart::alternative().singlepart(text).singlepart(
+                SinglePart::builder()
+                    .header(ContentType::TEXT_HTML)
+                    .body(html.clone()),
+            ),
+        ),
+        None => Body::Single(text),
+    };
+
+    let (inline, attached): (Vec<_>, Vec<_>) = message
+        .attachments
+        .iter()
+        .partition(|attachment| attachment.content_id.is_some());
+
+    if !inline.is_empty() {
+        body = Body::Multi(with_parts(MultiPart::related(), body, &inline)?);
+    }
+    if !attached.is_empty() {
+        body = Body::Multi(with_parts(MultiPart::mixed(), body, &attached)?);
+    }
+
+    Ok(body)
+}
+
+fn with_parts(
+    builder: lettre::message::MultiPartBuilder,
+    body: Body,
+    attachments: &[&EmailAttachment],
+) -> Result<MultiPart, EmailError> {
+    let mut multipart = match body {
+        Body::Single(part) => builder.singlepart(part),
+        Body::Multi(part) => builder.multipart(part),
+    };
+
+    for attachment in attachments {
+        let content_type = ContentType::parse(&attachment.content_type)
+            .map_err(|_| {
+                EmailError::InvalidAttachment(format!(
+                    "Invalid content type of {}",
+                    attachment.filename
+                ))
+            })?;
+
+        let part = match &attachment.content_id {
+            Some(content_id) => Attachment::new_inline(content_id.clone()),
+            None => Attachment::new(attachment.filename.clone()),
+        }
+        .body(attachment.content.clone(), content_type);
+
+        multipart = multipart.singlepart(part);
+    }
+
+    Ok(multipart)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_support;
+
+    fn transport() -> InMemoryEmailService {
+        test_support::init();
+        InMemoryEmailService::default()
+    }
+
+    #[tokio::test]
+    async fn captures_sent_messages() {
+        let transport = transport();
+        let message = EmailMessage::new("Confirm your email")
+            .to("alice@example.com")
+            .bcc("audit@example.com")
+            .text_body("Your code is 123456")
+            .header("Auto-Submitted", "auto-generated");
+
+        transport.send(&message).await.expect("valid message");
+
+        let messages = transport.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].to, ["alice@example.com"]);
+        assert_eq!(messages[0].bcc, ["audit@example.com"]);
+        assert_eq!(messages[0].subject, "Confirm your email");
+        assert_eq!(messages[0].text_body, "Your code is 123456");
+        assert_eq!(
+            messages[0].headers,
+            [("Auto-Submitted".to_string(), "auto-generated".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_messages_are_not_captured() {
+        let transport = transport();
+        let valid = || EmailMessage::new("Hello").to("alice@example.com");
+
+        let rejected = [
+            (EmailMessage::new("Hello").text_body("Hi"), "recipient"),
+            (valid().text_body("Hi").to("not-an-address"), "address"),
+            (valid().text_body("   "), "body"),
+            (
+                EmailMessage::new(" ").to("alice@example.com").text_body("Hi"),
+                "subject",
+            ),
+            (
+                valid().text_body("Hi").header("From", "evil@example.com"),
+                "header",
+            ),
+            (
+                valid().text_body("Hi").header("Message-ID", "<1@example.com>"),
+                "message id",
+            ),
+            (
+                valid().text_body("Hi").header("DKIM-Signature", "v=1"),
+                "signature",
+            ),
+        ];
+
+        for (message, case) in rejected {
+            let result = transport.send(&message).await;
+            assert!(result.is_err(), "{case} accepted");
+        }
+
+        assert!(transport.messages().is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime, format_description::well_known::Rfc2822};
+
+use crate::{
+    errors::invitation_errors::InvitationError,
+    models::{
+        email_outbox_models::NewOutboxEmail,
+        invitation_models::{CreateInvitationRequest, Invitation},
+    },
+    repositories::{
+        email_outbox_repository::EmailOutboxRepository,
+        invitation_repository::InvitationRepository,
+        users_repository::UserRepository,
+    },
+    utils::{
+        email_templates::EmailTemplates, secret_generator::SecretGenerator,
+        token_hasher::TokenHasher,
+    },
+};
+
+const INVITATION_TOKEN_LENGTH: usize = 48;
+
+pub struct InvitationService;
+
+impl InvitationService {
+    /// Invites the email with the role and queues the link. Earlier pending
+    /// invitations of the same email stop working.
+    pub async fn create(
+        pool: &PgPool,
+        invited_by: i32,
+        request: &CreateInvitationRequest,
+    ) -> Result<Invitation, InvitationError> {
+        if UserRepository::is_email_taken(pool, &request.email).await.map_err(
+            |e| {
+                log::error!("User repository error: {e}");
+                InvitationError::Internal
+            },
+        )? {
+            return Err(InvitationError::EmailAlreadyTaken);
+        }
+
+        let token = SecretGenerator::generate_alphanumeric_code(
+            INVITATION_TOKEN_LENGTH,
+        );
+        let expires_in = request
+            .expires_in
+            .unwrap_or(configs::Config::global().invitation_expires);
+        let expires_at =
+            OffsetDateTime::now_utc() + Duration::seconds(expires_in);
+
+        // The email goes out with the invitation or not at all
+        let mut tx = pool.begin().await?;
+
+        InvitationRepository::revoke_pending_for_email(
+            &mut *tx,
+            &request.email,
+        )
+        .await?;
+        let invitation_id = InvitationRepository::create(
+            &mut *tx,
+            invited_by,
+            request,
+            &TokenHasher::hash(&token),
+            expires_at,
+        )
+        .await?
+        .ok_or_else(|| InvitationError::RoleNotFound(request.role.clone()))?;
+
+        EmailOutboxRepository::enqueue(
+            &mut *tx,
+            &Self::invitation_email(request, &token, expires_at)?,
+        )
+        .await?;
+
+        let invitation =
+            InvitationRepository::find_by_id(&mut *tx, invitation_id).await?;
+        tx.commit().await?;
+
+        Ok(invitation)
+    }
+
+    pub async fn revoke(
+        pool: &PgPool,
+        invitation_id: i32,
+    ) -> Result<(), InvitationError> {
+        let invitation =
+            InvitationRepository::find_by_id(pool, invitation_id).await?;
+
+        if !InvitationRepository::revoke(pool, invitation_id).await? {
+            return Err(InvitationError::InvalidState(invitation.status));
+        }
+        Ok(())
+    }
+
+    fn invitation_email(
+        request: &CreateInvitationRequest,
+        token: &str,
+        expires_at: OffsetDateTime,
+    ) -> Result<NewOutboxEmail, InvitationError> {
+        let invitation_url = &configs::Config::global().invitation_url;
+        let expires_at = expires_at
+            .format(&Rfc2822)
+            .unwrap_or_else(|_| expires_at.to_string());
+
+        let rendered = EmailTemplates::global().render(
+            "invitation",
+            request.language.as_deref(),
+            serde_json::json!({
+                "link": format!("{invitation_url}?token={token}"),
+                "role": request.role,
+                "expires_at": expires_at,
+            }),
+        )?;
+
+        Ok(NewOutboxEmail::new(&request.email, rendered))
+    }
+}
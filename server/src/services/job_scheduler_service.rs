@@ -0,0 +1,213 @@
+use std::sync::OnceLock;
+
+use sqlx::{Connection, PgConnection, PgPool};
+use time::OffsetDateTime;
+
+use crate::{
+    errors::job_errors::JobError,
+    models::job_models::{JobRun, JobRunStatus, JobTrigger, ScheduledJob},
+    repositories::{
+        auth_repisitory::AuthRepository, job_repository::JobRepository,
+        temp_registration_repository::TempRegistrationRepository,
+    },
+    utils::cron::CronSchedule,
+};
+
+pub struct JobScheduler {
+    schedules: Vec<(ScheduledJob, CronSchedule)>,
+}
+
+static JOB_SCHEDULER: OnceLock<JobScheduler> = OnceLock::new();
+
+impl JobScheduler {
+    // Schedules come from JOB_*_SCHEDULE, a typo stops the server instead
+    // of silently never running the job
+    pub fn init() -> Result<(), JobError> {
+        let config = configs::Config::global();
+        let now = OffsetDateTime::now_utc();
+
+        let schedules = ScheduledJob::ALL
+            .into_iter()
+            .map(|job| {
+                let expression = match job {
+                    ScheduledJob::PurgeRegistrations => {
+                        &config.job_purge_registrations_schedule
+                    }
+                    ScheduledJob::PurgeRefreshTokens => {
+                        &config.job_purge_refresh_tokens_schedule
+                    }
+                };
+                let schedule = CronSchedule::parse(expression)?;
+                if schedule.next_after(now).is_none() {
+                    return Err(JobError::Config(format!(
+                        "schedule '{expression}' never matches"
+                    )));
+                }
+                Ok((job, schedule))
+            })
+            .collect::<Result<_, JobError>>()?;
+
+        JOB_SCHEDULER.set(JobScheduler { schedules }).map_err(|_| {
+            JobError::Config("Job scheduler already initialized".into())
+        })
+    }
+
+    pub fn global() -> &'static JobScheduler {
+        JOB_SCHEDULER.get().expect(
+            "Job scheduler not initialized. Call JobScheduler::init() first",
+        )
+    }
+
+    /// Background worker running due jobs. Every instance runs it, the
+    /// advisory lock and the stored next run make sure each scheduled run
+    /// happens on one of them only.
+    pub async fn run_worker(pool: PgPool) {
+        let scheduler = Self::global();
+        for (job, schedule) in &scheduler.schedules {
+            if let Err(e) = Self::register(&pool, *job, schedule).await {
+                log::error!("Failed to register job {}: {e}", job.as_ref());
+            }
+        }
+
+        let interval = configs::Config::global().job_scheduler_interval;
+        let mut ticker =
+            tokio::time::interval(std::time::Duration::from_secs(interval));
+
+        loop {
+            ticker.tick().await;
+
+            for (job, schedule) in &scheduler.schedules {
+                let Some(next_run_at) =
+                    schedule.next_after(OffsetDateTime::now_utc())
+                else {
+                    continue;
+                };
+                match Self::execute(
+                    &pool,
+                    *job,
+                    JobTrigger::Schedule,
+                    Some(next_run_at),
+                )
+                .await
+                {
+                    Ok(Some(JobRun { name, error: Some(error), .. })) => {
+                        log::error!("Job {name} failed: {error}");
+                    }
+                    Ok(Some(run)) => log::info!(
+                        "Job {} done, {} rows affected",
+                        run.name,
+                        run.affected.unwrap_or_default()
+                    ),
+                    Ok(None) => {}
+                    Err(e) => log::error!(
+                        "Job scheduler failed on {}: {e}",
+                        job.as_ref()
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Runs the job now, outside of its schedule.
+    pub async fn trigger(
+        pool: &PgPool,
+        name: &str,
+    ) -> Result<JobRun, JobError> {
+        let job = ScheduledJob::from_name(name)
+            .ok_or_else(|| JobError::NotFound(name.to_string()))?;
+
+        Self::execute(pool, job, JobTrigger::Manual, None)
+            .await?
+            .ok_or_else(|| JobError::AlreadyRunning(name.to_string()))
+    }
+
+    async fn register(
+        pool: &PgPool,
+        job: ScheduledJob,
+        schedule: &CronSchedule,
+    ) -> Result<(), JobError> {
+        let next_run_at = schedule
+            .next_after(OffsetDateTime::now_utc())
+            .ok_or_else(|| {
+                JobError::Config(format!("schedule '{schedule}' never matches"))
+            })?;
+
+        JobRepository::register(
+            pool,
+            job.as_ref(),
+            &schedule.to_string(),
+            next_run_at,
+        )
+        .await
+    }
+
+    // Runs the job under its advisory lock, `next_run_at` is set for
+    // scheduled runs. None when another instance holds the lock or the
+    // scheduled job is not due anymore.
+    // A failing job is rolled back to its savepoint and recorded as failed.
+    async fn execute(
+        pool: &PgPool,
+        job: ScheduledJob,
+        trigger: JobTrigger,
+        next_run_at: Option<OffsetDateTime>,
+    ) -> Result<Option<JobRun>, JobError> {
+        let name = job.as_ref();
+        let mut tx = pool.begin().await?;
+
+        if !JobRepository::try_lock(&mut *tx, name).await? {
+            return Ok(None);
+        }
+        if let Some(next_run_at) = next_run_at
+            && !JobRepository::claim_due(&mut *tx, name, next_run_at).await?
+        {
+            return Ok(None);
+        }
+
+        let started_at = OffsetDateTime::now_utc();
+        let mut savepoint = tx.begin().await?;
+        let result = Self::run(&mut savepoint, job).await;
+        let (status, error, affected) = match result {
+            Ok(affected) => {
+                savepoint.commit().await?;
+                (
+                    JobRunStatus::Succeeded,
+                    None,
+                    Some(i64::try_from(affected).unwrap_or(i64::MAX)),
+                )
+            }
+            Err(e) => {
+                savepoint.rollback().await?;
+                (JobRunStatus::Failed, Some(e.to_string()), None)
+            }
+        };
+
+        let run = JobRun {
+            name: name.to_string(),
+            trigger: trigger.as_ref().to_string(),
+            status: status.as_ref().to_string(),
+            error,
+            affected,
+            started_at,
+            finished_at: OffsetDateTime::now_utc(),
+        };
+        JobRepository::record_run(&mut *tx, &run).await?;
+        tx.commit().await?;
+
+        Ok(Some(run))
+    }
+
+    // Returns the number of rows the job affected
+    async fn run(
+        conn: &mut PgConnection,
+        job: ScheduledJob,
+    ) -> Result<u64, JobError> {
+        match job {
+            ScheduledJob::PurgeRegistrations => {
+                Ok(TempRegistrationRepository::cleanup_expired(conn).await?)
+            }
+            ScheduledJob::PurgeRefreshTokens => {
+                Ok(AuthRepository::purge_expired_refresh_tokens(conn).await?)
+            }
+        }
+    }
+}
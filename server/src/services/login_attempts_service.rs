@@ -0,0 +1,248 @@
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime, format_description::well_known::Rfc2822};
+
+use crate::{
+    errors::{
+        auth_errors::AuthError, email_template_errors::EmailTemplateError,
+    },
+    models::{
+        email_outbox_models::NewOutboxEmail,
+        login_attempts_models::{
+            LoginAccount, LoginAttempt, LoginAttemptScope,
+        },
+        security_events_models::SecurityEventType,
+        users_models::User,
+    },
+    repositories::{
+        email_outbox_repository::EmailOutboxRepository,
+        login_attempts_repository::LoginAttemptRepository,
+        security_events_repository::SecurityEventRepository,
+        users_repository::UserRepository,
+    },
+    utils::email_templates::EmailTemplates,
+};
+
+pub struct LoginAttemptService;
+
+impl LoginAttemptService {
+    // Rejects the attempt while the IP or account is locked, or while the
+    // account still has to wait out the delay earned by its last failure.
+    // Unknown logins are tracked the same way so responses do not reveal
+    // which accounts exist.
+    pub async fn check(
+        pool: &PgPool,
+        account: &LoginAccount,
+        ip_address: Option<&str>,
+    ) -> Result<(), AuthError> {
+        let now = OffsetDateTime::now_utc();
+
+        if let Some(ip) = ip_address
+            && let Some(attempt) =
+                LoginAttemptRepository::find(pool, LoginAttemptScope::Ip, ip)
+                    .await?
+            && let Some(locked_until) = Self::locked_until(&attempt, now)
+        {
+            log::warn!("Login from locked IP {ip}");
+            return Err(AuthError::TooManyAttempts(Self::seconds_until(
+                locked_until,
+                now,
+            )));
+        }
+
+        let Some(attempt) =
+            LoginAttemptRepository::find(pool, account.scope(), &account.key())
+                .await?
+        else {
+            return Ok(());
+        };
+
+        if let Some(locked_until) = Self::locked_until(&attempt, now) {
+            log::warn!("Login to locked account, {account}");
+            return Err(AuthError::AccountLocked(Self::seconds_until(
+                locked_until,
+                now,
+            )));
+        }
+
+        let retry_at =
+            attempt.last_failed_at + Self::delay(attempt.failed_attempts);
+        if retry_at > now {
+            log::warn!("Login to {account} attempted before delay expired");
+            return Err(AuthError::TooManyAttempts(Self::seconds_until(
+                retry_at, now,
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub async fn record_failure(
+        pool: &PgPool,
+        account: &LoginAccount,
+        ip_address: Option<&str>,
+    ) -> Result<(), AuthError> {
+        let config = configs::Config::global();
+        let now = OffsetDateTime::now_utc();
+        let lockout = Duration::seconds(config.login_lockout_duration);
+
+        if let Some(ip) = ip_address {
+            let attempt = LoginAttemptRepository::record_failure(
+                pool,
+                LoginAttemptScope::Ip,
+                ip,
+                now - lockout,
+            )
+            .await?;
+
+            if attempt.failed_attempts >= config.login_ip_lockout_threshold {
+                LoginAttemptRepository::lock(
+                    pool,
+                    LoginAttemptScope::Ip,
+                    ip,
+                    now + lockout,
+                )
+                .await?;
+            }
+        }
+
+        let attempt = LoginAttemptRepository::record_failure(
+            pool,
+            account.scope(),
+            &account.key(),
+            now - lockout,
+        )
+        .await?;
+
+        if attempt.failed_attempts >= config.login_lockout_threshold {
+            LoginAttemptRepository::lock(
+                pool,
+                account.scope(),
+                &account.key(),
+                now + lockout,
+            )
+            .await?;
+            if let LoginAccount::User(user_id) = account {
+                Self::notify_locked(pool, *user_id, now + lockout).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn record_success(
+        pool: &PgPool,
+        account: &LoginAccount,
+    ) -> Result<(), AuthError> {
+        LoginAttemptRepository::clear(pool, account.scope(), &[account.key()])
+            .await?;
+        Ok(())
+    }
+
+    /// Lifts a lockout and forgets previous failures, including those
+    /// counted under the user's username or email. Returns `false` if the
+    /// account had no failed logins on record.
+    pub async fn unlock(pool: &PgPool, user: &User) -> Result<bool, AuthError> {
+        let by_id = LoginAttemptRepository::clear(
+            pool,
+            LoginAttemptScope::User,
+            &[user.id.to_string()],
+        )
+        .await?;
+        let by_login = LoginAttemptRepository::clear(
+            pool,
+            LoginAttemptScope::Username,
+            &[
+                user.username.clone(),
+                user.username.to_lowercase(),
+                user.email.clone(),
+                user.email.to_lowercase(),
+            ],
+        )
+        .await?;
+
+        let unlocked = by_id || by_login;
+        if unlocked {
+            log::info!("Login attempts of user {} cleared by admin", user.id);
+        }
+        Ok(unlocked)
+    }
+
+    // Nothing for the first free attempts, then 1s, 2s, 4s... up to the cap
+    fn delay(failed_attempts: i32) -> Duration {
+        let config = configs::Config::global();
+        let Ok(exponent) =
+            u32::try_from(failed_attempts - config.login_free_attempts - 1)
+        else {
+            return Duration::ZERO;
+        };
+
+        let seconds = 1_i64
+            .checked_shl(exponent)
+            .unwrap_or(i64::MAX)
+            .min(config.login_delay_max);
+        Duration::seconds(seconds)
+    }
+
+    fn locked_until(
+        attempt: &LoginAttempt,
+        now: OffsetDateTime,
+    ) -> Option<OffsetDateTime> {
+        attempt.locked_until.filter(|locked_until| *locked_until > now)
+    }
+
+    // Rounded up so retrying right after Retry-After is never too early
+    fn seconds_until(until: OffsetDateTime, now: OffsetDateTime) -> i64 {
+        (until - now).whole_seconds() + 1
+    }
+
+    // Lookup and queueing failures are only logged: the lockout itself is
+    // already in place
+    async fn notify_locked(
+        pool: &PgPool,
+        user_id: i32,
+        locked_until: OffsetDateTime,
+    ) {
+        let Ok(user) = UserRepository::find_by_id(pool, user_id).await else {
+            return;
+        };
+
+        SecurityEventRepository::record(
+            pool,
+            Some(user.id),
+            SecurityEventType::AccountLocked,
+            &format!("Login locked until {locked_until} after failed attempts"),
+        )
+        .await;
+
+        let locked_email = match Self::locked_email(&user, locked_until) {
+            Ok(locked_email) => locked_email,
+            Err(e) => {
+                log::error!("Failed to render account locked email: {e}");
+                return;
+            }
+        };
+
+        if let Err(e) =
+            EmailOutboxRepository::enqueue(pool, &locked_email).await
+        {
+            log::error!("Failed to queue account locked email: {e}");
+        }
+    }
+
+    fn locked_email(
+        user: &User,
+        locked_until: OffsetDateTime,
+    ) -> Result<NewOutboxEmail, EmailTemplateError> {
+        let locked_until = locked_until
+            .format(&Rfc2822)
+            .unwrap_or_else(|_| locked_until.to_string());
+
+        let rendered = EmailTemplates::global().render(
+            "account_locked",
+            user.language.as_deref(),
+            serde_json::json!({ "locked_until": locked_until }),
+        )?;
+
+        Ok(NewOutboxEmail::new(&user.email, rendered))
+    }
+}
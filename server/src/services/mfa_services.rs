@@ -0,0 +1,256 @@
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+use time::OffsetDateTime;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::{
+    errors::auth_errors::AuthError,
+    models::{
+        auth_models::{RefreshToken, TokenPair},
+        login_attempts_models::LoginAccount,
+        mfa_models::{
+            MfaEnrollment, MfaPending, MfaSettings, MfaVerifyRequest,
+            RecoveryCodes,
+        },
+        sessions_models::ClientInfo,
+    },
+    repositories::{
+        mfa_repository::MfaRepository, users_repository::UserRepository,
+    },
+    services::{
+        auth_services::AuthService, login_attempts_service::LoginAttemptService,
+    },
+    utils::{secret_generator::SecretGenerator, token_hasher::TokenHasher},
+};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+const SECRET_LENGTH: usize = 32; // 160 bits of base32
+const RECOVERY_CODES_COUNT: usize = 10;
+const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+pub struct MfaService;
+
+impl MfaService {
+    pub async fn enroll(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<MfaEnrollment, AuthError> {
+        if MfaRepository::is_enabled(pool, user_id).await? {
+            return Err(AuthError::MfaAlreadyEnabled);
+        }
+
+        let user = UserRepository::find_by_id(pool, user_id)
+            .await
+            .map_err(|e| AuthError::Authentication(e.to_string()))?;
+
+        let secret = SecretGenerator::generate_base32_secret(SECRET_LENGTH);
+        let otpauth_uri = Self::totp(&secret, user.username)?.get_url();
+
+        MfaRepository::save_pending_secret(pool, user_id, &secret).await?;
+
+        Ok(MfaEnrollment { secret, otpauth_uri })
+    }
+
+    // Confirms the enrollment with a first code from the authenticator app
+    pub async fn enable(
+        pool: &PgPool,
+        user_id: i32,
+        code: &str,
+    ) -> Result<RecoveryCodes, AuthError> {
+        let mfa = MfaRepository::find_by_user(pool, user_id)
+            .await?
+            .ok_or(AuthError::MfaNotEnrolled)?;
+
+        if mfa.enabled {
+            return Err(AuthError::MfaAlreadyEnabled);
+        }
+
+        let step = Self::matching_step(&mfa.secret, code)?
+            .ok_or(AuthError::InvalidMfaCode)?;
+
+        MfaRepository::enable(pool, user_id, step).await?;
+        Self::generate_recovery_codes(pool, user_id).await
+    }
+
+    pub async fn disable(
+        pool: &PgPool,
+        user_id: i32,
+        code: &str,
+    ) -> Result<(), AuthError> {
+        let mfa = Self::find_enabled(pool, user_id).await?;
+
+        if !Self::check_code(pool, &mfa, code).await? {
+            return Err(AuthError::InvalidMfaCode);
+        }
+
+        MfaRepository::delete(pool, user_id).await
+    }
+
+    pub async fn regenerate_recovery_codes(
+        pool: &PgPool,
+        user_id: i32,
+        code: &str,
+    ) -> Result<RecoveryCodes, AuthError> {
+        let mfa = Self::find_enabled(pool, user_id).await?;
+
+        if !Self::check_code(pool, &mfa, code).await? {
+            return Err(AuthError::InvalidMfaCode);
+        }
+
+        Self::generate_recovery_codes(pool, user_id).await
+    }
+
+    pub async fn create_challenge(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<MfaPending, AuthError> {
+        let challenge = MfaRepository::create_challenge(pool, user_id).await?;
+
+        Ok(MfaPending {
+            mfa_required: true,
+            mfa_token: challenge.token,
+            expires_at: challenge.expires_at,
+        })
+    }
+
+    // Second login step: exchanges the mfa_pending token and a valid code
+    // for a TokenPair
+    pub async fn verify(
+        pool: &PgPool,
+        request: MfaVerifyRequest,
+        client: ClientInfo,
+    ) -> Result<TokenPair, AuthError> {
+        let challenge =
+            MfaRepository::find_challenge(pool, &request.mfa_token).await?;
+
+        if challenge.expires_at < OffsetDateTime::now_utc() {
+            MfaRepository::delete_challenge(pool, &challenge.token).await?;
+            return Err(AuthError::MfaChallengeNotFound);
+        }
+
+        // Wrong codes count towards the account lockout like wrong
+        // passwords, fresh challenges do not bring fresh guesses
+        let account = LoginAccount::User(challenge.user_id);
+        let ip_address = client.ip_address.as_deref();
+        LoginAttemptService::check(pool, &account, ip_address).await?;
+
+        let mfa = Self::find_enabled(pool, challenge.user_id).await?;
+
+        if !Self::check_code(pool, &mfa, &request.code).await? {
+            LoginAttemptService::record_failure(pool, &account, ip_address)
+                .await?;
+            let attempts = MfaRepository::increment_challenge_attempts(
+                pool,
+                &challenge.token,
+            )
+            .await?;
+
+            if attempts >= MAX_CHALLENGE_ATTEMPTS {
+                log::warn!(
+                    "Too many MFA attempts for user {}, challenge dropped",
+                    challenge.user_id
+                );
+                MfaRepository::delete_challenge(pool, &challenge.token).await?;
+            }
+            return Err(AuthError::InvalidMfaCode);
+        }
+
+        // Single use, a concurrent verify with the same token loses here
+        if !MfaRepository::delete_challenge(pool, &challenge.token).await? {
+            return Err(AuthError::MfaChallengeNotFound);
+        }
+
+        LoginAttemptService::record_success(pool, &account).await?;
+
+        AuthService::issue_token_pair(
+            pool,
+            RefreshToken::new(challenge.user_id, client),
+        )
+        .await
+    }
+
+    async fn find_enabled(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<MfaSettings, AuthError> {
+        MfaRepository::find_by_user(pool, user_id)
+            .await?
+            .filter(|mfa| mfa.enabled)
+            .ok_or(AuthError::MfaNotEnrolled)
+    }
+
+    // Accepts a TOTP code or an unused recovery code. Both are consumed.
+    async fn check_code(
+        pool: &PgPool,
+        mfa: &MfaSettings,
+        code: &str,
+    ) -> Result<bool, AuthError> {
+        let code = code.trim();
+
+        if code.len() == TOTP_DIGITS && code.bytes().all(|b| b.is_ascii_digit())
+        {
+            return match Self::matching_step(&mfa.secret, code)? {
+                Some(step) => {
+                    MfaRepository::consume_step(pool, mfa.user_id, step).await
+                }
+                None => Ok(false),
+            };
+        }
+
+        let code_hash = TokenHasher::hash(&code.to_lowercase());
+        MfaRepository::use_recovery_code(pool, mfa.user_id, &code_hash).await
+    }
+
+    async fn generate_recovery_codes(
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<RecoveryCodes, AuthError> {
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODES_COUNT)
+            .map(|_| SecretGenerator::generate_recovery_code())
+            .collect();
+        let code_hashes: Vec<String> =
+            recovery_codes.iter().map(|code| TokenHasher::hash(code)).collect();
+
+        MfaRepository::replace_recovery_codes(pool, user_id, &code_hashes)
+            .await?;
+
+        Ok(RecoveryCodes { recovery_codes })
+    }
+
+    // Time step the code belongs to, allowing one step of clock drift
+    fn matching_step(
+        secret: &str,
+        code: &str,
+    ) -> Result<Option<i64>, AuthError> {
+        let totp = Self::totp(secret, String::new())?;
+        let current = OffsetDateTime::now_utc().unix_timestamp().unsigned_abs()
+            / TOTP_STEP;
+
+        Ok([current - 1, current, current + 1]
+            .into_iter()
+            .find(|step| {
+                let expected = totp.generate(step * TOTP_STEP);
+                bool::from(expected.as_bytes().ct_eq(code.as_bytes()))
+            })
+            .and_then(|step| i64::try_from(step).ok()))
+    }
+
+    fn totp(secret: &str, account_name: String) -> Result<TOTP, AuthError> {
+        let secret =
+            Secret::Encoded(secret.to_string()).to_bytes().map_err(|e| {
+                AuthError::Mfa(format!("Invalid TOTP secret: {e:?}"))
+            })?;
+
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            1,
+            TOTP_STEP,
+            secret,
+            Some(configs::Config::global().mfa_issuer.clone()),
+            account_name,
+        )
+        .map_err(|e| AuthError::Mfa(format!("Invalid TOTP parameters: {e}")))
+    }
+}
@@ -1,4 +1,15 @@
 pub mod auth_services;
+pub mod email_campaign_service;
+pub mod email_outbox_service;
+pub mod email_relay_service;
 pub mod email_services;
+pub mod invitation_service;
+pub mod job_scheduler_service;
+pub mod login_attempts_service;
+pub mod mfa_services;
+pub mod password_reset_service;
+pub mod rate_limit_service;
 pub mod registration_completion_service;
 pub mod temp_registration_service;
+pub mod token_denylist_service;
+pub mod users_service;
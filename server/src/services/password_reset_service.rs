@@ -0,0 +1,134 @@
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    errors::{
+        auth_errors::AuthError, email_template_errors::EmailTemplateError,
+    },
+    models::{
+        email_outbox_models::NewOutboxEmail,
+        password_reset_models::ResetPasswordRequest,
+        security_events_models::SecurityEventType, users_models::User,
+    },
+    repositories::{
+        email_outbox_repository::EmailOutboxRepository,
+        password_reset_repository::PasswordResetRepository,
+        security_events_repository::SecurityEventRepository,
+        users_repository::UserRepository,
+    },
+    services::auth_services::AuthService,
+    utils::{
+        email_templates::EmailTemplates, password_hasher::PasswordHasher,
+        secret_generator::SecretGenerator, token_hasher::TokenHasher,
+    },
+};
+
+const RESET_TOKEN_LENGTH: usize = 48;
+
+pub struct PasswordResetService;
+
+impl PasswordResetService {
+    /// Queues a reset link if the address belongs to a user. Never reports
+    /// whether it does: the caller answers the same way in both cases and
+    /// runs this in the background so the timing does not tell either.
+    pub async fn request_reset(pool: &PgPool, email: &str) {
+        let Ok(user) = UserRepository::find_by_email(pool, email).await else {
+            log::info!("Password reset requested for unknown email");
+            return;
+        };
+
+        let token =
+            SecretGenerator::generate_alphanumeric_code(RESET_TOKEN_LENGTH);
+        let expires_in = configs::Config::global().password_reset_expires;
+        let expires_at =
+            OffsetDateTime::now_utc() + Duration::seconds(expires_in);
+
+        let reset_email = match Self::reset_email(&user, &token, expires_in) {
+            Ok(reset_email) => reset_email,
+            Err(e) => {
+                log::error!("Failed to render password reset email: {e}");
+                return;
+            }
+        };
+
+        if let Err(e) =
+            Self::create_token(pool, user.id, &token, expires_at, &reset_email)
+                .await
+        {
+            log::error!("Failed to create password reset token: {e}");
+        }
+    }
+
+    pub async fn reset_password(
+        pool: &PgPool,
+        request: ResetPasswordRequest,
+    ) -> Result<(), AuthError> {
+        let user_id = PasswordResetRepository::consume(
+            pool,
+            &TokenHasher::hash(&request.token),
+        )
+        .await?;
+
+        let password_hash = PasswordHasher::hash(&request.new_password).await?;
+        UserRepository::update_password(pool, user_id, &password_hash)
+            .await
+            .map_err(|e| AuthError::Authentication(e.to_string()))?;
+
+        PasswordResetRepository::invalidate_all(pool, user_id).await?;
+        AuthService::revoke_all_sessions(pool, user_id).await?;
+
+        SecurityEventRepository::record(
+            pool,
+            Some(user_id),
+            SecurityEventType::PasswordReset,
+            "Password reset via emailed token, all sessions revoked",
+        )
+        .await;
+
+        Ok(())
+    }
+
+    // The token and its email are stored together, a token nobody was told
+    // about is never left behind
+    async fn create_token(
+        pool: &PgPool,
+        user_id: i32,
+        token: &str,
+        expires_at: OffsetDateTime,
+        reset_email: &NewOutboxEmail,
+    ) -> Result<(), AuthError> {
+        let mut tx = pool.begin().await?;
+
+        PasswordResetRepository::create(
+            &mut *tx,
+            user_id,
+            &TokenHasher::hash(token),
+            expires_at,
+        )
+        .await?;
+
+        EmailOutboxRepository::enqueue(&mut *tx, reset_email).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    fn reset_email(
+        user: &User,
+        token: &str,
+        expires_in: i64,
+    ) -> Result<NewOutboxEmail, EmailTemplateError> {
+        let reset_url = &configs::Config::global().password_reset_url;
+
+        let rendered = EmailTemplates::global().render(
+            "password_reset",
+            user.language.as_deref(),
+            serde_json::json!({
+                "link": format!("{reset_url}?token={token}"),
+                "expires_in_minutes": expires_in / 60,
+            }),
+        )?;
+
+        Ok(NewOutboxEmail::new(&user.email, rendered))
+    }
+}
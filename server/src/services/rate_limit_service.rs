@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicI64, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use configs::RateLimitQuota;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    errors::rate_limit_errors::RateLimitError,
+    models::rate_limit_models::{RateLimitStatus, WindowHits},
+    repositories::rate_limit_repository::RateLimitRepository,
+};
+
+// How often expired counters are dropped, in seconds
+const PURGE_INTERVAL: i64 = 60;
+
+/// Backend keeping the per-window hit counters.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Counts a hit for `key` in the window starting at `window_start` (unix
+    /// seconds) and returns it together with the previous window.
+    async fn hit(
+        &self,
+        key: &str,
+        window_start: i64,
+        window: i64,
+    ) -> Result<WindowHits, RateLimitError>;
+}
+
+pub struct RateLimitService;
+
+impl RateLimitService {
+    // Sliding window approximation: the previous window is weighted by how
+    // much of it still overlaps the last `window` seconds
+    pub async fn check(
+        store: &dyn RateLimitStore,
+        key: &str,
+        quota: RateLimitQuota,
+    ) -> Result<RateLimitStatus, RateLimitError> {
+        let window = i64::try_from(quota.window).unwrap_or(i64::MAX);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let window_start = now - now.rem_euclid(window);
+        let elapsed = now - window_start;
+
+        let hits = store.hit(key, window_start, window).await?;
+        let estimate =
+            hits.previous * (window - elapsed) / window + hits.current;
+        let limit = i64::from(quota.limit);
+
+        Ok(RateLimitStatus {
+            limit: quota.limit,
+            remaining: u32::try_from(limit - estimate).unwrap_or(0),
+            reset: u64::try_from(window - elapsed).unwrap_or(0),
+            window: quota.window,
+            exceeded: estimate > limit,
+        })
+    }
+}
+
+struct MemoryWindow {
+    window_start: i64,
+    window: i64,
+    current: i64,
+    previous: i64,
+}
+
+/// Counters kept in process memory, each instance limits on its own.
+#[derive(Default)]
+pub struct InMemoryRateLimitStore {
+    windows: Mutex<HashMap<String, MemoryWindow>>,
+    purged_at: AtomicI64,
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn hit(
+        &self,
+        key: &str,
+        window_start: i64,
+        window: i64,
+    ) -> Result<WindowHits, RateLimitError> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut windows =
+            self.windows.lock().expect("Rate limit store lock poisoned");
+
+        if self.purged_at.load(Ordering::Relaxed) + PURGE_INTERVAL < now {
+            self.purged_at.store(now, Ordering::Relaxed);
+            windows
+                .retain(|_, entry| entry.window_start + 2 * entry.window > now);
+        }
+
+        let entry = windows.entry(key.to_string()).or_insert_with(|| {
+            MemoryWindow { window_start, window, current: 0, previous: 0 }
+        });
+
+        if entry.window_start != window_start {
+            entry.previous = if entry.window_start + window == window_start {
+                entry.current
+            } else {
+                0
+            };
+            entry.current = 0;
+            entry.window_start = window_start;
+            entry.window = window;
+        }
+        entry.current += 1;
+
+        Ok(WindowHits { current: entry.current, previous: entry.previous })
+    }
+}
+
+/// Counters shared by every instance through the `rate_limit_counters` table.
+pub struct PostgresRateLimitStore {
+    pool: PgPool,
+    purged_at: AtomicI64,
+}
+
+impl PostgresRateLimitStore {
+    pub fn new(pool: PgPool) -> Self {
+        PostgresRateLimitStore { pool, purged_at: AtomicI64::new(0) }
+    }
+
+    // Purge failures are only logged, stale rows are retried next time
+    async fn purge_expired(&self) {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let purged_at = self.purged_at.load(Ordering::Relaxed);
+        if purged_at + PURGE_INTERVAL > now
+            || self
+                .purged_at
+                .compare_exchange(
+                    purged_at,
+                    now,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return;
+        }
+
+        if let Err(e) = RateLimitRepository::purge_expired(&self.pool).await {
+            log::error!("Failed to purge expired rate limit counters: {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PostgresRateLimitStore {
+    async fn hit(
+        &self,
+        key: &str,
+        window_start: i64,
+        window: i64,
+    ) -> Result<WindowHits, RateLimitError> {
+        self.purge_expired().await;
+
+        // Kept until it can no longer count as the previous window
+        let expires_at = OffsetDateTime::from_unix_timestamp(window_start)
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+            + Duration::seconds(window.saturating_mul(2));
+
+        RateLimitRepository::hit(
+            &self.pool,
+            key,
+            window_start,
+            window,
+            expires_at,
+        )
+        .await
+    }
+}
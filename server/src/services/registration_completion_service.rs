@@ -1,13 +1,28 @@
 use crate::{
-    errors::temp_registration_errors::TempRegistrationError,
-    models::{temp_registration::TempRegistration, users_models::CreateUser},
+    errors::{
+        temp_registration_errors::TempRegistrationError,
+        users_errors::UserError,
+    },
+    models::{
+        invitation_models::AcceptInvitation,
+        temp_registration::TempRegistration,
+        users_models::{CreateUser, User},
+    },
     repositories::{
+        invitation_repository::InvitationRepository,
+        roles_repository::RoleRepository,
         temp_registration_repository::TempRegistrationRepository,
         users_repository::UserRepository,
     },
-    utils::secret_generator::SecretGenerator,
+    services::temp_registration_service::TempRegistrationService,
+    utils::{
+        password_hasher::{PasswordHasher, PasswordVerification},
+        secret_generator::SecretGenerator,
+        token_hasher::TokenHasher,
+        username_policy::UsernameRejection,
+    },
 };
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 
 pub struct RegistrationCompletionService;
 
@@ -17,27 +32,102 @@ impl RegistrationCompletionService {
         email: String,
         secret_key: String,
     ) -> Result<String, TempRegistrationError> {
+        TempRegistrationService::check_policy(&email)?;
+
+        // The user is created and the registration removed together, a
+        // failure in between leaves neither a user nor a replayable code
+        let mut tx = pool.begin().await?;
+
         let temp_registration =
-            Self::validate_registration(pool, &email, &secret_key).await?;
+            match Self::validate_registration(&mut tx, &email, &secret_key)
+                .await
+            {
+                Ok(temp_registration) => temp_registration,
+                Err(e) => {
+                    // Keeps the counted attempt or the dropped registration
+                    tx.commit().await?;
+                    return Err(e);
+                }
+            };
 
         let username =
-            Self::create_user_from_temp(pool, temp_registration).await?;
+            Self::create_user_from_temp(&mut tx, temp_registration).await?;
+
+        Self::cleanup_temp_data(&mut tx, &email).await?;
 
-        Self::cleanup_temp_data(pool, &email, &secret_key).await?;
+        tx.commit().await?;
 
         Ok(username)
     }
 
-    async fn validate_registration(
+    /// Creates the invited user with the invitation's role. The address is
+    /// verified by the link, so there is no confirmation code. Works under
+    /// every registration policy.
+    pub async fn complete_invitation(
         pool: &PgPool,
+        acceptance: AcceptInvitation,
+    ) -> Result<String, TempRegistrationError> {
+        // Usernames claimed by pending registrations stay theirs
+        if let Some(UsernameRejection::Taken) =
+            TempRegistrationService::username_rejection(
+                pool,
+                &acceptance.username,
+                None,
+            )
+            .await?
+        {
+            return Err(TempRegistrationError::UsernameTaken);
+        }
+
+        let password = PasswordHasher::hash(&acceptance.password).await?;
+
+        // Locks the invitation, a second acceptance waits and then finds it
+        // accepted
+        let mut tx = pool.begin().await?;
+
+        let invitation = InvitationRepository::find_pending_by_token(
+            &mut *tx,
+            &TokenHasher::hash(&acceptance.token),
+        )
+        .await?
+        .ok_or(TempRegistrationError::InvalidInvitation)?;
+
+        let user = Self::create_user(
+            &mut tx,
+            CreateUser {
+                username: acceptance.username,
+                password,
+                email: invitation.email,
+                language: acceptance.language.or(invitation.language),
+            },
+        )
+        .await?;
+
+        RoleRepository::assign_role_id(&mut *tx, user.id, invitation.role_id)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to assign invited role: {e}");
+                TempRegistrationError::Internal
+            })?;
+        InvitationRepository::mark_accepted(&mut *tx, invitation.id, user.id)
+            .await?;
+        // An open registration of the same address is moot now
+        TempRegistrationRepository::delete_by_email(&mut *tx, &user.email)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(user.username)
+    }
+
+    async fn validate_registration(
+        conn: &mut PgConnection,
         email: &str,
         secret_key: &str,
     ) -> Result<TempRegistration, TempRegistrationError> {
         let registration =
-            TempRegistrationRepository::find_valid_by_email_and_key(
-                pool, email, secret_key,
-            )
-            .await?;
+            TempRegistrationRepository::record_attempt(&mut *conn, email)
+                .await?;
 
         if registration.expires_at < time::OffsetDateTime::now_utc() {
             return Err(TempRegistrationError::Expired);
@@ -49,36 +139,82 @@ impl RegistrationCompletionService {
             ));
         }
 
-        Ok(registration)
+        // Past the limit the registration is dropped, a new one starts with
+        // a new code
+        let max_attempts =
+            configs::Config::global().registration_code_max_attempts;
+        if registration.attempts > max_attempts {
+            TempRegistrationRepository::delete_by_email(&mut *conn, email)
+                .await?;
+            return Err(TempRegistrationError::TooManyAttempts);
+        }
+
+        match PasswordHasher::verify(secret_key, &registration.secret_key_hash)
+            .await?
+        {
+            PasswordVerification::Valid
+            | PasswordVerification::ValidNeedsRehash => Ok(registration),
+            PasswordVerification::Invalid
+                if registration.attempts >= max_attempts =>
+            {
+                log::warn!(
+                    "Registration for {email} dropped after {max_attempts} wrong codes"
+                );
+                TempRegistrationRepository::delete_by_email(&mut *conn, email)
+                    .await?;
+                Err(TempRegistrationError::TooManyAttempts)
+            }
+            PasswordVerification::Invalid => {
+                Err(TempRegistrationError::InvalidSecretKey)
+            }
+        }
     }
 
     async fn create_user_from_temp(
-        pool: &PgPool,
+        conn: &mut PgConnection,
         temp_registration: TempRegistration,
     ) -> Result<String, TempRegistrationError> {
-        let username = SecretGenerator::generate_alphanumeric_code(15);
+        let username = temp_registration
+            .username
+            .unwrap_or_else(|| SecretGenerator::generate_alphanumeric_code(15));
         let new_user = CreateUser {
-            username: username.clone(),
+            username,
             password: temp_registration.password,
             email: temp_registration.email,
+            language: temp_registration.language,
         };
 
-        UserRepository::create(pool, new_user).await.map_err(|e| {
-            log::error!("Failed to create user: {}", e);
-            TempRegistrationError::Internal
-        })?;
+        Ok(Self::create_user(conn, new_user).await?.username)
+    }
 
-        Ok(username)
+    async fn create_user(
+        conn: &mut PgConnection,
+        new_user: CreateUser,
+    ) -> Result<User, TempRegistrationError> {
+        UserRepository::create(&mut *conn, new_user).await.map_err(
+            |e| match e {
+                // Taken since the registration started
+                UserError::AlreadyExists => {
+                    TempRegistrationError::EmailAlreadyTaken
+                }
+                UserError::UsernameTaken => {
+                    TempRegistrationError::UsernameTaken
+                }
+                e => {
+                    log::error!("Failed to create user: {e}");
+                    TempRegistrationError::Internal
+                }
+            },
+        )
     }
 
     async fn cleanup_temp_data(
-        pool: &PgPool,
+        conn: &mut PgConnection,
         email: &str,
-        secret_key: &str,
     ) -> Result<(), TempRegistrationError> {
-        TempRegistrationRepository::mark_as_confirmed(pool, email, secret_key)
+        TempRegistrationRepository::mark_as_confirmed(&mut *conn, email)
             .await?;
-        TempRegistrationRepository::delete_by_email(pool, email).await?;
+        TempRegistrationRepository::delete_by_email(&mut *conn, email).await?;
         Ok(())
     }
 }
@@ -1,23 +1,39 @@
 use crate::{
     errors::temp_registration_errors::TempRegistrationError,
-    models::temp_registration::CreateTempRegistration,
+    models::{
+        email_outbox_models::NewOutboxEmail,
+        email_template_models::RenderedEmail,
+        invitation_models::InvitationPreview,
+        temp_registration::CreateTempRegistration,
+    },
     repositories::{
+        email_outbox_repository::EmailOutboxRepository,
+        invitation_repository::InvitationRepository,
         temp_registration_repository::TempRegistrationRepository,
         users_repository::UserRepository,
     },
-    services::email_services::{EmailService, LettreEmailService},
-    utils::secret_generator::SecretGenerator,
+    utils::{
+        email_templates::EmailTemplates,
+        password_hasher::PasswordHasher,
+        secret_generator::SecretGenerator,
+        token_hasher::TokenHasher,
+        username_policy::{UsernamePolicy, UsernameRejection},
+    },
 };
+use configs::RegistrationPolicy;
 use sqlx::PgPool;
 
 pub struct TempRegistrationService;
 
 impl TempRegistrationService {
+    /// Starts a registration and queues the confirmation code. The code is
+    /// only ever sent by email, never returned to the caller.
     pub async fn start_registration(
         pool: &PgPool,
-        registration_data: CreateTempRegistration,
-    ) -> Result<String, TempRegistrationError> {
+        mut registration_data: CreateTempRegistration,
+    ) -> Result<(), TempRegistrationError> {
         let email = registration_data.email.clone();
+        Self::check_policy(&email)?;
 
         if UserRepository::is_email_taken(pool, &registration_data.email)
             .await
@@ -29,6 +45,23 @@ impl TempRegistrationService {
             return Err(TempRegistrationError::EmailAlreadyTaken);
         }
 
+        if let Some(rejection) = Self::username_rejection(
+            pool,
+            &registration_data.username,
+            Some(&email),
+        )
+        .await?
+        {
+            return Err(match rejection {
+                UsernameRejection::Taken => {
+                    TempRegistrationError::UsernameTaken
+                }
+                rejection => {
+                    TempRegistrationError::Validation(rejection.message())
+                }
+            });
+        }
+
         let can_update =
             TempRegistrationRepository::can_update_registration(pool, &email)
                 .await?;
@@ -38,42 +71,194 @@ impl TempRegistrationService {
         }
 
         let secret_key = SecretGenerator::generate_numeric_code();
+        let secret_key_hash = PasswordHasher::hash(&secret_key).await?;
 
-        TempRegistrationRepository::create(
-            pool,
+        // Stored hashed, copied as-is into users on completion
+        registration_data.password =
+            PasswordHasher::hash(&registration_data.password).await?;
+
+        let confirmation_email = Self::confirmation_email(
+            &secret_key,
+            registration_data.language.as_deref(),
+        )?;
+
+        // The confirmation email is queued with the registration, so
+        // neither exists without the other
+        let mut tx = pool.begin().await?;
+
+        // Checked again by the insert, a parallel start may have won
+        if TempRegistrationRepository::create(
+            &mut *tx,
             registration_data,
-            secret_key.clone(),
+            secret_key_hash,
+            Self::resend_cooldown(),
+        )
+        .await?
+        .is_none()
+        {
+            return Err(TempRegistrationError::AlreadyInProgress);
+        }
+
+        EmailOutboxRepository::enqueue(
+            &mut *tx,
+            &NewOutboxEmail::new(&email, confirmation_email),
         )
         .await?;
 
-        Self::send_confirmation_email(&email, &secret_key).await.map_err(
-            |e| {
-                log::error!("Failed to send confirmation email: {}", e);
-                TempRegistrationError::Internal
-            },
-        )?;
+        tx.commit().await?;
 
-        Ok(secret_key)
+        Ok(())
     }
 
-    async fn send_confirmation_email(
+    /// Sends a fresh code for a pending registration, at most once per
+    /// `REGISTRATION_RESEND_COOLDOWN`. Wrong guesses at earlier codes still
+    /// count against the new one.
+    pub async fn resend_code(
+        pool: &PgPool,
         email: &str,
-        secret_key: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let email_service = LettreEmailService::new()
-            .map_err(|e| format!("Failed to create email service: {}", e))?;
+    ) -> Result<(), TempRegistrationError> {
+        Self::check_policy(email)?;
+
+        let registration =
+            TempRegistrationRepository::find_by_email(pool, email)
+                .await?
+                .filter(|registration| {
+                    !registration.confirmed
+                        && registration.expires_at
+                            > time::OffsetDateTime::now_utc()
+                })
+                .ok_or(TempRegistrationError::NotFound)?;
+
+        if !TempRegistrationRepository::can_update_registration(pool, email)
+            .await?
+        {
+            return Err(TempRegistrationError::ResendTooSoon);
+        }
+
+        let secret_key = SecretGenerator::generate_numeric_code();
+        let secret_key_hash = PasswordHasher::hash(&secret_key).await?;
+        let confirmation_email = Self::confirmation_email(
+            &secret_key,
+            registration.language.as_deref(),
+        )?;
+
+        let mut tx = pool.begin().await?;
 
-        EmailService::send_email(
-            &email_service,
+        // Checked again by the update, a parallel resend may have won
+        if !TempRegistrationRepository::update_code(
+            &mut *tx,
             email,
-            "Confirm registration",
-            &format!("Your secret code: {}", secret_key),
-            Some(&format!("<p>Your secret code: <b>{}</b></p>", secret_key)),
+            &secret_key_hash,
+            Self::resend_cooldown(),
+        )
+        .await?
+        {
+            return Err(TempRegistrationError::ResendTooSoon);
+        }
+
+        EmailOutboxRepository::enqueue(
+            &mut *tx,
+            &NewOutboxEmail::new(email, confirmation_email),
         )
-        .await
-        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+        .await?;
+
+        tx.commit().await?;
 
-        log::info!("Confirmation email sent to: {}", email);
         Ok(())
     }
+
+    fn resend_cooldown() -> time::Duration {
+        time::Duration::seconds(
+            configs::Config::global().registration_resend_cooldown,
+        )
+    }
+
+    /// Whether `REGISTRATION_POLICY` lets the email register on its own.
+    /// Checked again on completion, so registrations started before the
+    /// policy changed cannot finish against it.
+    pub fn check_policy(email: &str) -> Result<(), TempRegistrationError> {
+        let config = configs::Config::global();
+
+        match config.registration_policy {
+            RegistrationPolicy::Open => Ok(()),
+            RegistrationPolicy::InviteOnly => {
+                Err(TempRegistrationError::RegistrationClosed)
+            }
+            RegistrationPolicy::AllowedDomains => {
+                let domain = email
+                    .rsplit_once('@')
+                    .map(|(_, domain)| domain.to_lowercase())
+                    .unwrap_or_default();
+                if config.registration_allowed_domains.contains(&domain) {
+                    Ok(())
+                } else {
+                    Err(TempRegistrationError::EmailDomainNotAllowed)
+                }
+            }
+        }
+    }
+
+    /// Who the invitation is for, shown before the invitee picks a
+    /// username and password.
+    pub async fn invitation_preview(
+        pool: &PgPool,
+        token: &str,
+    ) -> Result<InvitationPreview, TempRegistrationError> {
+        let invitation = InvitationRepository::find_pending_by_token(
+            pool,
+            &TokenHasher::hash(token),
+        )
+        .await?
+        .ok_or(TempRegistrationError::InvalidInvitation)?;
+
+        Ok(InvitationPreview {
+            email: invitation.email,
+            role: invitation.role,
+            expires_at: invitation.expires_at,
+        })
+    }
+
+    /// Why `username` cannot be registered, None if it can. Usernames of
+    /// pending registrations stay claimed until they expire, apart from the
+    /// one of `except_email`.
+    pub async fn username_rejection(
+        pool: &PgPool,
+        username: &str,
+        except_email: Option<&str>,
+    ) -> Result<Option<UsernameRejection>, TempRegistrationError> {
+        if let Err(rejection) = UsernamePolicy::global().check(username) {
+            return Ok(Some(rejection));
+        }
+
+        let taken = UserRepository::is_username_taken(pool, username)
+            .await
+            .map_err(|e| {
+                log::error!("User repository error: {e}");
+                TempRegistrationError::Internal
+            })?
+            || TempRegistrationRepository::is_username_pending(
+                pool,
+                username,
+                except_email,
+            )
+            .await?;
+
+        Ok(taken.then_some(UsernameRejection::Taken))
+    }
+
+    fn confirmation_email(
+        secret_key: &str,
+        language: Option<&str>,
+    ) -> Result<RenderedEmail, TempRegistrationError> {
+        EmailTemplates::global()
+            .render(
+                "registration_confirmation",
+                language,
+                serde_json::json!({ "secret_key": secret_key }),
+            )
+            .map_err(|e| {
+                log::error!("Failed to render confirmation email: {e}");
+                TempRegistrationError::Internal
+            })
+    }
 }
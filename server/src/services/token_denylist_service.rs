@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex, RwLock},
+};
+
+use sqlx::{Error as SqlxError, PgPool};
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    models::revoked_tokens_models::RevokedToken,
+    repositories::revoked_tokens_repository::RevokedTokenRepository,
+};
+
+// Revocations made by other instances are picked up on the next sync. The
+// overlap re-reads rows whose transaction committed after the previous sync
+// started, inserting the same jti twice is harmless.
+const SYNC_OVERLAP: Duration = Duration::seconds(30);
+
+// In-process copy of the revoked_access_tokens table, so checking a token
+// on every request never hits the database.
+pub struct TokenDenylist {
+    revoked: RwLock<HashMap<String, OffsetDateTime>>,
+    synced_at: Mutex<Option<OffsetDateTime>>,
+}
+
+static DENYLIST: LazyLock<TokenDenylist> = LazyLock::new(|| TokenDenylist {
+    revoked: RwLock::new(HashMap::new()),
+    synced_at: Mutex::new(None),
+});
+
+impl TokenDenylist {
+    pub fn global() -> &'static TokenDenylist {
+        &DENYLIST
+    }
+
+    pub fn is_revoked(&self, jti: &str) -> bool {
+        self.revoked
+            .read()
+            .expect("Token denylist lock poisoned")
+            .contains_key(jti)
+    }
+
+    pub async fn revoke_family(
+        &self,
+        pool: &PgPool,
+        user_id: i32,
+        family_id: &str,
+    ) -> Result<(), SqlxError> {
+        let tokens =
+            RevokedTokenRepository::revoke_family(pool, user_id, family_id)
+                .await?;
+        self.insert(tokens);
+        Ok(())
+    }
+
+    pub async fn revoke_user(
+        &self,
+        pool: &PgPool,
+        user_id: i32,
+    ) -> Result<(), SqlxError> {
+        let tokens = RevokedTokenRepository::revoke_user(pool, user_id).await?;
+        self.insert(tokens);
+        Ok(())
+    }
+
+    /// Loads revocations made since the last sync and drops expired
+    /// entries, both from the cache and from the table.
+    pub async fn sync(&self, pool: &PgPool) {
+        let started_at = OffsetDateTime::now_utc();
+        let since = self
+            .synced_at
+            .lock()
+            .expect("Token denylist lock poisoned")
+            .map_or(OffsetDateTime::UNIX_EPOCH, |synced_at| {
+                synced_at - SYNC_OVERLAP
+            });
+
+        match RevokedTokenRepository::find_revoked_since(pool, since).await {
+            Ok(tokens) => {
+                self.insert(tokens);
+                *self.synced_at.lock().expect("Token denylist lock poisoned") =
+                    Some(started_at);
+            }
+            Err(e) => log::error!("Failed to sync token denylist: {e}"),
+        }
+
+        self.revoked
+            .write()
+            .expect("Token denylist lock poisoned")
+            .retain(|_, expires_at| *expires_at > started_at);
+
+        match RevokedTokenRepository::purge_expired(pool).await {
+            Ok(0) => {}
+            Ok(purged) => log::info!("Purged {purged} expired revoked tokens"),
+            Err(e) => {
+                log::error!("Failed to purge expired revoked tokens: {e}");
+            }
+        }
+    }
+
+    pub async fn run_sync(pool: PgPool) {
+        let interval = configs::Config::global().token_denylist_sync_interval;
+        let mut ticker =
+            tokio::time::interval(std::time::Duration::from_secs(interval));
+
+        loop {
+            ticker.tick().await;
+            Self::global().sync(&pool).await;
+        }
+    }
+
+    fn insert(&self, tokens: Vec<RevokedToken>) {
+        if tokens.is_empty() {
+            return;
+        }
+
+        let mut revoked =
+            self.revoked.write().expect("Token denylist lock poisoned");
+        for token in tokens {
+            revoked.insert(token.jti, token.expires_at);
+        }
+    }
+}
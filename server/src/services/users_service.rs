@@ -0,0 +1,161 @@
+use sqlx::PgPool;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::{
+    errors::users_errors::UserError,
+    models::{
+        security_events_models::SecurityEventType,
+        users_models::{
+            AdminUserView, ChangePasswordRequest, PatchUser, UserCursor,
+            UserPage, UserUpdate, UsersQuery,
+        },
+    },
+    repositories::{
+        security_events_repository::SecurityEventRepository,
+        users_repository::UserRepository,
+    },
+    services::auth_services::AuthService,
+    utils::password_hasher::{PasswordHasher, PasswordVerification},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 25;
+
+pub struct UserService;
+
+impl UserService {
+    /// Page of the user list. Pages are numbered unless the query continues
+    /// from a cursor, `next_cursor` is given either way.
+    pub async fn list(
+        pool: &PgPool,
+        query: &UsersQuery,
+    ) -> Result<UserPage, UserError> {
+        let sort = query.sort();
+        let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        let after = match query.cursor.as_deref() {
+            Some(_) if query.page.is_some() => {
+                return Err(Self::invalid_cursor(
+                    "Use either page or cursor, not both",
+                ));
+            }
+            Some(cursor) => {
+                Some(UserCursor::decode(cursor, sort).ok_or_else(|| {
+                    Self::invalid_cursor("Cursor is invalid for this sort")
+                })?)
+            }
+            None => None,
+        };
+        let page = after.is_none().then(|| query.page.unwrap_or(1));
+        let offset = page.map_or(0, |page| (page - 1).saturating_mul(per_page));
+
+        let filter = query.filter();
+
+        // One extra row tells whether there is a next page
+        let mut items = UserRepository::find_page(
+            pool,
+            &filter,
+            sort,
+            after.as_ref(),
+            per_page + 1,
+            offset,
+        )
+        .await?;
+        let page_len = usize::try_from(per_page).unwrap_or(items.len());
+        let next_cursor = if items.len() > page_len {
+            items.truncate(page_len);
+            items.last().map(|user| UserCursor::after(sort, user).encode())
+        } else {
+            None
+        };
+
+        let total = UserRepository::count(pool, &filter).await?;
+
+        Ok(UserPage {
+            items: items.into_iter().map(AdminUserView::from).collect(),
+            total,
+            page,
+            per_page,
+            sort: sort.to_string(),
+            next_cursor,
+        })
+    }
+
+    /// Applies the fields of the patch that differ from the stored user
+    /// and reports which ones those were.
+    pub async fn patch(
+        pool: &PgPool,
+        editor_id: i32,
+        user_id: i32,
+        patch: PatchUser,
+    ) -> Result<UserUpdate, UserError> {
+        let mut tx = pool.begin().await?;
+
+        let current = UserRepository::lock_by_id(&mut *tx, user_id).await?;
+        let changes = patch.changes(&current);
+        if changes.is_empty() {
+            return Ok(UserUpdate {
+                user: AdminUserView::from(current),
+                changed: Vec::new(),
+            });
+        }
+
+        let user =
+            UserRepository::update_fields(&mut *tx, user_id, &changes).await?;
+        tx.commit().await?;
+
+        let fields: Vec<String> = changes
+            .iter()
+            .map(|(field, _)| field.as_ref().to_string())
+            .collect();
+        SecurityEventRepository::record(
+            pool,
+            Some(user_id),
+            SecurityEventType::UserUpdated,
+            &format!("Changed {} by user {editor_id}", fields.join(", ")),
+        )
+        .await;
+
+        Ok(UserUpdate { user: AdminUserView::from(user), changed: fields })
+    }
+
+    /// Replaces the password of a signed in user who knows the current one,
+    /// then signs them out everywhere.
+    pub async fn change_password(
+        pool: &PgPool,
+        user_id: i32,
+        request: &ChangePasswordRequest,
+    ) -> Result<(), UserError> {
+        let user = UserRepository::find_by_id(pool, user_id).await?;
+
+        if let PasswordVerification::Invalid =
+            PasswordHasher::verify(&request.current_password, &user.password)
+                .await?
+        {
+            return Err(UserError::WrongPassword);
+        }
+
+        let password_hash = PasswordHasher::hash(&request.new_password).await?;
+        UserRepository::update_password(pool, user_id, &password_hash).await?;
+
+        AuthService::revoke_all_sessions(pool, user_id).await?;
+
+        SecurityEventRepository::record(
+            pool,
+            Some(user_id),
+            SecurityEventType::PasswordChanged,
+            "Password changed by its owner, all sessions revoked",
+        )
+        .await;
+
+        Ok(())
+    }
+
+    fn invalid_cursor(message: &'static str) -> UserError {
+        let mut errors = ValidationErrors::new();
+        errors.add(
+            "cursor",
+            ValidationError::new("cursor").with_message(message.into()),
+        );
+        UserError::Validation(errors)
+    }
+}
@@ -0,0 +1,40 @@
+use std::net::IpAddr;
+
+use actix_web::HttpRequest;
+use configs::TrustedProxy;
+
+/// Address of the client. `X-Forwarded-For` is only read when the peer is
+/// one of `TRUSTED_PROXIES`, otherwise every client could name its own IP.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip().to_canonical();
+    let proxies = &configs::Config::global().trusted_proxies;
+    if !is_trusted(proxies, peer) {
+        return Some(peer);
+    }
+
+    let hops: Vec<&str> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    // Proxies append the address they got the request from, so the first
+    // untrusted hop from the right is the client. Hops left of it are
+    // whatever the client sent.
+    let mut client = peer;
+    for hop in hops.iter().rev() {
+        let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = ip.to_canonical();
+        if !is_trusted(proxies, client) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+fn is_trusted(proxies: &[TrustedProxy], ip: IpAddr) -> bool {
+    proxies.iter().any(|proxy| proxy.contains(ip))
+}
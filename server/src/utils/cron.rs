@@ -0,0 +1,292 @@
+use std::fmt;
+
+use time::{Date, Duration, OffsetDateTime, Time};
+
+use crate::errors::job_errors::JobError;
+
+// How far ahead the next run is searched, a schedule that never matches
+// (e.g. "0 0 30 2 *") is rejected instead of looping forever
+const SEARCH_LIMIT: Duration = Duration::days(5 * 366);
+
+/// Five-field cron expression (minute, hour, day of month, month, day of
+/// week) evaluated in UTC. Fields take `*`, values, ranges and steps
+/// (`*/15`, `1-5`, `0,30`, `10-40/10`), Sunday is 0 or 7. `@hourly`,
+/// `@daily`, `@weekly`, `@monthly` and `@yearly` are accepted as well.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Restricting both day fields matches either of them, as in cron
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, JobError> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let invalid = |reason: String| {
+            JobError::Config(format!(
+                "invalid schedule '{expression}': {reason}"
+            ))
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..]
+        else {
+            return Err(invalid(format!(
+                "expected 5 fields, got {}",
+                fields.len()
+            )));
+        };
+
+        let mut weekdays =
+            Self::parse_field(day_of_week, 0, 7).map_err(invalid)?;
+        // 7 is another name for Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(CronSchedule {
+            expression: expression.trim().to_string(),
+            minutes: Self::parse_field(minute, 0, 59).map_err(invalid)?,
+            hours: Self::parse_field(hour, 0, 23).map_err(invalid)?,
+            days_of_month: Self::parse_field(day_of_month, 1, 31)
+                .map_err(invalid)?,
+            months: Self::parse_field(month, 1, 12).map_err(invalid)?,
+            days_of_week: weekdays,
+            any_day_of_month: day_of_month == "*",
+            any_day_of_week: day_of_week == "*",
+        })
+    }
+
+    /// First matching minute strictly after `after`, None if there is none
+    /// within the next five years.
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let after = after.to_offset(time::UtcOffset::UTC);
+        let mut next = after.replace_time(
+            Time::from_hms(after.hour(), after.minute(), 0).ok()?,
+        ) + Duration::minutes(1);
+        let limit = next + SEARCH_LIMIT;
+
+        while next < limit {
+            let date = next.date();
+
+            if !Self::contains(self.months, u8::from(date.month())) {
+                let (year, month) = match date.month().next() {
+                    time::Month::January => {
+                        (date.year() + 1, time::Month::January)
+                    }
+                    month => (date.year(), month),
+                };
+                next = Date::from_calendar_date(year, month, 1)
+                    .ok()?
+                    .midnight()
+                    .assume_utc();
+            } else if !self.matches_day(date) {
+                next = date.next_day()?.midnight().assume_utc();
+            } else if !Self::contains(self.hours, next.hour()) {
+                next = next.replace_minute(0).ok()? + Duration::hours(1);
+            } else if !Self::contains(self.minutes, next.minute()) {
+                next += Duration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+
+        None
+    }
+
+    fn matches_day(&self, date: Date) -> bool {
+        let day_of_month = Self::contains(self.days_of_month, date.day());
+        let day_of_week = Self::contains(
+            self.days_of_week,
+            date.weekday().number_days_from_sunday(),
+        );
+
+        if self.any_day_of_month || self.any_day_of_week {
+            day_of_month && day_of_week
+        } else {
+            day_of_month || day_of_week
+        }
+    }
+
+    fn contains(set: u64, value: u8) -> bool {
+        set & (1 << value) != 0
+    }
+
+    // Bit n of the result is set when the field matches value n
+    fn parse_field(field: &str, min: u8, max: u8) -> Result<u64, String> {
+        let mut set = 0;
+
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (
+                    range,
+                    step.parse::<u8>()
+                        .ok()
+                        .filter(|step| *step > 0)
+                        .ok_or_else(|| format!("invalid step in '{part}'"))?,
+                ),
+                None => (part, 1),
+            };
+
+            let (start, end) = match range {
+                "*" => (min, max),
+                range => {
+                    let value = |value: &str| {
+                        value
+                            .parse::<u8>()
+                            .ok()
+                            .filter(|value| (min..=max).contains(value))
+                            .ok_or_else(|| {
+                                format!("'{part}' is out of range {min}-{max}")
+                            })
+                    };
+                    match range.split_once('-') {
+                        Some((start, end)) => (value(start)?, value(end)?),
+                        // "5/15" runs from 5 to the end of the range
+                        None if step > 1 => (value(range)?, max),
+                        None => (value(range)?, value(range)?),
+                    }
+                }
+            };
+            if start > end {
+                return Err(format!("'{part}' is an empty range"));
+            }
+
+            for value in (start..=end).step_by(usize::from(step)) {
+                set |= 1 << value;
+            }
+        }
+
+        Ok(set)
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::format_description::well_known::Rfc3339;
+
+    fn at(timestamp: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(timestamp, &Rfc3339).expect("valid timestamp")
+    }
+
+    fn next(expression: &str, after: &str) -> Option<OffsetDateTime> {
+        CronSchedule::parse(expression)
+            .expect("valid schedule")
+            .next_after(at(after))
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 1st of the month or a Monday, 2024-09-30 is a Monday
+        assert_eq!(
+            next("0 0 1 * 1", "2024-09-25T00:00:00Z"),
+            Some(at("2024-09-30T00:00:00Z"))
+        );
+        assert_eq!(
+            next("0 0 1 * 1", "2024-09-30T00:00:00Z"),
+            Some(at("2024-10-01T00:00:00Z"))
+        );
+
+        // With the other field left at `*` only the restricted one counts
+        assert_eq!(
+            next("0 0 1 * *", "2024-09-25T00:00:00Z"),
+            Some(at("2024-10-01T00:00:00Z"))
+        );
+        assert_eq!(
+            next("0 0 * * 1", "2024-09-30T00:00:00Z"),
+            Some(at("2024-10-07T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        // 2024-09-08 is a Sunday
+        for expression in ["0 12 * * 7", "0 12 * * 0", "0 12 * * 6-7"] {
+            assert_eq!(
+                next(expression, "2024-09-07T12:00:00Z"),
+                Some(at("2024-09-08T12:00:00Z")),
+                "{expression}"
+            );
+        }
+        assert_eq!(
+            next("0 12 * * 5-7", "2024-09-08T12:00:00Z"),
+            Some(at("2024-09-13T12:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn single_value_with_step_runs_to_the_end_of_the_range() {
+        let schedule = CronSchedule::parse("5/15 * * * *").unwrap();
+        let mut runs = Vec::new();
+        let mut after = at("2024-09-01T10:00:00Z");
+        for _ in 0..5 {
+            after = schedule.next_after(after).unwrap();
+            runs.push(after);
+        }
+
+        assert_eq!(
+            runs,
+            [
+                at("2024-09-01T10:05:00Z"),
+                at("2024-09-01T10:20:00Z"),
+                at("2024-09-01T10:35:00Z"),
+                at("2024-09-01T10:50:00Z"),
+                at("2024-09-01T11:05:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rolls_over_into_the_next_year() {
+        assert_eq!(
+            next("@yearly", "2024-12-31T23:59:00Z"),
+            Some(at("2025-01-01T00:00:00Z"))
+        );
+        assert_eq!(
+            next("30 9 * 2 *", "2024-11-15T08:00:00Z"),
+            Some(at("2025-02-01T09:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn gives_up_after_the_search_limit() {
+        // Next leap day is three years away, still within the limit
+        assert_eq!(
+            next("0 0 29 2 *", "2025-03-01T00:00:00Z"),
+            Some(at("2028-02-29T00:00:00Z"))
+        );
+        assert_eq!(next("0 0 30 2 *", "2024-01-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in
+            ["0 0 * *", "60 * * * *", "*/0 * * * *", "5-1 * * * *"]
+        {
+            assert!(
+                CronSchedule::parse(expression).is_err(),
+                "{expression} accepted"
+            );
+        }
+    }
+}
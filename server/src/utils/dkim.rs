@@ -0,0 +1,171 @@
+use std::{fs, sync::OnceLock};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use lettre::{
+    Message,
+    message::{
+        dkim::{
+            DkimCanonicalization, DkimCanonicalizationType, DkimConfig,
+            DkimSigningAlgorithm, DkimSigningKey,
+        },
+        header::HeaderName,
+    },
+};
+use rsa::{
+    RsaPrivateKey,
+    pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey},
+    pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding},
+};
+use serde::Serialize;
+
+use crate::errors::dkim_errors::DkimError;
+
+// List-Unsubscribe has to be signed for one-click unsubscribe (RFC 8058)
+const SIGNED_HEADERS: [&str; 9] = [
+    "From",
+    "Reply-To",
+    "To",
+    "Cc",
+    "Subject",
+    "Date",
+    "Message-ID",
+    "List-Unsubscribe",
+    "List-Unsubscribe-Post",
+];
+// Longest string a single TXT character-string may hold
+const TXT_CHUNK: usize = 255;
+
+/// TXT record the signing key has to be published under.
+#[derive(Debug, Serialize)]
+pub struct DkimDnsRecord {
+    pub name: String,
+    pub value: String,
+    // Zone file line, long values split into 255-char strings
+    pub zone_entry: String,
+}
+
+pub struct DkimSigner {
+    config: DkimConfig,
+    dns_record: DkimDnsRecord,
+}
+
+// None when DKIM is not configured
+static DKIM_SIGNER: OnceLock<Option<DkimSigner>> = OnceLock::new();
+
+impl DkimSigner {
+    // Signing is enabled by setting DKIM_SELECTOR, DKIM_DOMAIN and
+    // DKIM_PRIVATE_KEY_PATH together. The key is RSA in PKCS#1 or PKCS#8 PEM.
+    pub fn init() -> Result<(), DkimError> {
+        let config = configs::Config::global();
+
+        let signer = match (
+            &config.dkim_selector,
+            &config.dkim_domain,
+            &config.dkim_private_key_path,
+        ) {
+            (Some(selector), Some(domain), Some(key_path)) => {
+                Some(Self::load(selector, domain, key_path)?)
+            }
+            (None, None, None) => {
+                log::warn!("DKIM not configured, outgoing mail is unsigned");
+                None
+            }
+            _ => {
+                return Err(DkimError::Config(
+                    "DKIM_SELECTOR, DKIM_DOMAIN and DKIM_PRIVATE_KEY_PATH \
+                     must be set together"
+                        .into(),
+                ));
+            }
+        };
+
+        if let Some(signer) = &signer {
+            log::info!(
+                "DKIM signing enabled, publish: {}",
+                signer.dns_record.zone_entry
+            );
+        }
+
+        DKIM_SIGNER.set(signer).map_err(|_| {
+            DkimError::Config("DKIM signer already initialized".into())
+        })
+    }
+
+    pub fn global() -> Option<&'static DkimSigner> {
+        DKIM_SIGNER
+            .get()
+            .expect(
+                "DKIM signer not initialized. Call DkimSigner::init() first",
+            )
+            .as_ref()
+    }
+
+    pub fn sign(&self, message: &mut Message) {
+        message.sign(&self.config);
+    }
+
+    pub fn dns_record(&self) -> &DkimDnsRecord {
+        &self.dns_record
+    }
+
+    fn load(
+        selector: &str,
+        domain: &str,
+        key_path: &str,
+    ) -> Result<Self, DkimError> {
+        let pem = fs::read_to_string(key_path)?;
+        let private_key = RsaPrivateKey::from_pkcs1_pem(&pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs8_pem(&pem))
+            .map_err(|e| DkimError::Key(e.to_string()))?;
+
+        let public_key = private_key
+            .to_public_key()
+            .to_public_key_der()
+            .map_err(|e| DkimError::Key(e.to_string()))?;
+        let dns_record =
+            Self::build_dns_record(selector, domain, public_key.as_bytes());
+
+        // lettre only takes PKCS#1
+        let pkcs1 = private_key
+            .to_pkcs1_pem(LineEnding::LF)
+            .map_err(|e| DkimError::Key(e.to_string()))?;
+        let signing_key =
+            DkimSigningKey::new(&pkcs1, DkimSigningAlgorithm::Rsa)
+                .map_err(|e| DkimError::Key(e.to_string()))?;
+
+        let config = DkimConfig::new(
+            selector.to_string(),
+            domain.to_string(),
+            signing_key,
+            SIGNED_HEADERS
+                .iter()
+                .map(|name| HeaderName::new_from_ascii_str(name))
+                .collect(),
+            DkimCanonicalization {
+                header: DkimCanonicalizationType::Relaxed,
+                body: DkimCanonicalizationType::Relaxed,
+            },
+        );
+
+        Ok(DkimSigner { config, dns_record })
+    }
+
+    fn build_dns_record(
+        selector: &str,
+        domain: &str,
+        public_key_der: &[u8],
+    ) -> DkimDnsRecord {
+        let name = format!("{selector}._domainkey.{domain}");
+        let value =
+            format!("v=DKIM1; k=rsa; p={}", STANDARD.encode(public_key_der));
+
+        let chunks: Vec<String> = value
+            .as_bytes()
+            .chunks(TXT_CHUNK)
+            .map(|chunk| format!("\"{}\"", String::from_utf8_lossy(chunk)))
+            .collect();
+        let zone_entry = format!("{name}. IN TXT ( {} )", chunks.join(" "));
+
+        DkimDnsRecord { name, value, zone_entry }
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use minijinja::{Environment, UndefinedBehavior, Value, context, path_loader};
+use serde::Serialize;
+
+use crate::{
+    errors::email_template_errors::EmailTemplateError,
+    models::email_template_models::RenderedEmail,
+};
+
+const SUBJECT: &str = "subject.txt";
+const TEXT_BODY: &str = "body.txt";
+const HTML_BODY: &str = "body.html";
+const SAMPLE_DATA: &str = "sample.json";
+
+pub struct EmailTemplates {
+    env: Environment<'static>,
+    dir: PathBuf,
+    default_locale: String,
+}
+
+static EMAIL_TEMPLATES: OnceLock<EmailTemplates> = OnceLock::new();
+
+impl EmailTemplates {
+    // Every template is a directory in EMAIL_TEMPLATES_DIR holding one
+    // `<locale>/` directory per language with `subject.txt`, `body.txt` and
+    // an optional `body.html`. Layouts and partials anywhere in the
+    // directory are shared through `extends` and `include`. HTML parts are
+    // escaped automatically, a missing variable fails the rendering.
+    pub fn init() -> Result<(), EmailTemplateError> {
+        let config = configs::Config::global();
+        let dir = PathBuf::from(&config.email_templates_dir);
+
+        if !dir.is_dir() {
+            return Err(EmailTemplateError::Config(format!(
+                "EMAIL_TEMPLATES_DIR {} is not a directory",
+                dir.display()
+            )));
+        }
+
+        let mut env = Environment::new();
+        env.set_loader(path_loader(&dir));
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+
+        let templates = EmailTemplates {
+            env,
+            dir,
+            default_locale: config.email_default_locale.clone(),
+        };
+
+        EMAIL_TEMPLATES.set(templates).map_err(|_| {
+            EmailTemplateError::Config(
+                "Email templates already initialized".into(),
+            )
+        })
+    }
+
+    pub fn global() -> &'static EmailTemplates {
+        EMAIL_TEMPLATES.get().expect(
+            "Email templates not initialized. Call EmailTemplates::init() first",
+        )
+    }
+
+    /// Renders template `name` in `language`, its base language (`pt` for
+    /// `pt-br`) or the default locale, whichever exists first. The template
+    /// also sees `locale` and `default_locale`.
+    pub fn render<S: Serialize>(
+        &self,
+        name: &str,
+        language: Option<&str>,
+        data: S,
+    ) -> Result<RenderedEmail, EmailTemplateError> {
+        let locale = self.resolve_locale(name, language)?;
+        let ctx = context! {
+            locale => locale,
+            default_locale => self.default_locale,
+            ..Value::from_serialize(data)
+        };
+
+        let subject = self.render_part(name, &locale, SUBJECT, &ctx)?;
+        let text_body = self.render_part(name, &locale, TEXT_BODY, &ctx)?;
+        let html_body = if self.part_path(name, &locale, HTML_BODY).is_file() {
+            Some(self.render_part(name, &locale, HTML_BODY, &ctx)?)
+        } else {
+            None
+        };
+
+        Ok(RenderedEmail {
+            subject: Self::fold_subject(&subject),
+            locale,
+            text_body,
+            html_body,
+        })
+    }
+
+    /// Renders subject and bodies given as template sources rather than
+    /// files, such as campaigns written in the admin panel. Layouts and
+    /// partials are shared with the file templates.
+    pub fn render_source<S: Serialize>(
+        &self,
+        subject: &str,
+        text_body: &str,
+        html_body: Option<&str>,
+        language: Option<&str>,
+        data: S,
+    ) -> Result<RenderedEmail, EmailTemplateError> {
+        let locale = Self::normalize_language(language)
+            .unwrap_or_else(|| self.default_locale.clone());
+        let ctx = context! {
+            locale => locale,
+            default_locale => self.default_locale,
+            ..Value::from_serialize(data)
+        };
+
+        // The names pick the escaping, HTML only for the .html part
+        let subject = self.env.render_named_str(SUBJECT, subject, &ctx)?;
+        let text_body =
+            self.env.render_named_str(TEXT_BODY, text_body, &ctx)?;
+        let html_body = html_body
+            .map(|html_body| {
+                self.env.render_named_str(HTML_BODY, html_body, &ctx)
+            })
+            .transpose()?;
+
+        Ok(RenderedEmail {
+            subject: Self::fold_subject(&subject),
+            locale,
+            text_body,
+            html_body,
+        })
+    }
+
+    /// Renders template `name` with the `sample.json` stored next to it.
+    pub fn preview(
+        &self,
+        name: &str,
+        language: Option<&str>,
+    ) -> Result<RenderedEmail, EmailTemplateError> {
+        if !Self::is_valid_segment(name) {
+            return Err(EmailTemplateError::NotFound(name.to_string()));
+        }
+
+        let sample_path = self.dir.join(name).join(SAMPLE_DATA);
+        let data: serde_json::Value = if sample_path.is_file() {
+            serde_json::from_str(&fs::read_to_string(sample_path)?)?
+        } else {
+            serde_json::Value::Object(serde_json::Map::new())
+        };
+
+        self.render(name, language, data)
+    }
+
+    fn resolve_locale(
+        &self,
+        name: &str,
+        language: Option<&str>,
+    ) -> Result<String, EmailTemplateError> {
+        if !Self::is_valid_segment(name) {
+            return Err(EmailTemplateError::NotFound(name.to_string()));
+        }
+
+        let language = Self::normalize_language(language);
+        let base_language = language
+            .as_deref()
+            .and_then(|language| language.split_once('-'))
+            .map(|(base, _)| base.to_string());
+
+        [language, base_language, Some(self.default_locale.clone())]
+            .into_iter()
+            .flatten()
+            .find(|locale| self.part_path(name, locale, SUBJECT).is_file())
+            .ok_or_else(|| EmailTemplateError::NotFound(name.to_string()))
+    }
+
+    fn normalize_language(language: Option<&str>) -> Option<String> {
+        language
+            .map(|language| language.trim().to_lowercase().replace('_', "-"))
+            .filter(|language| Self::is_valid_segment(language))
+    }
+
+    // Folded to one line, a stray newline would break the header
+    fn fold_subject(subject: &str) -> String {
+        subject.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn render_part(
+        &self,
+        name: &str,
+        locale: &str,
+        part: &str,
+        ctx: &Value,
+    ) -> Result<String, EmailTemplateError> {
+        let template =
+            self.env.get_template(&format!("{name}/{locale}/{part}"))?;
+        Ok(template.render(ctx)?)
+    }
+
+    fn part_path(&self, name: &str, locale: &str, part: &str) -> PathBuf {
+        Path::new(&self.dir).join(name).join(locale).join(part)
+    }
+
+    // Names and locales end up in file paths
+    fn is_valid_segment(segment: &str) -> bool {
+        !segment.is_empty()
+            && segment.chars().all(|c| {
+                c.is_ascii_lowercase()
+                    || c.is_ascii_digit()
+                    || c == '_'
+                    || c == '-'
+            })
+    }
+}
@@ -0,0 +1,223 @@
+use std::{collections::HashMap, fs, path::Path, sync::OnceLock};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet,
+        KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+        RSAKeyParameters, RSAKeyType,
+    },
+};
+use rsa::{RsaPublicKey, pkcs8::DecodePublicKey, traits::PublicKeyParts};
+
+use crate::errors::jwt_key_errors::JwtKeyError;
+
+// DER prefix of an Ed25519 SubjectPublicKeyInfo, followed by the raw 32-byte key.
+const ED25519_SPKI_PREFIX: [u8; 12] =
+    [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+const PUBLIC_KEY_SUFFIX: &str = ".pub.pem";
+const PRIVATE_KEY_SUFFIX: &str = ".pem";
+
+struct VerificationKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+    jwk: Jwk,
+}
+
+pub struct JwtKeyStore {
+    signing_kid: Option<String>,
+    signing_algorithm: Algorithm,
+    signing_key: EncodingKey,
+    verification_keys: HashMap<String, VerificationKey>,
+    // Only set in HMAC mode, where tokens carry no `kid`.
+    shared_secret: Option<DecodingKey>,
+}
+
+static JWT_KEYS: OnceLock<JwtKeyStore> = OnceLock::new();
+
+impl JwtKeyStore {
+    // With JWT_KEYS_DIR set, every `<kid>.pub.pem` in it is accepted for
+    // verification and `<JWT_SIGNING_KID>.pem` signs new tokens. Otherwise
+    // tokens are signed with the shared HS256 JWT_ACCESS_SECRET.
+    pub fn init() -> Result<(), JwtKeyError> {
+        let config = configs::Config::global();
+
+        let store = if let Some(dir) = &config.jwt_keys_dir {
+            let kid = config.jwt_signing_kid.as_deref().ok_or_else(|| {
+                JwtKeyError::Config(
+                    "JWT_SIGNING_KID is required with JWT_KEYS_DIR".into(),
+                )
+            })?;
+            Self::from_dir(Path::new(dir), kid)?
+        } else {
+            let secret = config
+                .jwt_access_secret
+                .as_deref()
+                .filter(|secret| !secret.is_empty())
+                .ok_or_else(|| {
+                    JwtKeyError::Config(
+                        "Either JWT_KEYS_DIR or JWT_ACCESS_SECRET must be set"
+                            .into(),
+                    )
+                })?;
+            log::warn!("JWT_KEYS_DIR not set, signing tokens with HS256");
+            Self::from_secret(secret.as_bytes())
+        };
+
+        JWT_KEYS.set(store).map_err(|_| {
+            JwtKeyError::Config("JWT keys already initialized".into())
+        })
+    }
+
+    pub fn global() -> &'static JwtKeyStore {
+        JWT_KEYS
+            .get()
+            .expect("JWT keys not initialized. Call JwtKeyStore::init() first")
+    }
+
+    fn from_secret(secret: &[u8]) -> Self {
+        JwtKeyStore {
+            signing_kid: None,
+            signing_algorithm: Algorithm::HS256,
+            signing_key: EncodingKey::from_secret(secret),
+            verification_keys: HashMap::new(),
+            shared_secret: Some(DecodingKey::from_secret(secret)),
+        }
+    }
+
+    fn from_dir(dir: &Path, signing_kid: &str) -> Result<Self, JwtKeyError> {
+        let mut verification_keys = HashMap::new();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(kid) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(PUBLIC_KEY_SUFFIX))
+            else {
+                continue;
+            };
+
+            let key = Self::load_public_key(kid, &fs::read(&path)?)?;
+            log::info!(
+                "Loaded JWT verification key {kid} ({:?})",
+                key.algorithm
+            );
+            verification_keys.insert(kid.to_string(), key);
+        }
+
+        let signing_algorithm = verification_keys
+            .get(signing_kid)
+            .map(|key| key.algorithm)
+            .ok_or_else(|| {
+                JwtKeyError::Config(format!(
+                    "Public key {signing_kid}{PUBLIC_KEY_SUFFIX} not found"
+                ))
+            })?;
+
+        let private_pem =
+            fs::read(dir.join(format!("{signing_kid}{PRIVATE_KEY_SUFFIX}")))?;
+        let signing_key = match signing_algorithm {
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&private_pem)?,
+            _ => EncodingKey::from_rsa_pem(&private_pem)?,
+        };
+        log::info!("Signing JWTs with key {signing_kid}");
+
+        Ok(JwtKeyStore {
+            signing_kid: Some(signing_kid.to_string()),
+            signing_algorithm,
+            signing_key,
+            verification_keys,
+            shared_secret: None,
+        })
+    }
+
+    // Builds the public JWK first and derives the decoding key from it, so
+    // the JWKS endpoint always publishes exactly what we verify with.
+    fn load_public_key(
+        kid: &str,
+        pem: &[u8],
+    ) -> Result<VerificationKey, JwtKeyError> {
+        let der = pem::parse(pem)?.into_contents();
+
+        let (algorithm, parameters) = if let Some(x) =
+            der.strip_prefix(&ED25519_SPKI_PREFIX).filter(|x| x.len() == 32)
+        {
+            (
+                Algorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(x),
+                }),
+            )
+        } else {
+            let public_key = RsaPublicKey::from_public_key_der(&der)?;
+            (
+                Algorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+                }),
+            )
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match algorithm {
+                    Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+                    _ => KeyAlgorithm::RS256,
+                }),
+                key_id: Some(kid.to_string()),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        };
+
+        Ok(VerificationKey {
+            algorithm,
+            key: DecodingKey::from_jwk(&jwk)?,
+            jwk,
+        })
+    }
+
+    pub fn header(&self) -> Header {
+        Header {
+            kid: self.signing_kid.clone(),
+            ..Header::new(self.signing_algorithm)
+        }
+    }
+
+    pub fn signing_key(&self) -> &EncodingKey {
+        &self.signing_key
+    }
+
+    // The algorithm is pinned by the key, never taken from the token header.
+    pub fn verification_key(
+        &self,
+        kid: Option<&str>,
+    ) -> Option<(Algorithm, &DecodingKey)> {
+        match kid {
+            Some(kid) => self
+                .verification_keys
+                .get(kid)
+                .map(|key| (key.algorithm, &key.key)),
+            None => {
+                self.shared_secret.as_ref().map(|key| (Algorithm::HS256, key))
+            }
+        }
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .verification_keys
+                .values()
+                .map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
@@ -1 +1,12 @@
+pub mod client_ip;
+pub mod cron;
+pub mod dkim;
+pub mod email_templates;
+pub mod jwt_keys;
+pub mod password_hasher;
 pub mod secret_generator;
+pub mod token_hasher;
+pub mod username_policy;
+
+#[cfg(test)]
+pub mod test_support;
@@ -0,0 +1,109 @@
+use argon2::{
+    Algorithm, Argon2, Params, Version,
+    password_hash::{
+        self, PasswordHash, PasswordHasher as _, PasswordVerifier as _,
+        SaltString, rand_core::OsRng,
+    },
+};
+use subtle::ConstantTimeEq;
+
+use crate::errors::password_errors::PasswordError;
+
+/// Outcome of checking a password against the value stored in the DB.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordVerification {
+    Invalid,
+    Valid,
+    // Password matched, but the stored value is plaintext or was hashed
+    // with outdated parameters and should be replaced
+    ValidNeedsRehash,
+}
+
+pub struct PasswordHasher;
+
+impl PasswordHasher {
+    // Argon2id with the cost parameters from the config
+    fn argon2() -> Result<Argon2<'static>, PasswordError> {
+        let config = configs::Config::global();
+        let params = Params::new(
+            config.argon2_memory_cost,
+            config.argon2_time_cost,
+            config.argon2_parallelism,
+            None,
+        )
+        .map_err(password_hash::Error::from)?;
+
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    /// Hashes a password into a PHC string. Runs on the blocking pool
+    /// because Argon2 is intentionally expensive.
+    pub async fn hash(password: &str) -> Result<String, PasswordError> {
+        let password = password.to_owned();
+
+        tokio::task::spawn_blocking(move || Self::hash_blocking(&password))
+            .await?
+    }
+
+    /// Checks a password against a stored PHC string. Stored values that
+    /// are not Argon2 hashes are treated as legacy plaintext and compared
+    /// in constant time.
+    pub async fn verify(
+        password: &str,
+        stored: &str,
+    ) -> Result<PasswordVerification, PasswordError> {
+        let password = password.to_owned();
+        let stored = stored.to_owned();
+
+        tokio::task::spawn_blocking(move || {
+            Self::verify_blocking(&password, &stored)
+        })
+        .await?
+    }
+
+    fn hash_blocking(password: &str) -> Result<String, PasswordError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Self::argon2()?.hash_password(password.as_bytes(), &salt)?;
+
+        Ok(hash.to_string())
+    }
+
+    fn verify_blocking(
+        password: &str,
+        stored: &str,
+    ) -> Result<PasswordVerification, PasswordError> {
+        let Ok(parsed) = PasswordHash::new(stored) else {
+            // Legacy row written before hashing was introduced
+            let matches: bool =
+                password.as_bytes().ct_eq(stored.as_bytes()).into();
+            return Ok(if matches {
+                PasswordVerification::ValidNeedsRehash
+            } else {
+                PasswordVerification::Invalid
+            });
+        };
+
+        let argon2 = Self::argon2()?;
+        match argon2.verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => {}
+            Err(password_hash::Error::Password) => {
+                return Ok(PasswordVerification::Invalid);
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let outdated = parsed.algorithm != argon2::ARGON2ID_IDENT
+            || Params::try_from(&parsed).map_or(true, |params| {
+                let current = argon2.params();
+                params.m_cost() != current.m_cost()
+                    || params.t_cost() != current.t_cost()
+                    || params.p_cost() != current.p_cost()
+            });
+
+        Ok(if outdated {
+            PasswordVerification::ValidNeedsRehash
+        } else {
+            PasswordVerification::Valid
+        })
+    }
+}
@@ -10,19 +10,42 @@ impl SecretGenerator {
     }
 
     pub fn generate_alphanumeric_code(n: usize) -> String {
+        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                                abcdefghijklmnopqrstuvwxyz\
+                                0123456789";
+
+        Self::generate_from_charset(CHARSET, n)
+    }
+
+    // RFC 4648 base32, as expected by authenticator apps for TOTP secrets
+    pub fn generate_base32_secret(n: usize) -> String {
+        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+        Self::generate_from_charset(CHARSET, n)
+    }
+
+    // Lowercase code in "xxxxx-xxxxx" form, easy to type from a printout
+    pub fn generate_recovery_code() -> String {
+        const CHARSET: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+
+        format!(
+            "{}-{}",
+            Self::generate_from_charset(CHARSET, 5),
+            Self::generate_from_charset(CHARSET, 5)
+        )
+    }
+
+    fn generate_from_charset(charset: &[u8], n: usize) -> String {
         if n == 0 {
             return String::new();
         }
 
         let mut rng = rand::rng();
-        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
-                                abcdefghijklmnopqrstuvwxyz\
-                                0123456789";
 
         (0..n)
             .map(|_| {
-                let idx = rng.random_range(0..CHARSET.len());
-                CHARSET[idx] as char
+                let idx = rng.random_range(0..charset.len());
+                charset[idx] as char
             })
             .collect()
     }
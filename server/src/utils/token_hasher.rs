@@ -0,0 +1,11 @@
+use sha2::{Digest, Sha256};
+
+pub struct TokenHasher;
+
+impl TokenHasher {
+    // SHA-256 hex digest for high-entropy random tokens. Low-entropy
+    // secrets such as passwords go through PasswordHasher instead.
+    pub fn hash(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+}
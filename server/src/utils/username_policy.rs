@@ -0,0 +1,147 @@
+use std::{collections::HashSet, fs, sync::OnceLock};
+
+use strum_macros::AsRefStr;
+
+/// Why a username is not accepted, serialized as the `reason` code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum UsernameRejection {
+    TooShort,
+    TooLong,
+    InvalidCharacters,
+    MustStartWithLetter,
+    Reserved,
+    Blocked,
+    Taken,
+}
+
+impl UsernameRejection {
+    pub fn message(self) -> String {
+        let config = configs::Config::global();
+        match self {
+            UsernameRejection::TooShort => format!(
+                "Username must be at least {} chars",
+                config.username_min_length
+            ),
+            UsernameRejection::TooLong => format!(
+                "Username must be at most {} chars",
+                config.username_max_length
+            ),
+            UsernameRejection::InvalidCharacters => format!(
+                "Username may only contain letters, digits and {}",
+                config.username_allowed_symbols
+            ),
+            UsernameRejection::MustStartWithLetter => {
+                "Username must start with a letter".to_string()
+            }
+            UsernameRejection::Reserved => {
+                "This username is reserved".to_string()
+            }
+            UsernameRejection::Blocked => {
+                "This username is not allowed".to_string()
+            }
+            UsernameRejection::Taken => {
+                "This username is already taken".to_string()
+            }
+        }
+    }
+}
+
+pub struct UsernamePolicy {
+    min_length: usize,
+    max_length: usize,
+    allowed_symbols: Vec<char>,
+    reserved: HashSet<String>,
+    blocklist: Vec<String>,
+}
+
+static USERNAME_POLICY: OnceLock<UsernamePolicy> = OnceLock::new();
+
+impl UsernamePolicy {
+    // Usernames are ASCII letters, digits and USERNAME_ALLOWED_SYMBOLS and
+    // start with a letter. Reserved names are matched ignoring case, words
+    // from USERNAME_BLOCKLIST_PATH (one per line, # for comments) anywhere
+    // in the name, also when split by symbols or spelled with digits.
+    pub fn init() -> Result<(), std::io::Error> {
+        let config = configs::Config::global();
+
+        let blocklist = match &config.username_blocklist_path {
+            Some(path) => fs::read_to_string(path)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(Self::normalize)
+                .filter(|word| !word.is_empty())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let policy = UsernamePolicy {
+            min_length: config.username_min_length,
+            max_length: config.username_max_length,
+            allowed_symbols: config.username_allowed_symbols.chars().collect(),
+            reserved: config.username_reserved.iter().cloned().collect(),
+            blocklist,
+        };
+
+        USERNAME_POLICY.set(policy).map_err(|_| {
+            std::io::Error::other("Username policy already initialized")
+        })
+    }
+
+    pub fn global() -> &'static UsernamePolicy {
+        USERNAME_POLICY.get().expect(
+            "Username policy not initialized. Call UsernamePolicy::init() first",
+        )
+    }
+
+    /// Checks the username against the policy. Whether it is taken is up
+    /// to the caller.
+    pub fn check(&self, username: &str) -> Result<(), UsernameRejection> {
+        let length = username.chars().count();
+        if length < self.min_length {
+            return Err(UsernameRejection::TooShort);
+        }
+        if length > self.max_length {
+            return Err(UsernameRejection::TooLong);
+        }
+
+        if !username.chars().all(|c| {
+            c.is_ascii_alphanumeric() || self.allowed_symbols.contains(&c)
+        }) {
+            return Err(UsernameRejection::InvalidCharacters);
+        }
+        if !username.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Err(UsernameRejection::MustStartWithLetter);
+        }
+
+        if self.reserved.contains(&username.to_lowercase()) {
+            return Err(UsernameRejection::Reserved);
+        }
+
+        let normalized = Self::normalize(username);
+        if self.blocklist.iter().any(|word| normalized.contains(word.as_str()))
+        {
+            return Err(UsernameRejection::Blocked);
+        }
+
+        Ok(())
+    }
+
+    // Lowercase letters only, so "B4d_W0rd" compares as "badword"
+    fn normalize(value: &str) -> String {
+        value
+            .chars()
+            .filter_map(|c| match c.to_ascii_lowercase() {
+                '0' => Some('o'),
+                '1' => Some('i'),
+                '3' => Some('e'),
+                '4' | '@' => Some('a'),
+                '5' | '$' => Some('s'),
+                '7' => Some('t'),
+                c if c.is_ascii_lowercase() => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+}